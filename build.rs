@@ -1,56 +1,101 @@
 use std::error::Error;
+#[cfg(windows)]
 use std::ptr::null;
+#[cfg(windows)]
 use std::{env, fs, slice, str};
 
+#[cfg(windows)]
+use windows::core::PCSTR;
+#[cfg(windows)]
 use windows::Win32::Graphics::Direct3D::Fxc::D3DCompile;
-use windows::Win32::Graphics::Direct3D::ID3DBlob;
+#[cfg(windows)]
+use windows::Win32::Graphics::Direct3D::{ID3DBlob, D3D_SHADER_MACRO};
 
+// The shaders are only needed by the windows renderer, other targets only build
+// the platform independent parts of the crate.
+#[cfg(not(windows))]
+fn main() -> Result<(), Box<dyn Error + 'static>> {
+    Ok(())
+}
+
+#[cfg(windows)]
 fn main() -> Result<(), Box<dyn Error + 'static>> {
     static VERTEX_SHADER: &str = include_str!("src/vertex_shader.vs_4_0");
     static PIXEL_SHADER: &str = include_str!("src/pixel_shader.ps_4_0");
 
-    let mut err = None; // Never used, but left in-case inspection later is needed
-    let mut vs_blob = None;
-    let mut ps_blob = None;
-
     unsafe {
-        D3DCompile(
-            VERTEX_SHADER.as_ptr() as _,
-            VERTEX_SHADER.len(),
-            None,
-            null(),
-            None,
-            "main\0",
-            "vs_4_0\0",
-            0,
-            0,
-            &mut vs_blob,
-            &mut err,
-        )?;
-        if let Some(vs_blob) = vs_blob.as_ref() {
-            write_blob("vertex_shader.vs_4_0", vs_blob)?;
+        for (defines, shader_name) in [
+            (&[][..], "vertex_shader.vs_4_0"),
+            (&["PREMULTIPLIED_ALPHA\0"], "vertex_shader_premultiplied.vs_4_0"),
+            (&["LINEAR_COLORS\0"], "vertex_shader_linear.vs_4_0"),
+            (
+                &["LINEAR_COLORS\0", "PREMULTIPLIED_ALPHA\0"],
+                "vertex_shader_linear_premultiplied.vs_4_0",
+            ),
+        ] {
+            compile(VERTEX_SHADER, "vs_4_0\0", defines, shader_name)?;
         }
-
-        D3DCompile(
-            PIXEL_SHADER.as_ptr() as _,
-            PIXEL_SHADER.len(),
-            None,
-            null(),
-            None,
-            "main\0",
-            "ps_4_0\0",
-            0,
-            0,
-            &mut ps_blob,
-            &mut err,
-        )?;
-        if let Some(ps_blob) = ps_blob.as_ref() {
-            write_blob("pixel_shader.ps_4_0", ps_blob)?;
+        for (output_defines, output) in [
+            (&[][..], "sdr"),
+            (&["SCRGB_OUTPUT\0"], "scrgb"),
+            (&["HDR10_OUTPUT\0"], "hdr10"),
+            (&["HDR10_OUTPUT\0", "PREMULTIPLIED_ALPHA\0"], "hdr10_premultiplied"),
+        ] {
+            for (texture_defines, conversion) in [
+                (&[][..], "none"),
+                (&["PREMULTIPLY_TEXTURE\0"], "premultiply"),
+                (&["UNPREMULTIPLY_TEXTURE\0"], "unpremultiply"),
+            ] {
+                let defines: Vec<&str> =
+                    output_defines.iter().chain(texture_defines).copied().collect();
+                let shader_name = format!("pixel_shader_{}_{}.ps_4_0", output, conversion);
+                compile(PIXEL_SHADER, "ps_4_0\0", &defines, &shader_name)?;
+            }
         }
     }
     Ok(())
 }
 
+/// Compiles the `main` function of a shader with the given nul terminated
+/// defines and writes the bytecode to the output directory.
+#[cfg(windows)]
+unsafe fn compile(
+    source: &str,
+    target: &str,
+    defines: &[&str],
+    shader_name: &str,
+) -> Result<(), Box<dyn Error + 'static>> {
+    let defines: Vec<D3D_SHADER_MACRO> = defines
+        .iter()
+        .map(|name| D3D_SHADER_MACRO {
+            Name: PCSTR(name.as_ptr()),
+            Definition: PCSTR(b"1\0".as_ptr()),
+        })
+        .chain([D3D_SHADER_MACRO { Name: PCSTR(null()), Definition: PCSTR(null()) }])
+        .collect();
+
+    let mut err = None; // Never used, but left in-case inspection later is needed
+    let mut blob = None;
+    D3DCompile(
+        source.as_ptr() as _,
+        source.len(),
+        None,
+        defines.as_ptr(),
+        None,
+        "main\0",
+        target,
+        0,
+        0,
+        &mut blob,
+        &mut err,
+    )?;
+    if let Some(blob) = blob.as_ref() {
+        write_blob(shader_name, blob)?;
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
 unsafe fn write_blob(shader_name: &str, blob: &ID3DBlob) -> Result<(), Box<dyn Error + 'static>> {
     let out_dir = env::var("OUT_DIR")?;
     let data = slice::from_raw_parts(blob.GetBufferPointer().cast::<u8>(), blob.GetBufferSize());
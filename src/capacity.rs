@@ -0,0 +1,119 @@
+//! Platform independent planning of the vertex and index buffer capacities.
+//!
+//! A [`CapacityPlanner`] follows the number of elements a buffer is required
+//! to hold every frame and decides when the buffer is reallocated, growing it
+//! according to a [`GrowthPolicy`] and optionally shrinking it after a number
+//! of idle frames according to a [`ShrinkPolicy`].
+
+use crate::options::{GrowthPolicy, ShrinkPolicy};
+
+/// How often a buffer has been reallocated.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ReallocStats {
+    /// The number of reallocations growing the buffer.
+    pub grows: u64,
+    /// The number of reallocations shrinking the buffer.
+    pub shrinks: u64,
+}
+
+impl ReallocStats {
+    /// The total number of reallocations.
+    #[inline]
+    pub fn total(&self) -> u64 {
+        self.grows + self.shrinks
+    }
+}
+
+/// Plans the capacity of a buffer from the sizes it's required to hold.
+///
+/// [`CapacityPlanner::plan`] is called once per render call before the
+/// buffer is written. Should it return a capacity, the buffer is reallocated
+/// and the new capacity reported back with [`CapacityPlanner::reallocated`],
+/// so failed reallocations leave the capacity untouched.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapacityPlanner {
+    growth: GrowthPolicy,
+    shrink: Option<ShrinkPolicy>,
+    initial: usize,
+    capacity: usize,
+    /// The number of consecutive idle frames.
+    idle_frames: u32,
+    /// The largest size required during the idle frames.
+    idle_peak: usize,
+    stats: ReallocStats,
+}
+
+impl CapacityPlanner {
+    /// Creates a planner for a buffer created with `initial` elements.
+    ///
+    /// The buffer never shrinks below its initial capacity.
+    pub fn new(initial: usize, growth: GrowthPolicy, shrink: Option<ShrinkPolicy>) -> Self {
+        CapacityPlanner {
+            growth,
+            shrink,
+            initial,
+            capacity: initial,
+            idle_frames: 0,
+            idle_peak: 0,
+            stats: ReallocStats::default(),
+        }
+    }
+
+    /// The current capacity of the buffer.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// How often the buffer has been reallocated.
+    #[inline]
+    pub fn stats(&self) -> ReallocStats {
+        self.stats
+    }
+
+    /// Plans the capacity for a frame requiring `required` elements,
+    /// returning the capacity to reallocate the buffer with, if any.
+    ///
+    /// Frames requiring at most the [threshold] of the capacity count as
+    /// idle. After the configured number of consecutive idle frames the
+    /// buffer shrinks to fit the largest of them according to the growth
+    /// policy.
+    ///
+    /// [threshold]: ShrinkPolicy::threshold
+    pub fn plan(&mut self, required: usize) -> Option<usize> {
+        if required > self.capacity {
+            self.reset_idle();
+            return Some(self.growth.capacity(required, self.capacity, self.initial));
+        }
+        let shrink = self.shrink?;
+        if required as f64 > self.capacity as f64 * f64::from(shrink.threshold) {
+            self.reset_idle();
+            return None;
+        }
+        self.idle_frames += 1;
+        self.idle_peak = self.idle_peak.max(required);
+        if self.idle_frames < shrink.idle_frames {
+            return None;
+        }
+        let capacity = self.growth.capacity(self.idle_peak, self.idle_peak, self.initial);
+        // the idle window starts over whether or not the buffer shrinks
+        self.reset_idle();
+        (capacity < self.capacity).then(|| capacity)
+    }
+
+    /// Records the reallocation of the buffer with a capacity returned by
+    /// [`CapacityPlanner::plan`].
+    pub fn reallocated(&mut self, capacity: usize) {
+        if capacity > self.capacity {
+            self.stats.grows += 1;
+        } else {
+            self.stats.shrinks += 1;
+        }
+        self.capacity = capacity;
+    }
+
+    fn reset_idle(&mut self) {
+        self.idle_frames = 0;
+        self.idle_peak = 0;
+    }
+}
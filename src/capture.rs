@@ -0,0 +1,433 @@
+//! Capturing [`DrawData`] frames into a versioned binary format and replaying
+//! them.
+//!
+//! A [`FrameCapture`] owns a copy of everything a renderer reads from a
+//! [`DrawData`]: the draw lists with their vertex and index buffers and
+//! commands, as well as the display position, size and framebuffer scale. It
+//! can be serialized with [`FrameCapture::to_bytes`], loaded back with
+//! [`FrameCapture::from_bytes`] and fed to any renderer of this crate through
+//! [`FrameCapture::draw_data`].
+//!
+//! Texture ids are captured as is, so the textures a frame references have to
+//! be registered under the same ids before replaying it. User callbacks can't
+//! be serialized, they are replayed as callbacks doing nothing.
+//!
+//! # Format
+//!
+//! All values are little endian. The file starts with the magic bytes
+//! `IMGUICAP` followed by the `u32` format version, the display position,
+//! size and framebuffer scale as pairs of `f32` and the `u32` number of draw
+//! lists. Each draw list consists of
+//!
+//! - the `u32` vertex count followed by the vertices, each made up of the
+//!   position and uv as pairs of `f32` and the color as 4 bytes,
+//! - the `u32` index count followed by the indices, each a `u32`,
+//! - the `u32` command count followed by the commands, each starting with a
+//!   tag byte: `0` for elements followed by the `u32` index count, the clip
+//!   rectangle as 4 `f32`, the `u64` texture id and the `u32` vertex and index
+//!   offsets, `1` for a render state reset and `2` for a user callback.
+
+use alloc::vec::Vec;
+use core::convert::{TryFrom, TryInto};
+use core::{fmt, mem, ptr};
+
+use imgui::internal::RawCast;
+use imgui::{sys, DrawCmd, DrawCmdParams, DrawData, DrawIdx, DrawVert, TextureId};
+
+/// The magic bytes every capture starts with.
+pub const MAGIC: [u8; 8] = *b"IMGUICAP";
+/// The version of the format written by [`FrameCapture::to_bytes`].
+pub const FORMAT_VERSION: u32 = 1;
+
+const TAG_ELEMENTS: u8 = 0;
+const TAG_RESET_RENDER_STATE: u8 = 1;
+const TAG_CALLBACK: u8 = 2;
+
+/// The error returned when loading a [`FrameCapture`] fails.
+#[derive(Debug)]
+pub enum CaptureError {
+    /// The data doesn't start with [`MAGIC`].
+    InvalidMagic,
+    /// The format version is not supported by this version of the crate.
+    UnsupportedVersion(u32),
+    /// The data ended unexpectedly.
+    UnexpectedEof,
+    /// The data continues after the end of the capture.
+    TrailingData,
+    /// A command has an unknown tag.
+    InvalidCommandTag(u8),
+    /// An index doesn't fit into [`DrawIdx`].
+    IndexOverflow(u32),
+    /// A command references indices or vertices outside of its draw list.
+    OutOfBounds {
+        /// The index of the draw list.
+        draw_list: usize,
+        /// The index of the command in the draw list.
+        command: usize,
+    },
+    /// Reading or writing the capture file failed.
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::InvalidMagic => write!(f, "not a frame capture"),
+            CaptureError::UnsupportedVersion(version) => {
+                write!(f, "unsupported capture format version {}", version)
+            },
+            CaptureError::UnexpectedEof => write!(f, "unexpected end of capture data"),
+            CaptureError::TrailingData => write!(f, "trailing data after the capture"),
+            CaptureError::InvalidCommandTag(tag) => write!(f, "invalid command tag {}", tag),
+            CaptureError::IndexOverflow(idx) => write!(f, "index {} overflows DrawIdx", idx),
+            CaptureError::OutOfBounds { draw_list, command } => write!(
+                f,
+                "command {} of draw list {} is out of bounds of its buffers",
+                command, draw_list
+            ),
+            #[cfg(feature = "std")]
+            CaptureError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CaptureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CaptureError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for CaptureError {
+    fn from(e: std::io::Error) -> Self {
+        CaptureError::Io(e)
+    }
+}
+
+struct CapturedDrawList {
+    vtx_buffer: Vec<DrawVert>,
+    idx_buffer: Vec<DrawIdx>,
+    cmd_buffer: Vec<sys::ImDrawCmd>,
+}
+
+/// An owned copy of a [`DrawData`] frame.
+pub struct FrameCapture {
+    draw_lists: Vec<CapturedDrawList>,
+    // the raw imgui structures point into the buffers of `draw_lists` which
+    // are never modified after construction, the raw draw lists are only kept
+    // alive for `raw_draw_data` to point to
+    _raw_draw_lists: Vec<sys::ImDrawList>,
+    _raw_draw_list_ptrs: Vec<*mut sys::ImDrawList>,
+    raw_draw_data: sys::ImDrawData,
+}
+
+// the raw pointers only ever point into heap memory owned by the capture
+unsafe impl Send for FrameCapture {}
+unsafe impl Sync for FrameCapture {}
+
+impl FrameCapture {
+    /// Captures the given [`DrawData`].
+    pub fn capture(draw_data: &DrawData) -> Self {
+        // imgui leaves the draw list array null for frames without draw lists
+        let draw_lists = (draw_data.draw_lists_count() > 0)
+            .then(|| draw_data.draw_lists())
+            .into_iter()
+            .flatten()
+            .map(|draw_list| CapturedDrawList {
+                vtx_buffer: draw_list.vtx_buffer().to_vec(),
+                idx_buffer: draw_list.idx_buffer().to_vec(),
+                cmd_buffer: draw_list
+                    .commands()
+                    .map(|cmd| match cmd {
+                        DrawCmd::Elements { count, cmd_params } => elements_cmd(count, cmd_params),
+                        DrawCmd::ResetRenderState => callback_cmd(reset_render_state_callback()),
+                        DrawCmd::RawCallback { .. } => callback_cmd(noop_callback),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self::from_draw_lists(
+            draw_lists,
+            draw_data.display_pos,
+            draw_data.display_size,
+            draw_data.framebuffer_scale,
+        )
+    }
+
+    fn from_draw_lists(
+        mut draw_lists: Vec<CapturedDrawList>,
+        display_pos: [f32; 2],
+        display_size: [f32; 2],
+        framebuffer_scale: [f32; 2],
+    ) -> Self {
+        let mut raw_draw_lists: Vec<sys::ImDrawList> = draw_lists
+            .iter_mut()
+            .map(|draw_list| sys::ImDrawList {
+                CmdBuffer: sys::ImVector_ImDrawCmd {
+                    Size: draw_list.cmd_buffer.len() as i32,
+                    Capacity: draw_list.cmd_buffer.len() as i32,
+                    Data: draw_list.cmd_buffer.as_mut_ptr(),
+                },
+                IdxBuffer: sys::ImVector_ImDrawIdx {
+                    Size: draw_list.idx_buffer.len() as i32,
+                    Capacity: draw_list.idx_buffer.len() as i32,
+                    Data: draw_list.idx_buffer.as_mut_ptr(),
+                },
+                VtxBuffer: sys::ImVector_ImDrawVert {
+                    Size: draw_list.vtx_buffer.len() as i32,
+                    Capacity: draw_list.vtx_buffer.len() as i32,
+                    Data: draw_list.vtx_buffer.as_mut_ptr().cast(),
+                },
+                ..Default::default()
+            })
+            .collect();
+        let mut raw_draw_list_ptrs: Vec<*mut sys::ImDrawList> =
+            raw_draw_lists.iter_mut().map(|raw| raw as *mut _).collect();
+        let raw_draw_data = sys::ImDrawData {
+            Valid: true,
+            CmdListsCount: raw_draw_lists.len() as i32,
+            TotalIdxCount: draw_lists.iter().map(|list| list.idx_buffer.len() as i32).sum(),
+            TotalVtxCount: draw_lists.iter().map(|list| list.vtx_buffer.len() as i32).sum(),
+            CmdLists: raw_draw_list_ptrs.as_mut_ptr(),
+            DisplayPos: display_pos.into(),
+            DisplaySize: display_size.into(),
+            FramebufferScale: framebuffer_scale.into(),
+        };
+        FrameCapture {
+            draw_lists,
+            _raw_draw_lists: raw_draw_lists,
+            _raw_draw_list_ptrs: raw_draw_list_ptrs,
+            raw_draw_data,
+        }
+    }
+
+    /// The captured frame, ready to be rendered.
+    #[inline]
+    pub fn draw_data(&self) -> &DrawData {
+        unsafe { DrawData::from_raw(&self.raw_draw_data) }
+    }
+
+    /// Serializes the capture into the binary capture format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&MAGIC);
+        put_u32(&mut out, FORMAT_VERSION);
+        let draw_data = self.draw_data();
+        for v in [draw_data.display_pos, draw_data.display_size, draw_data.framebuffer_scale] {
+            put_f32s(&mut out, &v);
+        }
+        put_u32(&mut out, self.draw_lists.len() as u32);
+        for draw_list in &self.draw_lists {
+            put_u32(&mut out, draw_list.vtx_buffer.len() as u32);
+            for vtx in &draw_list.vtx_buffer {
+                put_f32s(&mut out, &vtx.pos);
+                put_f32s(&mut out, &vtx.uv);
+                out.extend_from_slice(&vtx.col);
+            }
+            put_u32(&mut out, draw_list.idx_buffer.len() as u32);
+            for &idx in &draw_list.idx_buffer {
+                put_u32(&mut out, u32::from(idx));
+            }
+            put_u32(&mut out, draw_list.cmd_buffer.len() as u32);
+            for cmd in &draw_list.cmd_buffer {
+                match cmd.UserCallback {
+                    None => {
+                        out.push(TAG_ELEMENTS);
+                        put_u32(&mut out, cmd.ElemCount);
+                        let clip_rect: [f32; 4] = cmd.ClipRect.into();
+                        put_f32s(&mut out, &clip_rect);
+                        out.extend_from_slice(&(cmd.TextureId as usize as u64).to_le_bytes());
+                        put_u32(&mut out, cmd.VtxOffset);
+                        put_u32(&mut out, cmd.IdxOffset);
+                    },
+                    Some(callback)
+                        if callback as usize == reset_render_state_callback() as usize =>
+                    {
+                        out.push(TAG_RESET_RENDER_STATE)
+                    },
+                    Some(_) => out.push(TAG_CALLBACK),
+                }
+            }
+        }
+        out
+    }
+
+    /// Loads a capture out of the binary capture format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CaptureError> {
+        let mut r = Reader(bytes);
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(CaptureError::InvalidMagic);
+        }
+        let version = r.u32()?;
+        if version != FORMAT_VERSION {
+            return Err(CaptureError::UnsupportedVersion(version));
+        }
+        let display_pos = r.f32_pair()?;
+        let display_size = r.f32_pair()?;
+        let framebuffer_scale = r.f32_pair()?;
+
+        let draw_list_count = r.len(mem::size_of::<u32>() * 3)?;
+        let mut draw_lists = Vec::with_capacity(draw_list_count);
+        for list_idx in 0..draw_list_count {
+            let vtx_count = r.len(20)?;
+            let mut vtx_buffer = Vec::with_capacity(vtx_count);
+            for _ in 0..vtx_count {
+                let pos = r.f32_pair()?;
+                let uv = r.f32_pair()?;
+                let col = <[u8; 4]>::try_from(r.take(4)?).unwrap();
+                vtx_buffer.push(DrawVert { pos, uv, col });
+            }
+            let idx_count = r.len(mem::size_of::<u32>())?;
+            let mut idx_buffer = Vec::with_capacity(idx_count);
+            for _ in 0..idx_count {
+                let idx = r.u32()?;
+                idx_buffer
+                    .push(DrawIdx::try_from(idx).map_err(|_| CaptureError::IndexOverflow(idx))?);
+            }
+            let cmd_count = r.len(1)?;
+            let mut cmd_buffer = Vec::with_capacity(cmd_count);
+            for cmd_idx in 0..cmd_count {
+                let cmd = match r.u8()? {
+                    TAG_ELEMENTS => {
+                        let count = r.u32()? as usize;
+                        let clip_rect = [r.f32()?, r.f32()?, r.f32()?, r.f32()?];
+                        let texture_id = TextureId::from(r.u64()? as usize);
+                        let vtx_offset = r.u32()? as usize;
+                        let idx_offset = r.u32()? as usize;
+                        let in_bounds = idx_offset
+                            .checked_add(count)
+                            .and_then(|end| idx_buffer.get(idx_offset..end))
+                            .map_or(false, |indices| {
+                                indices.iter().all(|&idx| vtx_offset + (idx as usize) < vtx_count)
+                            });
+                        if !in_bounds {
+                            return Err(CaptureError::OutOfBounds {
+                                draw_list: list_idx,
+                                command: cmd_idx,
+                            });
+                        }
+                        let cmd_params =
+                            DrawCmdParams { clip_rect, texture_id, vtx_offset, idx_offset };
+                        elements_cmd(count, cmd_params)
+                    },
+                    TAG_RESET_RENDER_STATE => callback_cmd(reset_render_state_callback()),
+                    TAG_CALLBACK => callback_cmd(noop_callback),
+                    tag => return Err(CaptureError::InvalidCommandTag(tag)),
+                };
+                cmd_buffer.push(cmd);
+            }
+            draw_lists.push(CapturedDrawList { vtx_buffer, idx_buffer, cmd_buffer });
+        }
+        if !r.0.is_empty() {
+            return Err(CaptureError::TrailingData);
+        }
+        Ok(Self::from_draw_lists(draw_lists, display_pos, display_size, framebuffer_scale))
+    }
+
+    /// Writes the capture to the file at `path`.
+    #[cfg(feature = "std")]
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    /// Loads a capture from the file at `path`.
+    #[cfg(feature = "std")]
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, CaptureError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+impl fmt::Debug for FrameCapture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let draw_data = self.draw_data();
+        f.debug_struct("FrameCapture")
+            .field("display_pos", &draw_data.display_pos)
+            .field("display_size", &draw_data.display_size)
+            .field("framebuffer_scale", &draw_data.framebuffer_scale)
+            .field("draw_lists", &self.draw_lists.len())
+            .field("total_vtx_count", &draw_data.total_vtx_count)
+            .field("total_idx_count", &draw_data.total_idx_count)
+            .finish()
+    }
+}
+
+type RawCallbackFn = unsafe extern "C" fn(*const sys::ImDrawList, *const sys::ImDrawCmd);
+
+unsafe extern "C" fn noop_callback(_: *const sys::ImDrawList, _: *const sys::ImDrawCmd) {}
+
+fn reset_render_state_callback() -> RawCallbackFn {
+    // imgui's ImDrawCallback_ResetRenderState, the callback is never invoked
+    unsafe { mem::transmute::<isize, RawCallbackFn>(-1) }
+}
+
+fn elements_cmd(count: usize, cmd_params: DrawCmdParams) -> sys::ImDrawCmd {
+    sys::ImDrawCmd {
+        ClipRect: cmd_params.clip_rect.into(),
+        TextureId: cmd_params.texture_id.id() as sys::ImTextureID,
+        VtxOffset: cmd_params.vtx_offset as u32,
+        IdxOffset: cmd_params.idx_offset as u32,
+        ElemCount: count as u32,
+        UserCallback: None,
+        UserCallbackData: ptr::null_mut(),
+    }
+}
+
+fn callback_cmd(callback: RawCallbackFn) -> sys::ImDrawCmd {
+    sys::ImDrawCmd { UserCallback: Some(callback), ..Default::default() }
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_f32s(out: &mut Vec<u8>, v: &[f32]) {
+    v.iter().for_each(|v| out.extend_from_slice(&v.to_le_bytes()));
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], CaptureError> {
+        if self.0.len() < n {
+            return Err(CaptureError::UnexpectedEof);
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, CaptureError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, CaptureError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, CaptureError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, CaptureError> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32_pair(&mut self) -> Result<[f32; 2], CaptureError> {
+        Ok([self.f32()?, self.f32()?])
+    }
+
+    /// Reads an element count, rejecting counts the remaining data can't hold
+    /// at `min_size` bytes per element before allocating for them.
+    fn len(&mut self, min_size: usize) -> Result<usize, CaptureError> {
+        let len = self.u32()? as usize;
+        if len.saturating_mul(min_size) > self.0.len() {
+            return Err(CaptureError::UnexpectedEof);
+        }
+        Ok(len)
+    }
+}
@@ -0,0 +1,204 @@
+//! Color spaces of render targets and the transfer functions converting
+//! between them.
+//!
+//! Imgui specifies all colors, vertex colors and style colors alike, as sRGB
+//! encoded values. Rendering them as is is correct for `UNORM` render
+//! targets, while `_SRGB` render targets expect linear colors they encode on
+//! write and HDR swapchains expect colors scaled to a brightness in nits.
+//! The functions in here are the CPU counterparts of the conversions the
+//! shaders do, [`output_color`] mirrors the output stage of the pixel
+//! shader.
+
+use crate::texture::AlphaMode;
+
+/// The brightness of 1.0 in scRGB, in nits.
+pub const SCRGB_WHITE_NITS: f32 = 80.0;
+
+/// The default brightness of white UI elements in HDR color spaces, the
+/// reference white of ITU-R BT.2408 in nits.
+pub const DEFAULT_PAPER_WHITE_NITS: f32 = 203.0;
+
+/// The brightness of the maximum PQ signal, in nits.
+pub const PQ_MAX_NITS: f32 = 10000.0;
+
+/// The color space of the render target the renderer draws into.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// The render target stores sRGB encoded colors as written, like
+    /// `R8G8B8A8_UNORM` targets.
+    Srgb,
+    /// The render target encodes linear colors to sRGB on write, like
+    /// `R8G8B8A8_UNORM_SRGB` targets.
+    ///
+    /// Vertex colors are converted to linear and the font texture is sampled
+    /// through an sRGB view.
+    Linear,
+    /// The render target is an scRGB swapchain, like `R16G16B16A16_FLOAT`
+    /// with `DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709`.
+    ///
+    /// Colors are linear Rec. 709 scaled so white is output at the paper
+    /// white level, with 1.0 being [`SCRGB_WHITE_NITS`].
+    ScRgb,
+    /// The render target is an HDR10 swapchain, like `R10G10B10A2_UNORM`
+    /// with `DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020`.
+    ///
+    /// Colors are converted to Rec. 2020, scaled so white is output at the
+    /// paper white level and PQ encoded. Blending happens on the encoded
+    /// values.
+    Hdr10,
+}
+
+impl Default for ColorSpace {
+    fn default() -> Self {
+        ColorSpace::Srgb
+    }
+}
+
+impl ColorSpace {
+    /// Whether colors are rendered linear, that is everything but
+    /// [`ColorSpace::Srgb`].
+    #[inline]
+    pub const fn is_linear(self) -> bool {
+        !matches!(self, ColorSpace::Srgb)
+    }
+}
+
+/// Converts an sRGB encoded color channel in `0.0..=1.0` to linear.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        powf((c + 0.055) / 1.055, 2.4)
+    }
+}
+
+/// Converts a linear color channel in `0.0..=1.0` to sRGB encoding.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * powf(c, 1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts the color channels of an sRGB encoded RGBA color to linear,
+/// leaving alpha as is.
+pub fn srgb_color_to_linear([r, g, b, a]: [f32; 4]) -> [f32; 4] {
+    [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+}
+
+/// Encodes a brightness in nits with the SMPTE ST 2084 (PQ) inverse EOTF.
+///
+/// Brightnesses are clamped to `0.0..=PQ_MAX_NITS`.
+pub fn pq_encode(nits: f32) -> f32 {
+    let y_m1 = powf((nits / PQ_MAX_NITS).clamp(0.0, 1.0), PQ_M1);
+    powf((PQ_C1 + PQ_C2 * y_m1) / (1.0 + PQ_C3 * y_m1), PQ_M2)
+}
+
+/// Decodes a PQ signal in `0.0..=1.0` to a brightness in nits with the
+/// SMPTE ST 2084 EOTF.
+pub fn pq_decode(signal: f32) -> f32 {
+    let e = powf(signal.clamp(0.0, 1.0), 1.0 / PQ_M2);
+    let y = (e - PQ_C1).max(0.0) / (PQ_C2 - PQ_C3 * e);
+    powf(y, 1.0 / PQ_M1) * PQ_MAX_NITS
+}
+
+const PQ_M1: f32 = 2610.0 / 16384.0;
+const PQ_M2: f32 = 2523.0 / 4096.0 * 128.0;
+const PQ_C1: f32 = 3424.0 / 4096.0;
+const PQ_C2: f32 = 2413.0 / 4096.0 * 32.0;
+const PQ_C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+/// Converts a linear Rec. 709 color to linear Rec. 2020.
+pub fn rec709_to_rec2020([r, g, b]: [f32; 3]) -> [f32; 3] {
+    REC709_TO_REC2020.map(|[m0, m1, m2]| m0 * r + m1 * g + m2 * b)
+}
+
+/// The conversion matrix from Rec. 709 to Rec. 2020 primaries, row major.
+const REC709_TO_REC2020: [[f32; 3]; 3] = [
+    [0.627_403_9, 0.329_283_04, 0.043_313_067],
+    [0.069_097_29, 0.919_540_4, 0.011_362_316],
+    [0.016_391_44, 0.088_013_31, 0.895_595_3],
+];
+
+/// Converts a linear Rec. 709 color to the encoding of the color space, the
+/// way the pixel shader outputs its colors.
+///
+/// `alpha` is the alpha mode of the color, `paper_white` the brightness of
+/// white in nits for the HDR color spaces.
+pub fn output_color(
+    color: [f32; 4],
+    color_space: ColorSpace,
+    alpha: AlphaMode,
+    paper_white: f32,
+) -> [f32; 4] {
+    let [r, g, b, a] = color;
+    match color_space {
+        ColorSpace::Srgb | ColorSpace::Linear => color,
+        ColorSpace::ScRgb => {
+            let scale = paper_white / SCRGB_WHITE_NITS;
+            [r * scale, g * scale, b * scale, a]
+        },
+        ColorSpace::Hdr10 => {
+            // PQ isn't linear, premultiplied colors are encoded unpremultiplied
+            let coverage = match alpha {
+                AlphaMode::Straight => 1.0,
+                AlphaMode::Premultiplied if a > 0.0 => a,
+                AlphaMode::Premultiplied => return [0.0; 4],
+            };
+            let rgb = rec709_to_rec2020([r / coverage, g / coverage, b / coverage]);
+            let [r, g, b] = rgb.map(|c| pq_encode(c.max(0.0) * paper_white) * coverage);
+            [r, g, b, a]
+        },
+    }
+}
+
+/// `x` raised to the power of `y` for `x >= 0.0` and `y > 0.0`.
+///
+/// `core` has no floating point powers, this computes `2^(y * log2(x))`
+/// with series expansions accurate to about 1e-7, plenty for 8 and 16 bit
+/// color channels and the steep exponents of PQ.
+fn powf(x: f32, y: f32) -> f32 {
+    // subnormals aren't handled by log2, their powers are close to zero
+    if x < f32::MIN_POSITIVE {
+        return 0.0;
+    }
+    exp2(y * log2(x))
+}
+
+fn log2(x: f32) -> f32 {
+    // x = m * 2^e with m in 1.0..2.0
+    let bits = x.to_bits();
+    let exponent = ((bits >> 23) & 0xFF) as i32 - 127;
+    let m = f32::from_bits((bits & 0x007F_FFFF) | 0x3F80_0000);
+    // ln(m) = 2 * atanh(t) with t = (m - 1) / (m + 1) in 0.0..1/3
+    let t = (m - 1.0) / (m + 1.0);
+    let t2 = t * t;
+    let mut series = 1.0 / 15.0;
+    for n in [13.0, 11.0, 9.0, 7.0, 5.0, 3.0, 1.0] {
+        series = 1.0 / n + t2 * series;
+    }
+    let ln_m = 2.0 * t * series;
+    exponent as f32 + ln_m * core::f32::consts::LOG2_E
+}
+
+fn exp2(y: f32) -> f32 {
+    if y < -126.0 {
+        return 0.0;
+    } else if y >= 128.0 {
+        return f32::INFINITY;
+    }
+    // y = n + f with f in 0.0..1.0
+    let mut n = y as i32;
+    if (n as f32) > y {
+        n -= 1;
+    }
+    let f = (y - n as f32) * core::f32::consts::LN_2;
+    let mut term = 1.0;
+    let mut exp_f = 1.0;
+    for i in 1..=8 {
+        term *= f / i as f32;
+        exp_f += term;
+    }
+    exp_f * f32::from_bits(((n + 127) as u32) << 23)
+}
@@ -0,0 +1,436 @@
+//! Parsing of DDS files holding block-compressed or uncompressed 2D textures.
+//!
+//! [`DdsTexture::parse`] reads the header, either with the DX10 extension or
+//! a legacy FourCC or RGB pixel format, and splits the pixel data into the
+//! subresources of every array slice and mip level. The parsed texture is
+//! uploaded as is with [`Renderer::create_dds_texture`], without
+//! decompressing it.
+//!
+//! Supported are the BC1 to BC7 formats as well as 8 bit RGBA and BGRA,
+//! with or without alpha, for plain textures and texture arrays with any
+//! number of mip levels. Cube maps, volume textures and 1D textures are
+//! rejected.
+//!
+//! [`Renderer::create_dds_texture`]: crate::Renderer::create_dds_texture
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::fmt;
+
+use crate::texture::MAX_TEXTURE_DIMENSION;
+
+/// The magic bytes every DDS file starts with.
+pub const MAGIC: [u8; 4] = *b"DDS ";
+/// The maximum number of slices of a texture array, the d3d11 limit.
+pub const MAX_ARRAY_SIZE: u32 = 2048;
+
+const HEADER_SIZE: usize = 124;
+const DX10_HEADER_SIZE: usize = 20;
+
+const DDSD_MIPMAPCOUNT: u32 = 0x2_0000;
+const DDSD_DEPTH: u32 = 0x80_0000;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x20_0000;
+const RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+const RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+/// The pixel format of a DDS texture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DdsFormat {
+    /// BC1, also known as DXT1.
+    Bc1Unorm,
+    /// BC1 with sRGB colors.
+    Bc1UnormSrgb,
+    /// BC2, also known as DXT2 and DXT3.
+    Bc2Unorm,
+    /// BC2 with sRGB colors.
+    Bc2UnormSrgb,
+    /// BC3, also known as DXT4 and DXT5.
+    Bc3Unorm,
+    /// BC3 with sRGB colors.
+    Bc3UnormSrgb,
+    /// BC4 with an unsigned channel.
+    Bc4Unorm,
+    /// BC4 with a signed channel.
+    Bc4Snorm,
+    /// BC5 with unsigned channels.
+    Bc5Unorm,
+    /// BC5 with signed channels.
+    Bc5Snorm,
+    /// BC6H with unsigned half floats.
+    Bc6hUf16,
+    /// BC6H with signed half floats.
+    Bc6hSf16,
+    /// BC7.
+    Bc7Unorm,
+    /// BC7 with sRGB colors.
+    Bc7UnormSrgb,
+    /// 8 bit red, green, blue and alpha channels.
+    Rgba8Unorm,
+    /// 8 bit sRGB red, green, blue and linear alpha channels.
+    Rgba8UnormSrgb,
+    /// 8 bit blue, green, red and alpha channels.
+    Bgra8Unorm,
+    /// 8 bit sRGB blue, green, red and linear alpha channels.
+    Bgra8UnormSrgb,
+    /// 8 bit blue, green and red channels and an unused byte.
+    Bgrx8Unorm,
+    /// 8 bit sRGB blue, green and red channels and an unused byte.
+    Bgrx8UnormSrgb,
+    /// 8 bit red, green and blue channels and an unused byte, only found in
+    /// legacy files.
+    ///
+    /// d3d11 lacks a matching format, the texture is uploaded as
+    /// [`DdsFormat::Rgba8Unorm`] with opaque alpha, see
+    /// [`DdsTexture::upload_data`].
+    Rgbx8Unorm,
+}
+
+impl DdsFormat {
+    const ALL: [DdsFormat; 21] = [
+        DdsFormat::Bc1Unorm,
+        DdsFormat::Bc1UnormSrgb,
+        DdsFormat::Bc2Unorm,
+        DdsFormat::Bc2UnormSrgb,
+        DdsFormat::Bc3Unorm,
+        DdsFormat::Bc3UnormSrgb,
+        DdsFormat::Bc4Unorm,
+        DdsFormat::Bc4Snorm,
+        DdsFormat::Bc5Unorm,
+        DdsFormat::Bc5Snorm,
+        DdsFormat::Bc6hUf16,
+        DdsFormat::Bc6hSf16,
+        DdsFormat::Bc7Unorm,
+        DdsFormat::Bc7UnormSrgb,
+        DdsFormat::Rgba8Unorm,
+        DdsFormat::Rgba8UnormSrgb,
+        DdsFormat::Bgra8Unorm,
+        DdsFormat::Bgra8UnormSrgb,
+        DdsFormat::Bgrx8Unorm,
+        DdsFormat::Bgrx8UnormSrgb,
+        // last, so the DXGI format maps back to Rgba8Unorm
+        DdsFormat::Rgbx8Unorm,
+    ];
+
+    /// The numeric value of the corresponding `DXGI_FORMAT`.
+    pub const fn dxgi_format(self) -> u32 {
+        match self {
+            DdsFormat::Bc1Unorm => 71,
+            DdsFormat::Bc1UnormSrgb => 72,
+            DdsFormat::Bc2Unorm => 74,
+            DdsFormat::Bc2UnormSrgb => 75,
+            DdsFormat::Bc3Unorm => 77,
+            DdsFormat::Bc3UnormSrgb => 78,
+            DdsFormat::Bc4Unorm => 80,
+            DdsFormat::Bc4Snorm => 81,
+            DdsFormat::Bc5Unorm => 83,
+            DdsFormat::Bc5Snorm => 84,
+            DdsFormat::Bc6hUf16 => 95,
+            DdsFormat::Bc6hSf16 => 96,
+            DdsFormat::Bc7Unorm => 98,
+            DdsFormat::Bc7UnormSrgb => 99,
+            DdsFormat::Rgba8Unorm => 28,
+            DdsFormat::Rgba8UnormSrgb => 29,
+            DdsFormat::Bgra8Unorm => 87,
+            DdsFormat::Bgra8UnormSrgb => 91,
+            DdsFormat::Bgrx8Unorm => 88,
+            DdsFormat::Bgrx8UnormSrgb => 93,
+            DdsFormat::Rgbx8Unorm => 28,
+        }
+    }
+
+    /// The format with the given numeric `DXGI_FORMAT` value.
+    pub fn from_dxgi_format(format: u32) -> Option<Self> {
+        Self::ALL.iter().copied().find(|f| f.dxgi_format() == format)
+    }
+
+    /// The size in bytes of a 4x4 block of a block-compressed format.
+    pub const fn block_size(self) -> Option<usize> {
+        match self {
+            DdsFormat::Bc1Unorm
+            | DdsFormat::Bc1UnormSrgb
+            | DdsFormat::Bc4Unorm
+            | DdsFormat::Bc4Snorm => Some(8),
+            DdsFormat::Rgba8Unorm
+            | DdsFormat::Rgba8UnormSrgb
+            | DdsFormat::Bgra8Unorm
+            | DdsFormat::Bgra8UnormSrgb
+            | DdsFormat::Bgrx8Unorm
+            | DdsFormat::Bgrx8UnormSrgb
+            | DdsFormat::Rgbx8Unorm => None,
+            _ => Some(16),
+        }
+    }
+
+    /// Whether the format is block-compressed.
+    #[inline]
+    pub const fn is_block_compressed(self) -> bool {
+        self.block_size().is_some()
+    }
+
+    /// The row pitch and the size in bytes of a surface of the given size.
+    ///
+    /// Rows of block-compressed formats are rows of 4x4 blocks.
+    pub const fn surface_pitch(self, width: u32, height: u32) -> (usize, usize) {
+        match self.block_size() {
+            Some(block_size) => {
+                let row_pitch = (width as usize + 3) / 4 * block_size;
+                (row_pitch, row_pitch * ((height as usize + 3) / 4))
+            },
+            None => (width as usize * 4, width as usize * 4 * height as usize),
+        }
+    }
+}
+
+/// The size of a mip level of a texture dimension.
+#[inline]
+pub const fn mip_dimension(size: u32, level: u32) -> u32 {
+    match size.checked_shr(level) {
+        Some(0) | None => 1,
+        Some(size) => size,
+    }
+}
+
+/// The number of levels of a full mip chain of a texture of the given size.
+#[inline]
+pub const fn full_mip_count(width: u32, height: u32) -> u32 {
+    let size = if width > height { width } else { height };
+    32 - size.leading_zeros()
+}
+
+/// A surface of a single mip level of an array slice.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Subresource<'a> {
+    /// The mip level of the surface.
+    pub mip_level: u32,
+    /// The array slice of the surface.
+    pub array_slice: u32,
+    /// The width of the surface.
+    pub width: u32,
+    /// The height of the surface.
+    pub height: u32,
+    /// The size of a row of pixels or blocks in bytes.
+    pub row_pitch: usize,
+    /// The pixel data of the surface.
+    pub data: &'a [u8],
+}
+
+/// A 2D texture or texture array parsed from a DDS file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DdsTexture<'a> {
+    /// The width of the top mip level.
+    pub width: u32,
+    /// The height of the top mip level.
+    pub height: u32,
+    /// The number of mip levels.
+    pub mip_levels: u32,
+    /// The number of array slices, `1` for plain textures.
+    pub array_size: u32,
+    /// The pixel format.
+    pub format: DdsFormat,
+    subresources: Vec<Subresource<'a>>,
+}
+
+impl<'a> DdsTexture<'a> {
+    /// Parses a DDS file, borrowing its pixel data.
+    ///
+    /// Data following the last subresource is ignored.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, DdsError> {
+        if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+            return Err(DdsError::InvalidMagic);
+        }
+        let header = bytes.get(4..4 + HEADER_SIZE).ok_or(DdsError::UnexpectedEof)?;
+        let field = |offset: usize| u32_at(header, offset);
+        if field(0) as usize != HEADER_SIZE || field(72) != 32 {
+            return Err(DdsError::InvalidHeader);
+        }
+        let flags = field(4);
+        let (height, width) = (field(8), field(12));
+        let mip_count = field(24);
+        let (pf_flags, four_cc) = (field(76), field(80));
+        let caps2 = field(108);
+        if caps2 & (DDSCAPS2_CUBEMAP | DDSCAPS2_VOLUME) != 0 || flags & DDSD_DEPTH != 0 {
+            return Err(DdsError::UnsupportedTexture);
+        }
+
+        let mut offset = 4 + HEADER_SIZE;
+        let (format, array_size) = if pf_flags & DDPF_FOURCC != 0 && four_cc == fourcc(b"DX10") {
+            let dx10 =
+                bytes.get(offset..offset + DX10_HEADER_SIZE).ok_or(DdsError::UnexpectedEof)?;
+            offset += DX10_HEADER_SIZE;
+            let dxgi_format = u32_at(dx10, 0);
+            if u32_at(dx10, 4) != RESOURCE_DIMENSION_TEXTURE2D
+                || u32_at(dx10, 8) & RESOURCE_MISC_TEXTURECUBE != 0
+            {
+                return Err(DdsError::UnsupportedTexture);
+            }
+            let format = DdsFormat::from_dxgi_format(dxgi_format)
+                .ok_or(DdsError::UnsupportedFormat(dxgi_format))?;
+            (format, u32_at(dx10, 12))
+        } else if pf_flags & DDPF_FOURCC != 0 {
+            (legacy_fourcc_format(four_cc).ok_or(DdsError::UnsupportedFormat(four_cc))?, 1)
+        } else if pf_flags & DDPF_RGB != 0 {
+            let masks = [field(84), field(88), field(92), field(96), field(100)];
+            (legacy_rgb_format(pf_flags, masks).ok_or(DdsError::UnsupportedFormat(0))?, 1)
+        } else {
+            return Err(DdsError::UnsupportedFormat(0));
+        };
+
+        if width == 0
+            || height == 0
+            || width > MAX_TEXTURE_DIMENSION
+            || height > MAX_TEXTURE_DIMENSION
+            || array_size == 0
+            || array_size > MAX_ARRAY_SIZE
+            // d3d11 requires whole blocks for the top level of block-compressed
+            // textures
+            || (format.is_block_compressed() && (width % 4 != 0 || height % 4 != 0))
+        {
+            return Err(DdsError::InvalidDimensions { width, height, array_size });
+        }
+        let mip_levels = if flags & DDSD_MIPMAPCOUNT != 0 { mip_count.max(1) } else { 1 };
+        if mip_levels > full_mip_count(width, height) {
+            return Err(DdsError::InvalidMipCount(mip_levels));
+        }
+
+        // subresources are stored slice by slice, each with its full mip chain
+        let mut subresources = Vec::with_capacity((array_size * mip_levels) as usize);
+        for array_slice in 0..array_size {
+            for mip_level in 0..mip_levels {
+                let width = mip_dimension(width, mip_level);
+                let height = mip_dimension(height, mip_level);
+                let (row_pitch, size) = format.surface_pitch(width, height);
+                let data = bytes.get(offset..offset + size).ok_or(DdsError::UnexpectedEof)?;
+                offset += size;
+                subresources.push(Subresource {
+                    mip_level,
+                    array_slice,
+                    width,
+                    height,
+                    row_pitch,
+                    data,
+                });
+            }
+        }
+        Ok(DdsTexture { width, height, mip_levels, array_size, format, subresources })
+    }
+
+    /// The subresources in d3d11 subresource order, the mip levels of the
+    /// first array slice followed by those of the next.
+    #[inline]
+    pub fn subresources(&self) -> &[Subresource<'a>] {
+        &self.subresources
+    }
+
+    /// The pixel data of the subresources the way devices upload it, in the
+    /// order of [`DdsTexture::subresources`].
+    ///
+    /// The data is borrowed, except for [`DdsFormat::Rgbx8Unorm`] textures
+    /// whose unused bytes are replaced by opaque alpha.
+    pub fn upload_data(&self) -> Vec<Cow<'a, [u8]>> {
+        self.subresources
+            .iter()
+            .map(|sub| match self.format {
+                DdsFormat::Rgbx8Unorm => {
+                    let mut data = sub.data.to_vec();
+                    data.chunks_exact_mut(4).for_each(|texel| texel[3] = 0xFF);
+                    Cow::Owned(data)
+                },
+                _ => Cow::Borrowed(sub.data),
+            })
+            .collect()
+    }
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*code)
+}
+
+fn legacy_fourcc_format(four_cc: u32) -> Option<DdsFormat> {
+    let format = match &four_cc.to_le_bytes() {
+        b"DXT1" => DdsFormat::Bc1Unorm,
+        b"DXT2" | b"DXT3" => DdsFormat::Bc2Unorm,
+        b"DXT4" | b"DXT5" => DdsFormat::Bc3Unorm,
+        b"ATI1" | b"BC4U" => DdsFormat::Bc4Unorm,
+        b"BC4S" => DdsFormat::Bc4Snorm,
+        b"ATI2" | b"BC5U" => DdsFormat::Bc5Unorm,
+        b"BC5S" => DdsFormat::Bc5Snorm,
+        _ => return None,
+    };
+    Some(format)
+}
+
+fn legacy_rgb_format(pf_flags: u32, [bit_count, r, g, b, a]: [u32; 5]) -> Option<DdsFormat> {
+    // the unused byte of formats without alpha is undefined, often zero
+    let alpha = match (pf_flags & DDPF_ALPHAPIXELS != 0, a) {
+        (true, 0xFF00_0000) => true,
+        (true, _) => return None,
+        (false, _) => false,
+    };
+    match (bit_count, r, g, b, alpha) {
+        (32, 0xFF, 0xFF00, 0xFF_0000, true) => Some(DdsFormat::Rgba8Unorm),
+        (32, 0xFF, 0xFF00, 0xFF_0000, false) => Some(DdsFormat::Rgbx8Unorm),
+        (32, 0xFF_0000, 0xFF00, 0xFF, true) => Some(DdsFormat::Bgra8Unorm),
+        (32, 0xFF_0000, 0xFF00, 0xFF, false) => Some(DdsFormat::Bgrx8Unorm),
+        _ => None,
+    }
+}
+
+/// The error returned when parsing a DDS file fails.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DdsError {
+    /// The data doesn't start with [`MAGIC`].
+    InvalidMagic,
+    /// The header has an invalid size field.
+    InvalidHeader,
+    /// The data ended before the last subresource.
+    UnexpectedEof,
+    /// The pixel format is not supported, carrying the `DXGI_FORMAT`, the
+    /// FourCC code or `0` for unsupported RGB formats.
+    UnsupportedFormat(u32),
+    /// The file holds a cube map, a volume or a 1D texture.
+    UnsupportedTexture,
+    /// The texture is empty, exceeds the d3d11 limits or isn't made of whole
+    /// 4x4 blocks for a block-compressed format.
+    InvalidDimensions {
+        /// The width of the texture.
+        width: u32,
+        /// The height of the texture.
+        height: u32,
+        /// The number of array slices.
+        array_size: u32,
+    },
+    /// The texture has more mip levels than a full mip chain.
+    InvalidMipCount(u32),
+}
+
+impl fmt::Display for DdsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            DdsError::InvalidMagic => write!(f, "not a DDS file"),
+            DdsError::InvalidHeader => write!(f, "invalid DDS header"),
+            DdsError::UnexpectedEof => write!(f, "unexpected end of DDS data"),
+            DdsError::UnsupportedFormat(format) => {
+                write!(f, "unsupported DDS pixel format {:#x}", format)
+            },
+            DdsError::UnsupportedTexture => {
+                write!(f, "only 2D textures and texture arrays are supported")
+            },
+            DdsError::InvalidDimensions { width, height, array_size } => {
+                write!(f, "invalid texture dimensions {}x{}x{}", width, height, array_size)
+            },
+            DdsError::InvalidMipCount(count) => write!(f, "invalid mip level count {}", count),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DdsError {}
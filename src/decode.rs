@@ -0,0 +1,118 @@
+//! Decoding of PNG, JPEG and BMP images into RGBA8 texture data.
+//!
+//! Images are decoded with the pure Rust [`image`] crate and converted to
+//! tightly packed RGBA8 pixels, optionally premultiplying the color channels
+//! by alpha. [`Renderer::load_image_texture`] decodes an image file and
+//! registers it as a texture in one go.
+//!
+//! [`Renderer::load_image_texture`]: crate::Renderer::load_image_texture
+
+use std::error::Error;
+use std::path::Path;
+use std::{fmt, fs, io};
+
+use alloc::vec::Vec;
+
+pub use crate::texture::{premultiply_alpha, AlphaMode};
+
+/// A decoded image of tightly packed RGBA8 pixels.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedImage {
+    /// The width of the image.
+    pub width: u32,
+    /// The height of the image.
+    pub height: u32,
+    /// The pixels of the image, row by row.
+    pub pixels: Vec<u8>,
+}
+
+/// Decodes a PNG, JPEG or BMP image, detecting the format from its contents.
+pub fn decode_image(bytes: &[u8], alpha: AlphaMode) -> Result<DecodedImage, DecodeError> {
+    let image = image::load_from_memory(bytes)?.into_rgba8();
+    let (width, height) = image.dimensions();
+    let mut pixels = image.into_raw();
+    if alpha == AlphaMode::Premultiplied {
+        premultiply_alpha(&mut pixels);
+    }
+    Ok(DecodedImage { width, height, pixels })
+}
+
+/// Reads and decodes a PNG, JPEG or BMP image file.
+pub fn load_image(path: &Path, alpha: AlphaMode) -> Result<DecodedImage, DecodeError> {
+    decode_image(&fs::read(path)?, alpha)
+}
+
+/// The error returned when an image can't be decoded.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// Reading the image file failed.
+    Io(io::Error),
+    /// The image is malformed or in an unsupported format.
+    Image(image::ImageError),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Io(e) => e.fmt(f),
+            DecodeError::Image(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for DecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DecodeError::Io(e) => Some(e),
+            DecodeError::Image(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(e: io::Error) -> Self {
+        DecodeError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for DecodeError {
+    fn from(e: image::ImageError) -> Self {
+        DecodeError::Image(e)
+    }
+}
+
+/// The error returned by [`Renderer::load_image_texture`], generic over the
+/// device's error type.
+///
+/// [`Renderer::load_image_texture`]: crate::Renderer::load_image_texture
+#[derive(Debug)]
+pub enum LoadTextureError<E> {
+    /// The image couldn't be decoded.
+    Decode(DecodeError),
+    /// Creating the texture failed.
+    Device(E),
+}
+
+impl<E: fmt::Display> fmt::Display for LoadTextureError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadTextureError::Decode(e) => e.fmt(f),
+            LoadTextureError::Device(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for LoadTextureError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadTextureError::Decode(e) => Some(e),
+            LoadTextureError::Device(e) => Some(e),
+        }
+    }
+}
+
+impl<E> From<DecodeError> for LoadTextureError<E> {
+    fn from(e: DecodeError) -> Self {
+        LoadTextureError::Decode(e)
+    }
+}
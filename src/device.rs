@@ -0,0 +1,427 @@
+//! The abstraction over the graphics device the [`Renderer`] issues its
+//! commands to.
+//!
+//! The [`Device`] trait covers exactly the calls the renderer makes. The
+//! d3d11 implementation lives in `Dx11Device`, the `mock` module, enabled by
+//! the `mock` feature, offers a platform independent implementation
+//! recording all calls.
+//!
+//! [`Renderer`]: crate::Renderer
+
+use crate::color::ColorSpace;
+use crate::dds::DdsTexture;
+use crate::effects::EffectError;
+use crate::plan::{ScissorRect, UnknownTexture};
+use crate::texture::{AlphaMode, SamplerDesc, TextureDesc, TextureError, TextureRect};
+use crate::timing::TimestampData;
+use crate::viewports::UnknownViewport;
+
+/// The kind of a buffer created by [`Device::create_buffer`].
+///
+/// All buffers are dynamic and written by the CPU through [`Device::map`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BufferKind {
+    /// A vertex buffer.
+    Vertex,
+    /// An index buffer.
+    Index,
+    /// A constant buffer.
+    Constant,
+}
+
+/// The format of the indices of an index buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IndexFormat {
+    /// 16 bit unsigned indices.
+    U16,
+    /// 32 bit unsigned indices.
+    U32,
+}
+
+/// A viewport in render target pixels, spanning the depth range `0.0..=1.0`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Viewport {
+    /// The left edge of the viewport.
+    pub x: f32,
+    /// The top edge of the viewport.
+    pub y: f32,
+    /// The width of the viewport.
+    pub width: f32,
+    /// The height of the viewport.
+    pub height: f32,
+}
+
+/// How triangles are filled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FillMode {
+    /// The triangles are filled.
+    Solid,
+    /// Only the edges of the triangles are drawn, for debugging.
+    Wireframe,
+}
+
+impl Default for FillMode {
+    fn default() -> Self {
+        FillMode::Solid
+    }
+}
+
+/// Which triangles are culled.
+///
+/// Imgui emits triangles of both windings, culling is only useful for
+/// geometry drawn by callbacks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CullMode {
+    /// No triangles are culled.
+    None,
+    /// Front facing, clockwise, triangles are culled.
+    Front,
+    /// Back facing, counterclockwise, triangles are culled.
+    Back,
+}
+
+impl Default for CullMode {
+    fn default() -> Self {
+        CullMode::None
+    }
+}
+
+/// The rasterizer state of a pipeline.
+///
+/// Scissor testing is always enabled, clip rectangles rely on it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RasterizerDesc {
+    /// How triangles are filled.
+    pub fill_mode: FillMode,
+    /// Which triangles are culled.
+    pub cull_mode: CullMode,
+    /// Whether fragments outside of the viewport's depth range are clipped.
+    pub depth_clip: bool,
+}
+
+impl Default for RasterizerDesc {
+    fn default() -> Self {
+        RasterizerDesc { fill_mode: FillMode::Solid, cull_mode: CullMode::None, depth_clip: true }
+    }
+}
+
+/// A function comparing a fragment's depth against the stored depth.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CompareFunc {
+    /// The comparison never passes.
+    Never,
+    /// Passes if the fragment is closer.
+    Less,
+    /// Passes if the depths are equal.
+    Equal,
+    /// Passes if the fragment is closer or as close.
+    LessEqual,
+    /// Passes if the fragment is farther away.
+    Greater,
+    /// Passes if the depths differ.
+    NotEqual,
+    /// Passes if the fragment is farther away or as far.
+    GreaterEqual,
+    /// The comparison always passes.
+    Always,
+}
+
+/// The depth stencil state of a pipeline.
+///
+/// Imgui vertices are drawn at depth 0.5, tested against the depth stencil
+/// view bound by the host. Stencil testing is always disabled.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DepthStencilDesc {
+    /// The depth test fragments have to pass, `None` to disable depth
+    /// testing.
+    pub depth_test: Option<CompareFunc>,
+    /// Whether fragments passing the depth test write their depth, requires
+    /// a depth test.
+    pub depth_write: bool,
+}
+
+/// The configuration of a pipeline created by [`Device::create_pipeline`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PipelineDesc {
+    /// The alpha mode colors are blended and output in.
+    pub alpha: AlphaMode,
+    /// The color space of the render targets drawn into.
+    pub color_space: ColorSpace,
+    /// The rasterizer state.
+    pub rasterizer: RasterizerDesc,
+    /// The depth stencil state.
+    pub depth_stencil: DepthStencilDesc,
+}
+
+/// How much of the device state the [`Renderer`] preserves around a render
+/// call.
+///
+/// [`Renderer`]: crate::Renderer
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StatePolicy {
+    /// Captures every slot of the state the renderer could touch and applies
+    /// it afterwards.
+    Full,
+    /// Captures and applies only the state the frame's [`DrawPlan`]
+    /// touches.
+    ///
+    /// [`DrawPlan`]: crate::plan::DrawPlan
+    Touched,
+    /// Leaves the renderer's state bound, for hosts resetting their state
+    /// themselves.
+    None,
+}
+
+impl Default for StatePolicy {
+    fn default() -> Self {
+        StatePolicy::Full
+    }
+}
+
+/// The device state a render call touches, as derived by
+/// [`DrawPlan::touched_state`].
+///
+/// Slot counts start at the first slot. [`TouchedState::ALL`] covers every
+/// slot, devices clamp the counts to their limits.
+///
+/// [`DrawPlan::touched_state`]: crate::plan::DrawPlan::touched_state
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TouchedState {
+    /// The render targets and depth stencil view.
+    pub render_targets: bool,
+    /// The viewports.
+    pub viewports: bool,
+    /// The scissor rectangles.
+    pub scissor_rects: bool,
+    /// The shaders of every stage, the input layout, the primitive topology
+    /// and the blend, depth stencil and rasterizer states.
+    pub pipeline: bool,
+    /// The index buffer.
+    pub index_buffer: bool,
+    /// The number of vertex buffer slots.
+    pub vertex_buffers: u32,
+    /// The number of vertex shader constant buffer slots.
+    pub vs_constant_buffers: u32,
+    /// The number of pixel shader constant buffer slots.
+    pub ps_constant_buffers: u32,
+    /// The number of pixel shader texture slots.
+    pub ps_textures: u32,
+    /// The number of pixel shader sampler slots.
+    pub ps_samplers: u32,
+}
+
+impl TouchedState {
+    /// Every piece of state, with every slot.
+    pub const ALL: TouchedState = TouchedState {
+        render_targets: true,
+        viewports: true,
+        scissor_rects: true,
+        pipeline: true,
+        index_buffer: true,
+        vertex_buffers: u32::MAX,
+        vs_constant_buffers: u32::MAX,
+        ps_constant_buffers: u32::MAX,
+        ps_textures: u32::MAX,
+        ps_samplers: u32::MAX,
+    };
+
+    /// Whether no state is touched at all.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        *self == TouchedState::default()
+    }
+}
+
+/// A graphics device the [`Renderer`] can render with.
+///
+/// [`Renderer`]: crate::Renderer
+pub trait Device {
+    /// The error returned by failing device calls.
+    type Error: From<UnknownTexture>
+        + From<TextureError>
+        + From<EffectError>
+        + From<UnknownViewport>;
+    /// A buffer living on the device.
+    type Buffer;
+    /// A texture view that can be bound to the pixel shader.
+    type Texture: Clone;
+    /// A sampler state that can be bound to the pixel shader.
+    type Sampler;
+    /// A render target the renderer can draw into.
+    type RenderTarget;
+    /// The shaders and fixed function state the renderer draws with.
+    type Pipeline;
+    /// A pixel shader replacing the pipeline's default pixel shader.
+    type PixelShader;
+    /// A snapshot of device state, see [`Device::capture_state`].
+    type StateBlock;
+    /// A query measuring the GPU time between two points, see
+    /// [`Device::begin_timer_query`].
+    type TimerQuery;
+
+    /// Creates the pipeline the renderer draws with.
+    ///
+    /// The vertex colors reaching the pixel shader are converted to the color
+    /// space and, for premultiplied alpha, premultiplied.
+    fn create_pipeline(&mut self, desc: &PipelineDesc) -> Result<Self::Pipeline, Self::Error>;
+
+    /// Creates a pixel shader out of compiled shader bytecode.
+    fn create_pixel_shader(&mut self, bytecode: &[u8]) -> Result<Self::PixelShader, Self::Error>;
+
+    /// Creates a dynamic, CPU writable buffer of `size` bytes.
+    fn create_buffer(&mut self, kind: BufferKind, size: usize)
+        -> Result<Self::Buffer, Self::Error>;
+
+    /// Creates a texture out of tightly packed pixel data.
+    ///
+    /// The data has been validated against the description.
+    fn create_texture(
+        &mut self,
+        desc: &TextureDesc,
+        data: &[u8],
+    ) -> Result<Self::Texture, Self::Error>;
+
+    /// Creates a texture with every array slice and mip level of a DDS file,
+    /// keeping the pixel data in its block-compressed format.
+    fn create_dds_texture(&mut self, dds: &DdsTexture<'_>) -> Result<Self::Texture, Self::Error>;
+
+    /// Overwrites a rectangle of a texture created with the given description
+    /// with tightly packed pixel data.
+    ///
+    /// The rectangle and data have been validated against the description.
+    fn update_texture(
+        &mut self,
+        texture: &Self::Texture,
+        desc: &TextureDesc,
+        rect: TextureRect,
+        data: &[u8],
+    ) -> Result<(), Self::Error>;
+
+    /// Creates a sampler state.
+    fn create_sampler(&mut self, desc: &SamplerDesc) -> Result<Self::Sampler, Self::Error>;
+
+    /// Maps the buffer for writing, discarding its previous contents.
+    ///
+    /// The returned pointer is valid for writes of the buffer's size until
+    /// [`Device::unmap`] is called.
+    fn map(&mut self, buffer: &Self::Buffer) -> Result<*mut u8, Self::Error>;
+
+    /// Maps a vertex or index buffer for writing, keeping its previous
+    /// contents.
+    ///
+    /// The renderer only writes ranges no draw issued since the last
+    /// [`Device::map`] reads. The returned pointer is valid for writes of the
+    /// buffer's size until [`Device::unmap`] is called.
+    fn map_no_overwrite(&mut self, buffer: &Self::Buffer) -> Result<*mut u8, Self::Error>;
+
+    /// Unmaps a buffer previously mapped with [`Device::map`] or
+    /// [`Device::map_no_overwrite`].
+    fn unmap(&mut self, buffer: &Self::Buffer);
+
+    /// The size of the render target in pixels.
+    fn render_target_size(&mut self, target: &Self::RenderTarget) -> Result<[u32; 2], Self::Error>;
+
+    /// Binds the render target as the only render target, without a depth
+    /// stencil view.
+    fn set_render_target(&mut self, target: &Self::RenderTarget);
+
+    /// Clears the render target to the given color.
+    fn clear_render_target(&mut self, target: &Self::RenderTarget, color: [f32; 4]);
+
+    /// Sets the viewport.
+    fn set_viewport(&mut self, viewport: Viewport);
+
+    /// Binds the shaders and fixed function state of the pipeline and unbinds
+    /// all other shader stages.
+    ///
+    /// The bound pixel shader samples textures in the pipeline's alpha mode.
+    fn set_pipeline(&mut self, pipeline: &Self::Pipeline);
+
+    /// Binds the pixel shader, or for `None` the default pixel shader of the
+    /// pipeline converting textures in `texture_alpha` mode to the pipeline's
+    /// alpha mode.
+    fn set_pixel_shader(
+        &mut self,
+        pipeline: &Self::Pipeline,
+        shader: Option<&Self::PixelShader>,
+        texture_alpha: AlphaMode,
+    );
+
+    /// Binds the vertex buffer with the given vertex stride.
+    fn set_vertex_buffer(&mut self, buffer: &Self::Buffer, stride: u32);
+
+    /// Binds the index buffer with the given index format.
+    fn set_index_buffer(&mut self, buffer: &Self::Buffer, format: IndexFormat);
+
+    /// Binds the constant buffer of the vertex shader, which is also bound to
+    /// the second slot of the pixel shader.
+    fn set_constant_buffer(&mut self, buffer: &Self::Buffer);
+
+    /// Binds the first constant buffer of the pixel shader.
+    fn set_pixel_constant_buffer(&mut self, buffer: &Self::Buffer);
+
+    /// Binds the texture of the pixel shader.
+    fn set_texture(&mut self, texture: &Self::Texture);
+
+    /// Binds the sampler of the pixel shader.
+    fn set_sampler(&mut self, sampler: &Self::Sampler);
+
+    /// Sets the scissor rectangle.
+    fn set_scissor_rect(&mut self, rect: ScissorRect);
+
+    /// Draws indexed triangles.
+    fn draw_indexed(&mut self, index_count: u32, start_index: u32, base_vertex: i32);
+
+    /// Captures the touched device state.
+    fn capture_state(&mut self, touched: &TouchedState) -> Self::StateBlock;
+
+    /// Applies a state captured by [`Device::capture_state`], leaving state
+    /// not captured as is.
+    fn apply_state(&mut self, state: &Self::StateBlock);
+
+    /// Creates a timer query.
+    fn create_timer_query(&mut self) -> Result<Self::TimerQuery, Self::Error>;
+
+    /// Begins measuring the GPU time of the following commands.
+    fn begin_timer_query(&mut self, query: &Self::TimerQuery);
+
+    /// Ends measuring the GPU time of the commands issued since
+    /// [`Device::begin_timer_query`].
+    fn end_timer_query(&mut self, query: &Self::TimerQuery);
+
+    /// Reads back the timestamps of an ended query without waiting for the
+    /// GPU, `None` while they're not available yet.
+    fn timer_query_data(&mut self, query: &Self::TimerQuery) -> Option<TimestampData>;
+}
+
+/// A [`Device`] able to present to native windows, as required for rendering
+/// secondary viewports.
+pub trait SurfaceDevice: Device {
+    /// A native window handle.
+    type Window;
+    /// A presentable surface of a window, like a swapchain.
+    type Surface;
+
+    /// Creates a surface of `size` pixels presenting to the window.
+    fn create_surface(
+        &mut self,
+        window: &Self::Window,
+        size: [u32; 2],
+    ) -> Result<Self::Surface, Self::Error>;
+
+    /// Resizes the surface, invalidating its previous render target.
+    fn resize_surface(
+        &mut self,
+        surface: &mut Self::Surface,
+        size: [u32; 2],
+    ) -> Result<(), Self::Error>;
+
+    /// The render target of the surface's current back buffer.
+    ///
+    /// Returns `None` if the surface lost its render target due to a failed
+    /// resize.
+    fn surface_target(surface: &Self::Surface) -> Option<&Self::RenderTarget>;
+
+    /// Presents the surface's back buffer to its window.
+    fn present(&mut self, surface: &Self::Surface) -> Result<(), Self::Error>;
+}
@@ -0,0 +1,1076 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+use core::ptr::{null, null_mut};
+
+use windows::core::*;
+use windows::Win32::Foundation::{E_INVALIDARG, E_POINTER, HWND, RECT, S_OK};
+use windows::Win32::Graphics::Direct3D::*;
+use windows::Win32::Graphics::Direct3D11::*;
+use windows::Win32::Graphics::Dxgi::Common::*;
+use windows::Win32::Graphics::Dxgi::*;
+
+use crate::color::ColorSpace;
+use crate::dds::DdsTexture;
+use crate::device::{
+    BufferKind, CompareFunc, CullMode, Device, FillMode, IndexFormat, PipelineDesc, SurfaceDevice,
+    TouchedState, Viewport,
+};
+use crate::effects::EffectError;
+use crate::plan::{ScissorRect, UnknownTexture};
+use crate::texture::{
+    AddressMode, AlphaMode, Filter, SamplerDesc, TextureDesc, TextureError, TextureFormat,
+    TextureRect,
+};
+use crate::timing::TimestampData;
+use crate::viewports::UnknownViewport;
+use crate::Renderer;
+
+impl Renderer<Dx11Device> {
+    /// Creates a new renderer for the given [`ID3D11Device`].
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid [`ID3D11Device`] pointer.
+    ///
+    /// [`ID3D11Device`]: https://docs.rs/winapi/0.3/x86_64-pc-windows-msvc/winapi/um/d3d11/struct.ID3D11Device.html
+    pub unsafe fn new(im_ctx: &mut imgui::Context, device: &ID3D11Device) -> Result<Self> {
+        Self::with_device(im_ctx, Dx11Device::new(device))
+    }
+}
+
+impl From<UnknownTexture> for Error {
+    fn from(_: UnknownTexture) -> Self {
+        DXGI_ERROR_INVALID_CALL.into()
+    }
+}
+
+impl From<EffectError> for Error {
+    fn from(err: EffectError) -> Self {
+        match err {
+            EffectError::UnknownEffect(_) => DXGI_ERROR_INVALID_CALL.into(),
+            EffectError::DataSizeMismatch { .. } => E_INVALIDARG.into(),
+        }
+    }
+}
+
+impl From<UnknownViewport> for Error {
+    fn from(_: UnknownViewport) -> Self {
+        DXGI_ERROR_INVALID_CALL.into()
+    }
+}
+
+impl From<TextureError> for Error {
+    fn from(err: TextureError) -> Self {
+        match err {
+            TextureError::UnknownTexture(_) | TextureError::Unmanaged(_) => {
+                DXGI_ERROR_INVALID_CALL.into()
+            },
+            _ => E_INVALIDARG.into(),
+        }
+    }
+}
+
+fn dxgi_format(format: TextureFormat) -> DXGI_FORMAT {
+    match format {
+        TextureFormat::Rgba8 => DXGI_FORMAT_R8G8B8A8_UNORM,
+        TextureFormat::Bgra8 => DXGI_FORMAT_B8G8R8A8_UNORM,
+        TextureFormat::Rgba8Srgb => DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+        TextureFormat::Bgra8Srgb => DXGI_FORMAT_B8G8R8A8_UNORM_SRGB,
+        TextureFormat::Rgba16Float => DXGI_FORMAT_R16G16B16A16_FLOAT,
+        TextureFormat::Rgba32Float => DXGI_FORMAT_R32G32B32A32_FLOAT,
+    }
+}
+
+/// The d3d11 [`Device`], issuing commands to the immediate context of an
+/// [`ID3D11Device`].
+#[derive(Debug, Clone)]
+pub struct Dx11Device {
+    device: ID3D11Device,
+    context: ID3D11DeviceContext,
+}
+
+impl Dx11Device {
+    /// Creates a new device for the given [`ID3D11Device`].
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid [`ID3D11Device`] pointer.
+    pub unsafe fn new(device: &ID3D11Device) -> Self {
+        let mut context = None;
+        device.GetImmediateContext(&mut context);
+        Dx11Device { device: device.clone(), context: context.unwrap() }
+    }
+
+    /// The underlying [`ID3D11Device`].
+    #[inline]
+    pub fn device(&self) -> &ID3D11Device {
+        &self.device
+    }
+
+    /// The immediate context commands are issued to.
+    #[inline]
+    pub fn context(&self) -> &ID3D11DeviceContext {
+        &self.context
+    }
+
+    unsafe fn create_vertex_shader(
+        &self,
+        desc: &PipelineDesc,
+    ) -> Result<(ID3D11VertexShader, ID3D11InputLayout)> {
+        const VERTEX_SHADER: &[u8] =
+            include_bytes!(concat!(env!("OUT_DIR"), "/vertex_shader.vs_4_0"));
+        const PREMULTIPLIED_VERTEX_SHADER: &[u8] =
+            include_bytes!(concat!(env!("OUT_DIR"), "/vertex_shader_premultiplied.vs_4_0"));
+        const LINEAR_VERTEX_SHADER: &[u8] =
+            include_bytes!(concat!(env!("OUT_DIR"), "/vertex_shader_linear.vs_4_0"));
+        const LINEAR_PREMULTIPLIED_VERTEX_SHADER: &[u8] =
+            include_bytes!(concat!(env!("OUT_DIR"), "/vertex_shader_linear_premultiplied.vs_4_0"));
+        let bytecode = match (desc.color_space.is_linear(), desc.alpha) {
+            (false, AlphaMode::Straight) => VERTEX_SHADER,
+            (false, AlphaMode::Premultiplied) => PREMULTIPLIED_VERTEX_SHADER,
+            (true, AlphaMode::Straight) => LINEAR_VERTEX_SHADER,
+            (true, AlphaMode::Premultiplied) => LINEAR_PREMULTIPLIED_VERTEX_SHADER,
+        };
+        let vs_shader = self.device.CreateVertexShader(bytecode, None)?;
+
+        let local_layout = [
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: PCSTR(b"POSITION\0".as_ptr()),
+                SemanticIndex: 0,
+                Format: DXGI_FORMAT_R32G32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 0,
+                InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
+                InstanceDataStepRate: 0,
+            },
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: PCSTR(b"TEXCOORD\0".as_ptr()),
+                SemanticIndex: 0,
+                Format: DXGI_FORMAT_R32G32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 8,
+                InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
+                InstanceDataStepRate: 0,
+            },
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: PCSTR(b"COLOR\0".as_ptr()),
+                SemanticIndex: 0,
+                Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                InputSlot: 0,
+                AlignedByteOffset: 16,
+                InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
+                InstanceDataStepRate: 0,
+            },
+        ];
+
+        let input_layout = self.device.CreateInputLayout(&local_layout, bytecode)?;
+        Ok((vs_shader, input_layout))
+    }
+
+    /// Creates the default pixel shader and the one converting textures of
+    /// the other alpha mode to `alpha`.
+    unsafe fn create_default_pixel_shaders(
+        &self,
+        desc: &PipelineDesc,
+    ) -> Result<(ID3D11PixelShader, ID3D11PixelShader)> {
+        macro_rules! pixel_shaders {
+            ($output:literal) => {
+                [
+                    include_bytes!(concat!(
+                        env!("OUT_DIR"),
+                        "/pixel_shader_",
+                        $output,
+                        "_none.ps_4_0"
+                    )) as &[u8],
+                    include_bytes!(concat!(
+                        env!("OUT_DIR"),
+                        "/pixel_shader_",
+                        $output,
+                        "_premultiply.ps_4_0"
+                    )),
+                    include_bytes!(concat!(
+                        env!("OUT_DIR"),
+                        "/pixel_shader_",
+                        $output,
+                        "_unpremultiply.ps_4_0"
+                    )),
+                ]
+            };
+        }
+        // the shaders sampling textures as they are, premultiplying and
+        // unpremultiplying them, per output encoding
+        const SDR_PIXEL_SHADERS: [&[u8]; 3] = pixel_shaders!("sdr");
+        const SCRGB_PIXEL_SHADERS: [&[u8]; 3] = pixel_shaders!("scrgb");
+        const HDR10_PIXEL_SHADERS: [&[u8]; 3] = pixel_shaders!("hdr10");
+        const HDR10_PREMULTIPLIED_PIXEL_SHADERS: [&[u8]; 3] = pixel_shaders!("hdr10_premultiplied");
+
+        let [matching, premultiply, unpremultiply] = match (desc.color_space, desc.alpha) {
+            (ColorSpace::Srgb | ColorSpace::Linear, _) => SDR_PIXEL_SHADERS,
+            (ColorSpace::ScRgb, _) => SCRGB_PIXEL_SHADERS,
+            (ColorSpace::Hdr10, AlphaMode::Straight) => HDR10_PIXEL_SHADERS,
+            (ColorSpace::Hdr10, AlphaMode::Premultiplied) => HDR10_PREMULTIPLIED_PIXEL_SHADERS,
+        };
+        let converting = match desc.alpha {
+            AlphaMode::Straight => unpremultiply,
+            AlphaMode::Premultiplied => premultiply,
+        };
+        Ok((
+            self.device.CreatePixelShader(matching, None)?,
+            self.device.CreatePixelShader(converting, None)?,
+        ))
+    }
+
+    unsafe fn create_device_objects(
+        &self,
+        pipeline_desc: &PipelineDesc,
+    ) -> Result<(ID3D11BlendState, ID3D11RasterizerState, ID3D11DepthStencilState)> {
+        let src_blend = match pipeline_desc.alpha {
+            AlphaMode::Straight => D3D11_BLEND_SRC_ALPHA,
+            AlphaMode::Premultiplied => D3D11_BLEND_ONE,
+        };
+        let desc = D3D11_BLEND_DESC {
+            AlphaToCoverageEnable: false.into(),
+            IndependentBlendEnable: true.into(),
+            RenderTarget: [D3D11_RENDER_TARGET_BLEND_DESC {
+                BlendEnable: true.into(),
+                SrcBlend: src_blend,
+                DestBlend: D3D11_BLEND_INV_SRC_ALPHA,
+                BlendOp: D3D11_BLEND_OP_ADD,
+                SrcBlendAlpha: D3D11_BLEND_ONE,
+                DestBlendAlpha: D3D11_BLEND_INV_SRC_ALPHA,
+                BlendOpAlpha: D3D11_BLEND_OP_ADD,
+                RenderTargetWriteMask: D3D11_COLOR_WRITE_ENABLE_ALL.0 as u8,
+            }; 8],
+        };
+        let blend_state = self.device.CreateBlendState(&desc)?;
+
+        let rasterizer = pipeline_desc.rasterizer;
+        let desc = D3D11_RASTERIZER_DESC {
+            FillMode: match rasterizer.fill_mode {
+                FillMode::Solid => D3D11_FILL_SOLID,
+                FillMode::Wireframe => D3D11_FILL_WIREFRAME,
+            },
+            CullMode: match rasterizer.cull_mode {
+                CullMode::None => D3D11_CULL_NONE,
+                CullMode::Front => D3D11_CULL_FRONT,
+                CullMode::Back => D3D11_CULL_BACK,
+            },
+            DepthClipEnable: rasterizer.depth_clip.into(),
+            ScissorEnable: true.into(),
+            ..Default::default()
+        };
+        let rasterizer_state = self.device.CreateRasterizerState(&desc)?;
+
+        let stencil_op_desc = D3D11_DEPTH_STENCILOP_DESC {
+            StencilFailOp: D3D11_STENCIL_OP_KEEP,
+            StencilDepthFailOp: D3D11_STENCIL_OP_KEEP,
+            StencilPassOp: D3D11_STENCIL_OP_KEEP,
+            StencilFunc: D3D11_COMPARISON_ALWAYS,
+        };
+        let depth_stencil = pipeline_desc.depth_stencil;
+        let desc = D3D11_DEPTH_STENCIL_DESC {
+            DepthEnable: depth_stencil.depth_test.is_some().into(),
+            DepthWriteMask: if depth_stencil.depth_write {
+                D3D11_DEPTH_WRITE_MASK_ALL
+            } else {
+                D3D11_DEPTH_WRITE_MASK_ZERO
+            },
+            DepthFunc: depth_stencil.depth_test.map_or(D3D11_COMPARISON_ALWAYS, comparison_func),
+            StencilEnable: false.into(),
+            StencilReadMask: 0,
+            StencilWriteMask: 0,
+            FrontFace: stencil_op_desc,
+            BackFace: stencil_op_desc,
+        };
+        let depth_stencil_state = self.device.CreateDepthStencilState(&desc)?;
+        Ok((blend_state, rasterizer_state, depth_stencil_state))
+    }
+}
+
+fn comparison_func(func: CompareFunc) -> D3D11_COMPARISON_FUNC {
+    match func {
+        CompareFunc::Never => D3D11_COMPARISON_NEVER,
+        CompareFunc::Less => D3D11_COMPARISON_LESS,
+        CompareFunc::Equal => D3D11_COMPARISON_EQUAL,
+        CompareFunc::LessEqual => D3D11_COMPARISON_LESS_EQUAL,
+        CompareFunc::Greater => D3D11_COMPARISON_GREATER,
+        CompareFunc::NotEqual => D3D11_COMPARISON_NOT_EQUAL,
+        CompareFunc::GreaterEqual => D3D11_COMPARISON_GREATER_EQUAL,
+        CompareFunc::Always => D3D11_COMPARISON_ALWAYS,
+    }
+}
+
+/// The shaders and fixed function state of the d3d11 renderer.
+#[derive(Debug, Clone)]
+pub struct Dx11Pipeline {
+    alpha: AlphaMode,
+    vertex_shader: ID3D11VertexShader,
+    pixel_shader: ID3D11PixelShader,
+    /// The pixel shader converting textures of the other alpha mode.
+    converting_pixel_shader: ID3D11PixelShader,
+    input_layout: ID3D11InputLayout,
+    blend_state: ID3D11BlendState,
+    rasterizer_state: ID3D11RasterizerState,
+    depth_stencil_state: ID3D11DepthStencilState,
+}
+
+/// A disjoint query along with the timestamp queries at the beginning and end
+/// of the measured commands.
+#[derive(Debug, Clone)]
+pub struct Dx11TimerQuery {
+    disjoint: ID3D11Query,
+    begin: ID3D11Query,
+    end: ID3D11Query,
+}
+
+/// Reads back the data of a query without waiting for it, `None` while the
+/// data isn't available.
+unsafe fn query_data<T: Default>(context: &ID3D11DeviceContext, query: &ID3D11Query) -> Option<T> {
+    let mut data = T::default();
+    // the generated wrapper maps S_FALSE, returned while the data isn't
+    // available, to Ok
+    let hr = (Interface::vtable(context).GetData)(
+        mem::transmute_copy(context),
+        mem::transmute_copy(query),
+        (&mut data as *mut T).cast(),
+        mem::size_of::<T>() as u32,
+        0,
+    );
+    (hr == S_OK).then(|| data)
+}
+
+impl Device for Dx11Device {
+    type Error = Error;
+    type Buffer = ID3D11Buffer;
+    type Texture = ID3D11ShaderResourceView;
+    type Sampler = ID3D11SamplerState;
+    type RenderTarget = ID3D11RenderTargetView;
+    type Pipeline = Dx11Pipeline;
+    type PixelShader = ID3D11PixelShader;
+    type StateBlock = StateBlock;
+    type TimerQuery = Dx11TimerQuery;
+
+    fn create_pipeline(&mut self, desc: &PipelineDesc) -> Result<Dx11Pipeline> {
+        let alpha = desc.alpha;
+        unsafe {
+            let (vertex_shader, input_layout) = self.create_vertex_shader(desc)?;
+            let (pixel_shader, converting_pixel_shader) =
+                self.create_default_pixel_shaders(desc)?;
+            let (blend_state, rasterizer_state, depth_stencil_state) =
+                self.create_device_objects(desc)?;
+            Ok(Dx11Pipeline {
+                alpha,
+                vertex_shader,
+                pixel_shader,
+                converting_pixel_shader,
+                input_layout,
+                blend_state,
+                rasterizer_state,
+                depth_stencil_state,
+            })
+        }
+    }
+
+    fn create_pixel_shader(&mut self, bytecode: &[u8]) -> Result<ID3D11PixelShader> {
+        unsafe { self.device.CreatePixelShader(bytecode, None) }
+    }
+
+    fn create_buffer(&mut self, kind: BufferKind, size: usize) -> Result<ID3D11Buffer> {
+        let bind_flags = match kind {
+            BufferKind::Vertex => D3D11_BIND_VERTEX_BUFFER,
+            BufferKind::Index => D3D11_BIND_INDEX_BUFFER,
+            BufferKind::Constant => D3D11_BIND_CONSTANT_BUFFER,
+        };
+        let desc = D3D11_BUFFER_DESC {
+            ByteWidth: size as u32,
+            Usage: D3D11_USAGE_DYNAMIC,
+            BindFlags: bind_flags.0,
+            CPUAccessFlags: D3D11_CPU_ACCESS_WRITE.0,
+            MiscFlags: 0,
+            StructureByteStride: 0,
+        };
+
+        unsafe { self.device.CreateBuffer(&desc, null()) }
+    }
+
+    fn create_texture(
+        &mut self,
+        desc: &TextureDesc,
+        data: &[u8],
+    ) -> Result<ID3D11ShaderResourceView> {
+        let format = dxgi_format(desc.format);
+        let tex_desc = D3D11_TEXTURE2D_DESC {
+            Width: desc.width,
+            Height: desc.height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: format,
+            SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE,
+            ..Default::default()
+        };
+        let sub_resource = D3D11_SUBRESOURCE_DATA {
+            pSysMem: data.as_ptr().cast(),
+            SysMemPitch: desc.row_pitch() as u32,
+            SysMemSlicePitch: 0,
+        };
+
+        unsafe {
+            let texture: ID3D11Texture2D = self.device.CreateTexture2D(&tex_desc, &sub_resource)?;
+            let mut srv_desc = D3D11_SHADER_RESOURCE_VIEW_DESC {
+                Format: format,
+                ViewDimension: D3D11_SRV_DIMENSION_TEXTURE2D,
+                ..Default::default()
+            };
+            srv_desc.Anonymous.Texture2D.MipLevels = tex_desc.MipLevels;
+            srv_desc.Anonymous.Texture2D.MostDetailedMip = 0;
+            self.device.CreateShaderResourceView(texture, &srv_desc)
+        }
+    }
+
+    fn create_dds_texture(&mut self, dds: &DdsTexture<'_>) -> Result<ID3D11ShaderResourceView> {
+        let format = DXGI_FORMAT(dds.format.dxgi_format());
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width: dds.width,
+            Height: dds.height,
+            MipLevels: dds.mip_levels,
+            ArraySize: dds.array_size,
+            Format: format,
+            SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+            Usage: D3D11_USAGE_IMMUTABLE,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE,
+            ..Default::default()
+        };
+        let data = dds.upload_data();
+        let sub_resources: Vec<D3D11_SUBRESOURCE_DATA> = dds
+            .subresources()
+            .iter()
+            .zip(&data)
+            .map(|(sub, data)| D3D11_SUBRESOURCE_DATA {
+                pSysMem: data.as_ptr().cast(),
+                SysMemPitch: sub.row_pitch as u32,
+                SysMemSlicePitch: data.len() as u32,
+            })
+            .collect();
+
+        unsafe {
+            let texture: ID3D11Texture2D =
+                self.device.CreateTexture2D(&desc, sub_resources.as_ptr())?;
+            let mut srv_desc =
+                D3D11_SHADER_RESOURCE_VIEW_DESC { Format: format, ..Default::default() };
+            if dds.array_size > 1 {
+                srv_desc.ViewDimension = D3D11_SRV_DIMENSION_TEXTURE2DARRAY;
+                srv_desc.Anonymous.Texture2DArray = D3D11_TEX2D_ARRAY_SRV {
+                    MostDetailedMip: 0,
+                    MipLevels: dds.mip_levels,
+                    FirstArraySlice: 0,
+                    ArraySize: dds.array_size,
+                };
+            } else {
+                srv_desc.ViewDimension = D3D11_SRV_DIMENSION_TEXTURE2D;
+                srv_desc.Anonymous.Texture2D =
+                    D3D11_TEX2D_SRV { MostDetailedMip: 0, MipLevels: dds.mip_levels };
+            }
+            self.device.CreateShaderResourceView(texture, &srv_desc)
+        }
+    }
+
+    fn update_texture(
+        &mut self,
+        texture: &ID3D11ShaderResourceView,
+        desc: &TextureDesc,
+        rect: TextureRect,
+        data: &[u8],
+    ) -> Result<()> {
+        let region = D3D11_BOX {
+            left: rect.x,
+            top: rect.y,
+            front: 0,
+            right: rect.x + rect.width,
+            bottom: rect.y + rect.height,
+            back: 1,
+        };
+        let row_pitch = rect.width as usize * desc.format.bytes_per_pixel();
+        unsafe {
+            let mut resource = None;
+            texture.GetResource(&mut resource);
+            let resource = resource.ok_or_else(|| Error::from(E_POINTER))?;
+            self.context.UpdateSubresource(
+                resource,
+                0,
+                &region,
+                data.as_ptr().cast(),
+                row_pitch as u32,
+                0,
+            );
+        }
+        Ok(())
+    }
+
+    fn create_sampler(&mut self, desc: &SamplerDesc) -> Result<ID3D11SamplerState> {
+        let (filter, max_anisotropy) = match desc.filter {
+            Filter::Point => (D3D11_FILTER_MIN_MAG_MIP_POINT, 0),
+            Filter::Linear => (D3D11_FILTER_MIN_MAG_MIP_LINEAR, 0),
+            Filter::Anisotropic(max) => (D3D11_FILTER_ANISOTROPIC, u32::from(max.clamp(1, 16))),
+        };
+        let address_mode = match desc.address_mode {
+            AddressMode::Clamp => D3D11_TEXTURE_ADDRESS_CLAMP,
+            AddressMode::Wrap => D3D11_TEXTURE_ADDRESS_WRAP,
+            AddressMode::Mirror => D3D11_TEXTURE_ADDRESS_MIRROR,
+            AddressMode::Border => D3D11_TEXTURE_ADDRESS_BORDER,
+        };
+        let desc = D3D11_SAMPLER_DESC {
+            Filter: filter,
+            AddressU: address_mode,
+            AddressV: address_mode,
+            AddressW: address_mode,
+            MipLODBias: 0.0,
+            MaxAnisotropy: max_anisotropy,
+            ComparisonFunc: D3D11_COMPARISON_ALWAYS,
+            BorderColor: [0.0; 4],
+            MinLOD: 0.0,
+            MaxLOD: D3D11_FLOAT32_MAX,
+        };
+        unsafe { self.device.CreateSamplerState(&desc) }
+    }
+
+    fn map(&mut self, buffer: &ID3D11Buffer) -> Result<*mut u8> {
+        unsafe {
+            let resource: D3D11_MAPPED_SUBRESOURCE =
+                self.context.Map(buffer, 0, D3D11_MAP_WRITE_DISCARD, 0)?;
+            Ok(resource.pData.cast())
+        }
+    }
+
+    fn map_no_overwrite(&mut self, buffer: &ID3D11Buffer) -> Result<*mut u8> {
+        unsafe {
+            let resource: D3D11_MAPPED_SUBRESOURCE =
+                self.context.Map(buffer, 0, D3D11_MAP_WRITE_NO_OVERWRITE, 0)?;
+            Ok(resource.pData.cast())
+        }
+    }
+
+    fn unmap(&mut self, buffer: &ID3D11Buffer) {
+        unsafe { self.context.Unmap(buffer, 0) }
+    }
+
+    fn render_target_size(&mut self, target: &ID3D11RenderTargetView) -> Result<[u32; 2]> {
+        unsafe {
+            let mut view_desc = D3D11_RENDER_TARGET_VIEW_DESC::default();
+            target.GetDesc(&mut view_desc);
+            let mip_slice = match view_desc.ViewDimension {
+                D3D11_RTV_DIMENSION_TEXTURE2D => view_desc.Anonymous.Texture2D.MipSlice,
+                D3D11_RTV_DIMENSION_TEXTURE2DARRAY => view_desc.Anonymous.Texture2DArray.MipSlice,
+                D3D11_RTV_DIMENSION_TEXTURE2DMS | D3D11_RTV_DIMENSION_TEXTURE2DMSARRAY => 0,
+                _ => return Err(E_INVALIDARG.into()),
+            };
+            let mut resource = None;
+            target.GetResource(&mut resource);
+            let texture: ID3D11Texture2D =
+                resource.ok_or_else(|| Error::from(E_POINTER))?.cast()?;
+            let mut desc = D3D11_TEXTURE2D_DESC::default();
+            texture.GetDesc(&mut desc);
+            Ok([(desc.Width >> mip_slice).max(1), (desc.Height >> mip_slice).max(1)])
+        }
+    }
+
+    fn set_render_target(&mut self, target: &ID3D11RenderTargetView) {
+        unsafe { self.context.OMSetRenderTargets(&[Some(target.clone())], None) }
+    }
+
+    fn clear_render_target(&mut self, target: &ID3D11RenderTargetView, color: [f32; 4]) {
+        unsafe { self.context.ClearRenderTargetView(target, color.as_ptr()) }
+    }
+
+    fn set_viewport(&mut self, viewport: Viewport) {
+        let vp = D3D11_VIEWPORT {
+            TopLeftX: viewport.x,
+            TopLeftY: viewport.y,
+            Width: viewport.width,
+            Height: viewport.height,
+            MinDepth: 0.0,
+            MaxDepth: 1.0,
+        };
+        unsafe { self.context.RSSetViewports(&[vp]) }
+    }
+
+    fn set_pipeline(&mut self, pipeline: &Dx11Pipeline) {
+        let ctx = &self.context;
+        let blend_factor = 0.0;
+        unsafe {
+            ctx.IASetInputLayout(&pipeline.input_layout);
+            ctx.IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+            ctx.VSSetShader(&pipeline.vertex_shader, &[]);
+            ctx.PSSetShader(&pipeline.pixel_shader, &[]);
+            ctx.GSSetShader(None, &[]);
+            ctx.HSSetShader(None, &[]);
+            ctx.DSSetShader(None, &[]);
+            ctx.CSSetShader(None, &[]);
+            ctx.OMSetBlendState(&pipeline.blend_state, &blend_factor, 0xFFFFFFFF);
+            ctx.OMSetDepthStencilState(&pipeline.depth_stencil_state, 0);
+            ctx.RSSetState(&pipeline.rasterizer_state);
+        }
+    }
+
+    fn set_pixel_shader(
+        &mut self,
+        pipeline: &Dx11Pipeline,
+        shader: Option<&ID3D11PixelShader>,
+        texture_alpha: AlphaMode,
+    ) {
+        let shader = shader.unwrap_or(if texture_alpha == pipeline.alpha {
+            &pipeline.pixel_shader
+        } else {
+            &pipeline.converting_pixel_shader
+        });
+        unsafe { self.context.PSSetShader(shader, &[]) }
+    }
+
+    fn set_vertex_buffer(&mut self, buffer: &ID3D11Buffer, stride: u32) {
+        unsafe { self.context.IASetVertexBuffers(0, 1, &Some(buffer.clone()), &stride, &0) }
+    }
+
+    fn set_index_buffer(&mut self, buffer: &ID3D11Buffer, format: IndexFormat) {
+        let format = match format {
+            IndexFormat::U16 => DXGI_FORMAT_R16_UINT,
+            IndexFormat::U32 => DXGI_FORMAT_R32_UINT,
+        };
+        unsafe { self.context.IASetIndexBuffer(buffer, format, 0) }
+    }
+
+    fn set_constant_buffer(&mut self, buffer: &ID3D11Buffer) {
+        unsafe {
+            self.context.VSSetConstantBuffers(0, &[Some(buffer.clone())]);
+            self.context.PSSetConstantBuffers(1, &[Some(buffer.clone())]);
+        }
+    }
+
+    fn set_pixel_constant_buffer(&mut self, buffer: &ID3D11Buffer) {
+        unsafe { self.context.PSSetConstantBuffers(0, &[Some(buffer.clone())]) }
+    }
+
+    fn set_texture(&mut self, texture: &ID3D11ShaderResourceView) {
+        unsafe { self.context.PSSetShaderResources(0, &[Some(texture.clone())]) }
+    }
+
+    fn set_sampler(&mut self, sampler: &ID3D11SamplerState) {
+        unsafe { self.context.PSSetSamplers(0, &[Some(sampler.clone())]) }
+    }
+
+    fn set_scissor_rect(&mut self, r: ScissorRect) {
+        let rect = RECT { left: r.left, top: r.top, right: r.right, bottom: r.bottom };
+        unsafe { self.context.RSSetScissorRects(&[rect]) }
+    }
+
+    fn draw_indexed(&mut self, index_count: u32, start_index: u32, base_vertex: i32) {
+        unsafe { self.context.DrawIndexed(index_count, start_index, base_vertex) }
+    }
+
+    fn capture_state(&mut self, touched: &TouchedState) -> StateBlock {
+        unsafe { StateBlock::capture_touched(&self.context, touched) }
+    }
+
+    fn apply_state(&mut self, state: &StateBlock) {
+        state.apply()
+    }
+
+    fn create_timer_query(&mut self) -> Result<Dx11TimerQuery> {
+        let query = |query| {
+            let desc = D3D11_QUERY_DESC { Query: query, MiscFlags: 0 };
+            unsafe { self.device.CreateQuery(&desc) }
+        };
+        Ok(Dx11TimerQuery {
+            disjoint: query(D3D11_QUERY_TIMESTAMP_DISJOINT)?,
+            begin: query(D3D11_QUERY_TIMESTAMP)?,
+            end: query(D3D11_QUERY_TIMESTAMP)?,
+        })
+    }
+
+    fn begin_timer_query(&mut self, query: &Dx11TimerQuery) {
+        unsafe {
+            self.context.Begin(&query.disjoint);
+            self.context.End(&query.begin);
+        }
+    }
+
+    fn end_timer_query(&mut self, query: &Dx11TimerQuery) {
+        unsafe {
+            self.context.End(&query.end);
+            self.context.End(&query.disjoint);
+        }
+    }
+
+    fn timer_query_data(&mut self, query: &Dx11TimerQuery) -> Option<TimestampData> {
+        unsafe {
+            let disjoint: D3D11_QUERY_DATA_TIMESTAMP_DISJOINT =
+                query_data(&self.context, &query.disjoint)?;
+            Some(TimestampData {
+                begin: query_data(&self.context, &query.begin)?,
+                end: query_data(&self.context, &query.end)?,
+                frequency: disjoint.Frequency,
+                disjoint: disjoint.Disjoint.as_bool(),
+            })
+        }
+    }
+}
+
+/// The swapchain of a window along with the render target of its back buffer.
+#[derive(Debug, Clone)]
+pub struct Dx11Surface {
+    swap_chain: IDXGISwapChain,
+    target: Option<ID3D11RenderTargetView>,
+}
+
+impl Dx11Surface {
+    /// The swapchain of the surface.
+    #[inline]
+    pub fn swap_chain(&self) -> &IDXGISwapChain {
+        &self.swap_chain
+    }
+}
+
+impl Dx11Device {
+    unsafe fn create_back_buffer_target(
+        &self,
+        swap_chain: &IDXGISwapChain,
+    ) -> Result<ID3D11RenderTargetView> {
+        let back_buffer: ID3D11Resource = swap_chain.GetBuffer(0)?;
+        self.device.CreateRenderTargetView(&back_buffer, null())
+    }
+}
+
+impl SurfaceDevice for Dx11Device {
+    type Window = HWND;
+    type Surface = Dx11Surface;
+
+    fn create_surface(&mut self, window: &HWND, size: [u32; 2]) -> Result<Dx11Surface> {
+        let desc = DXGI_SWAP_CHAIN_DESC {
+            BufferDesc: DXGI_MODE_DESC {
+                Width: size[0],
+                Height: size[1],
+                Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                ..Default::default()
+            },
+            SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+            BufferUsage: DXGI_USAGE_RENDER_TARGET_OUTPUT,
+            BufferCount: 1,
+            OutputWindow: *window,
+            Windowed: true.into(),
+            SwapEffect: DXGI_SWAP_EFFECT_DISCARD,
+            Flags: 0,
+        };
+        unsafe {
+            let factory: IDXGIFactory =
+                self.device.cast::<IDXGIDevice>()?.GetAdapter()?.GetParent()?;
+            let swap_chain = factory.CreateSwapChain(&self.device, &desc)?;
+            let target = self.create_back_buffer_target(&swap_chain)?;
+            Ok(Dx11Surface { swap_chain, target: Some(target) })
+        }
+    }
+
+    fn resize_surface(&mut self, surface: &mut Dx11Surface, size: [u32; 2]) -> Result<()> {
+        // all references to the back buffer have to be released before resizing
+        surface.target = None;
+        unsafe {
+            surface.swap_chain.ResizeBuffers(0, size[0], size[1], DXGI_FORMAT_UNKNOWN, 0)?;
+            surface.target = Some(self.create_back_buffer_target(&surface.swap_chain)?);
+        }
+        Ok(())
+    }
+
+    fn surface_target(surface: &Dx11Surface) -> Option<&ID3D11RenderTargetView> {
+        surface.target.as_ref()
+    }
+
+    fn present(&mut self, surface: &Dx11Surface) -> Result<()> {
+        unsafe { surface.swap_chain.Present(0, 0) }
+    }
+}
+
+/// A snapshot of d3d11 device context state, captured and applied around
+/// render calls according to the renderer's [`StatePolicy`].
+///
+/// Covers the render targets, the rasterizer and output merger state, the
+/// input assembler, the shaders of every stage with their class instances
+/// and the vertex and pixel shader slots the renderer binds. Users may
+/// capture and apply blocks around their own code as well.
+///
+/// [`StatePolicy`]: crate::device::StatePolicy
+#[derive(Debug)]
+pub struct StateBlock {
+    context: ID3D11DeviceContext,
+    render_targets: Option<RenderTargets>,
+    viewports: Option<Vec<D3D11_VIEWPORT>>,
+    scissor_rects: Option<Vec<RECT>>,
+    pipeline: Option<PipelineState>,
+    index_buffer: Option<IndexBuffer>,
+    vertex_buffers: VertexBuffers,
+    vs_constant_buffers: Vec<Option<ID3D11Buffer>>,
+    ps_constant_buffers: Vec<Option<ID3D11Buffer>>,
+    ps_shader_resources: Vec<Option<ID3D11ShaderResourceView>>,
+    ps_samplers: Vec<Option<ID3D11SamplerState>>,
+}
+
+#[derive(Debug)]
+struct RenderTargets {
+    views: [Option<ID3D11RenderTargetView>; D3D11_SIMULTANEOUS_RENDER_TARGET_COUNT as usize],
+    depth_stencil_view: Option<ID3D11DepthStencilView>,
+}
+
+#[derive(Debug)]
+struct PipelineState {
+    vertex_shader: BoundShader<ID3D11VertexShader>,
+    hull_shader: BoundShader<ID3D11HullShader>,
+    domain_shader: BoundShader<ID3D11DomainShader>,
+    geometry_shader: BoundShader<ID3D11GeometryShader>,
+    pixel_shader: BoundShader<ID3D11PixelShader>,
+    compute_shader: BoundShader<ID3D11ComputeShader>,
+    input_layout: Option<ID3D11InputLayout>,
+    topology: D3D_PRIMITIVE_TOPOLOGY,
+    rasterizer_state: Option<ID3D11RasterizerState>,
+    blend_state: Option<ID3D11BlendState>,
+    blend_factor: [f32; 4],
+    sample_mask: u32,
+    depth_stencil_state: Option<ID3D11DepthStencilState>,
+    stencil_ref: u32,
+}
+
+#[derive(Debug)]
+struct IndexBuffer {
+    buffer: Option<ID3D11Buffer>,
+    format: DXGI_FORMAT,
+    offset: u32,
+}
+
+#[derive(Debug, Default)]
+struct VertexBuffers {
+    buffers: Vec<Option<ID3D11Buffer>>,
+    strides: Vec<u32>,
+    offsets: Vec<u32>,
+}
+
+/// A shader bound to a stage together with its class instances.
+#[derive(Debug)]
+struct BoundShader<T> {
+    shader: Option<T>,
+    instances: Vec<Option<ID3D11ClassInstance>>,
+}
+
+impl<T> BoundShader<T> {
+    /// Queries a stage's shader with one of the `*GetShader` functions.
+    unsafe fn get(
+        get_shader: impl FnOnce(*mut Option<T>, *mut Option<ID3D11ClassInstance>, *mut u32),
+    ) -> Self {
+        const NONE: Option<ID3D11ClassInstance> = None;
+        let mut shader = None;
+        let mut instances = [NONE; D3D11_SHADER_MAX_INTERFACES as usize];
+        let mut count = D3D11_SHADER_MAX_INTERFACES;
+        get_shader(&mut shader, instances.as_mut_ptr(), &mut count);
+        // class linkage is rare, only instances in use end up on the heap
+        let instances = instances[..count as usize].iter_mut().map(Option::take).collect();
+        BoundShader { shader, instances }
+    }
+}
+
+/// `count` empty slots, at most `limit`.
+fn slots<T: Clone>(count: u32, limit: u32) -> Vec<Option<T>> {
+    vec![None; count.min(limit) as usize]
+}
+
+impl StateBlock {
+    /// Captures all of the state a state block covers, with every slot.
+    ///
+    /// # Safety
+    ///
+    /// `context` must be a valid [`ID3D11DeviceContext`] pointer.
+    ///
+    /// [`ID3D11DeviceContext`]: https://docs.rs/winapi/0.3/x86_64-pc-windows-msvc/winapi/um/d3d11/struct.ID3D11DeviceContext.html
+    pub unsafe fn capture(context: &ID3D11DeviceContext) -> Self {
+        Self::capture_touched(context, &TouchedState::ALL)
+    }
+
+    /// Captures only the touched state, slot counts are clamped to the d3d11
+    /// limits.
+    ///
+    /// # Safety
+    ///
+    /// `context` must be a valid [`ID3D11DeviceContext`] pointer.
+    ///
+    /// [`ID3D11DeviceContext`]: https://docs.rs/winapi/0.3/x86_64-pc-windows-msvc/winapi/um/d3d11/struct.ID3D11DeviceContext.html
+    pub unsafe fn capture_touched(context: &ID3D11DeviceContext, touched: &TouchedState) -> Self {
+        let ctx = context;
+        let render_targets = touched.render_targets.then(|| {
+            let mut targets = RenderTargets { views: Default::default(), depth_stencil_view: None };
+            ctx.OMGetRenderTargets(&mut targets.views, &mut targets.depth_stencil_view);
+            targets
+        });
+        // a null array queries the number of bound viewports and rects
+        let viewports = touched.viewports.then(|| {
+            let mut count = 0;
+            ctx.RSGetViewports(&mut count, null_mut());
+            let mut viewports = vec![D3D11_VIEWPORT::default(); count as usize];
+            ctx.RSGetViewports(&mut count, viewports.as_mut_ptr());
+            viewports
+        });
+        let scissor_rects = touched.scissor_rects.then(|| {
+            let mut count = 0;
+            ctx.RSGetScissorRects(&mut count, null_mut());
+            let mut rects = vec![RECT::default(); count as usize];
+            ctx.RSGetScissorRects(&mut count, rects.as_mut_ptr());
+            rects
+        });
+        let pipeline = touched.pipeline.then(|| {
+            let mut state = PipelineState {
+                vertex_shader: BoundShader::get(|s, i, n| ctx.VSGetShader(s, i, n)),
+                hull_shader: BoundShader::get(|s, i, n| ctx.HSGetShader(s, i, n)),
+                domain_shader: BoundShader::get(|s, i, n| ctx.DSGetShader(s, i, n)),
+                geometry_shader: BoundShader::get(|s, i, n| ctx.GSGetShader(s, i, n)),
+                pixel_shader: BoundShader::get(|s, i, n| ctx.PSGetShader(s, i, n)),
+                compute_shader: BoundShader::get(|s, i, n| ctx.CSGetShader(s, i, n)),
+                input_layout: None,
+                topology: D3D_PRIMITIVE_TOPOLOGY::default(),
+                rasterizer_state: None,
+                blend_state: None,
+                blend_factor: [0.0; 4],
+                sample_mask: 0,
+                depth_stencil_state: None,
+                stencil_ref: 0,
+            };
+            ctx.IAGetInputLayout(&mut state.input_layout);
+            ctx.IAGetPrimitiveTopology(&mut state.topology);
+            ctx.RSGetState(&mut state.rasterizer_state);
+            ctx.OMGetBlendState(
+                &mut state.blend_state,
+                state.blend_factor.as_mut_ptr(),
+                &mut state.sample_mask,
+            );
+            ctx.OMGetDepthStencilState(&mut state.depth_stencil_state, &mut state.stencil_ref);
+            state
+        });
+        let index_buffer = touched.index_buffer.then(|| {
+            let mut index_buffer =
+                IndexBuffer { buffer: None, format: DXGI_FORMAT::default(), offset: 0 };
+            ctx.IAGetIndexBuffer(
+                &mut index_buffer.buffer,
+                &mut index_buffer.format,
+                &mut index_buffer.offset,
+            );
+            index_buffer
+        });
+
+        let mut vertex_buffers = VertexBuffers::default();
+        vertex_buffers.buffers =
+            slots(touched.vertex_buffers, D3D11_IA_VERTEX_INPUT_RESOURCE_SLOT_COUNT);
+        let count = vertex_buffers.buffers.len();
+        vertex_buffers.strides = vec![0; count];
+        vertex_buffers.offsets = vec![0; count];
+        if count > 0 {
+            ctx.IAGetVertexBuffers(
+                0,
+                count as u32,
+                vertex_buffers.buffers.as_mut_ptr(),
+                vertex_buffers.strides.as_mut_ptr(),
+                vertex_buffers.offsets.as_mut_ptr(),
+            );
+        }
+        let mut vs_constant_buffers =
+            slots(touched.vs_constant_buffers, D3D11_COMMONSHADER_CONSTANT_BUFFER_API_SLOT_COUNT);
+        ctx.VSGetConstantBuffers(0, &mut vs_constant_buffers);
+        let mut ps_constant_buffers =
+            slots(touched.ps_constant_buffers, D3D11_COMMONSHADER_CONSTANT_BUFFER_API_SLOT_COUNT);
+        ctx.PSGetConstantBuffers(0, &mut ps_constant_buffers);
+        let mut ps_shader_resources =
+            slots(touched.ps_textures, D3D11_COMMONSHADER_INPUT_RESOURCE_SLOT_COUNT);
+        ctx.PSGetShaderResources(0, &mut ps_shader_resources);
+        let mut ps_samplers = slots(touched.ps_samplers, D3D11_COMMONSHADER_SAMPLER_SLOT_COUNT);
+        ctx.PSGetSamplers(0, &mut ps_samplers);
+
+        StateBlock {
+            context: context.clone(),
+            render_targets,
+            viewports,
+            scissor_rects,
+            pipeline,
+            index_buffer,
+            vertex_buffers,
+            vs_constant_buffers,
+            ps_constant_buffers,
+            ps_shader_resources,
+            ps_samplers,
+        }
+    }
+
+    /// Applies the captured state to the context it was captured from,
+    /// leaving state not captured as is.
+    pub fn apply(&self) {
+        unsafe {
+            let ctx = &self.context;
+            if let Some(targets) = &self.render_targets {
+                ctx.OMSetRenderTargets(&targets.views, &targets.depth_stencil_view);
+            }
+            if let Some(viewports) = &self.viewports {
+                ctx.RSSetViewports(viewports);
+            }
+            if let Some(rects) = &self.scissor_rects {
+                ctx.RSSetScissorRects(rects);
+            }
+            if let Some(state) = &self.pipeline {
+                let PipelineState {
+                    vertex_shader: vs,
+                    hull_shader: hs,
+                    domain_shader: ds,
+                    geometry_shader: gs,
+                    pixel_shader: ps,
+                    compute_shader: cs,
+                    ..
+                } = state;
+                ctx.VSSetShader(&vs.shader, &vs.instances);
+                ctx.HSSetShader(&hs.shader, &hs.instances);
+                ctx.DSSetShader(&ds.shader, &ds.instances);
+                ctx.GSSetShader(&gs.shader, &gs.instances);
+                ctx.PSSetShader(&ps.shader, &ps.instances);
+                ctx.CSSetShader(&cs.shader, &cs.instances);
+                ctx.IASetInputLayout(&state.input_layout);
+                ctx.IASetPrimitiveTopology(state.topology);
+                ctx.RSSetState(&state.rasterizer_state);
+                ctx.OMSetBlendState(
+                    &state.blend_state,
+                    state.blend_factor.as_ptr(),
+                    state.sample_mask,
+                );
+                ctx.OMSetDepthStencilState(&state.depth_stencil_state, state.stencil_ref);
+            }
+            if let Some(index_buffer) = &self.index_buffer {
+                ctx.IASetIndexBuffer(
+                    &index_buffer.buffer,
+                    index_buffer.format,
+                    index_buffer.offset,
+                );
+            }
+            let vertex_buffers = &self.vertex_buffers;
+            if !vertex_buffers.buffers.is_empty() {
+                ctx.IASetVertexBuffers(
+                    0,
+                    vertex_buffers.buffers.len() as u32,
+                    vertex_buffers.buffers.as_ptr(),
+                    vertex_buffers.strides.as_ptr(),
+                    vertex_buffers.offsets.as_ptr(),
+                );
+            }
+            if !self.vs_constant_buffers.is_empty() {
+                ctx.VSSetConstantBuffers(0, &self.vs_constant_buffers);
+            }
+            if !self.ps_constant_buffers.is_empty() {
+                ctx.PSSetConstantBuffers(0, &self.ps_constant_buffers);
+            }
+            if !self.ps_shader_resources.is_empty() {
+                ctx.PSSetShaderResources(0, &self.ps_shader_resources);
+            }
+            if !self.ps_samplers.is_empty() {
+                ctx.PSSetSamplers(0, &self.ps_samplers);
+            }
+        }
+    }
+}
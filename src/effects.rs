@@ -0,0 +1,133 @@
+//! Custom pixel shader effects replacing the default pixel shader for some
+//! draws.
+//!
+//! An effect is a compiled pixel shader, `ps_4_0` bytecode for d3d11, with
+//! optional constant data bound to the first constant buffer slot. It takes
+//! the same inputs as the default pixel shader:
+//!
+//! ```hlsl
+//! struct PS_INPUT {
+//!     float4 pos: SV_POSITION;
+//!     float4 col: COLOR0;
+//!     float2 uv: TEXCOORD0;
+//! };
+//!
+//! sampler sampler0;
+//! Texture2D texture0;
+//! ```
+//!
+//! Renderers created with premultiplied alpha pass premultiplied vertex
+//! colors and expect premultiplied output. Textures are sampled as they are,
+//! regardless of their alpha mode.
+//!
+//! Effects output colors in the renderer's [color space] as is. For the HDR
+//! color spaces the renderer's constants are bound to the second constant
+//! buffer slot:
+//!
+//! ```hlsl
+//! cbuffer vertexBuffer: register(b1) {
+//!     float4x4 ProjectionMatrix;
+//!     float PaperWhiteNits;
+//! };
+//! ```
+//!
+//! [color space]: crate::color::ColorSpace
+//!
+//! Effects are created with [`Renderer::create_effect`] and applied either to
+//! every draw of a texture with [`Renderer::set_texture_effect`] or to the
+//! draws following an effect marker pushed with [`push_effect`]. Markers take
+//! precedence over texture effects until they are ended or the render state
+//! is reset.
+//!
+//! Markers are pairs of draw list callbacks doing nothing, so renderers
+//! unaware of them simply ignore them. The user data of the first one is the
+//! address of a private static, which no other callback can hold, the second
+//! one holds the effect. [`FrameCapture`]s replay them as plain callbacks,
+//! dropping the effect.
+//!
+//! [`Renderer::create_effect`]: crate::Renderer::create_effect
+//! [`Renderer::set_texture_effect`]: crate::Renderer::set_texture_effect
+//! [`FrameCapture`]: crate::capture::FrameCapture
+
+use core::{fmt, ptr};
+
+use imgui::sys;
+
+/// The id of an effect created with [`Renderer::create_effect`].
+///
+/// [`Renderer::create_effect`]: crate::Renderer::create_effect
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EffectId(pub(crate) usize);
+
+impl EffectId {
+    /// The numeric value of the id.
+    #[inline]
+    pub fn id(self) -> usize {
+        self.0
+    }
+}
+
+/// The error returned when setting up an effect fails.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EffectError {
+    /// The id doesn't belong to an effect of the renderer, for example
+    /// because it was removed.
+    UnknownEffect(EffectId),
+    /// The constant data doesn't match the size of the effect's constants.
+    DataSizeMismatch {
+        /// The size of the effect's constants in bytes.
+        expected: usize,
+        /// The size of the given data in bytes.
+        actual: usize,
+    },
+}
+
+impl fmt::Display for EffectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            EffectError::UnknownEffect(id) => write!(f, "unknown effect id {}", id.id()),
+            EffectError::DataSizeMismatch { expected, actual } => {
+                write!(f, "expected {} bytes of effect constants, got {}", expected, actual)
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EffectError {}
+
+/// The static whose address identifies effect markers.
+static EFFECT_MARKER: u8 = 0;
+
+/// The callback of both commands of an effect marker.
+unsafe extern "C" fn effect_marker(_: *const sys::ImDrawList, _: *const sys::ImDrawCmd) {}
+
+/// Whether the callback command starts an effect marker.
+pub(crate) fn is_effect_marker(raw_cmd: *const sys::ImDrawCmd) -> bool {
+    let data = unsafe { (*raw_cmd).UserCallbackData };
+    ptr::eq(data as *const u8, &EFFECT_MARKER)
+}
+
+/// Decodes the effect of the callback command following the start of an
+/// effect marker.
+///
+/// `None` ends the effect of a previous marker.
+pub(crate) fn marker_effect(raw_cmd: *const sys::ImDrawCmd) -> Option<EffectId> {
+    let data = unsafe { (*raw_cmd).UserCallbackData } as usize;
+    data.checked_sub(1).map(EffectId)
+}
+
+/// Pushes an effect marker onto a draw list, drawing the following commands
+/// of the list with `effect`, or ending the effect of a previous marker for
+/// `None`.
+///
+/// # Safety
+///
+/// `draw_list` must be a valid draw list of the current frame, like the one
+/// returned by [`sys::igGetWindowDrawList`].
+pub unsafe fn push_effect(draw_list: *mut sys::ImDrawList, effect: Option<EffectId>) {
+    let marker = &EFFECT_MARKER as *const u8;
+    sys::ImDrawList_AddCallback(draw_list, Some(effect_marker), marker as *mut _);
+    let data = effect.map_or(0, |effect| effect.0 + 1);
+    sys::ImDrawList_AddCallback(draw_list, Some(effect_marker), data as *mut _);
+}
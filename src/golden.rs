@@ -0,0 +1,380 @@
+//! Golden image regression testing of rendered frames.
+//!
+//! A [`FrameHarness`] renders scripted imgui frames with the
+//! [`SoftwareRenderer`], and a [`GoldenDir`] compares the resulting images
+//! against checked-in PNG goldens with a per-pixel tolerance, writing the
+//! actual and a diff image on failure.
+//!
+//! Goldens are (re)written instead of compared when the `UPDATE_GOLDENS`
+//! environment variable is set.
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::{env, fmt};
+
+use alloc::vec::Vec;
+
+use crate::plan::UnknownTexture;
+use crate::software::{Image, SoftwareRenderer};
+
+/// The environment variable which, when set, makes [`GoldenDir::check`]
+/// write goldens instead of comparing against them.
+pub const UPDATE_GOLDENS_ENV: &str = "UPDATE_GOLDENS";
+
+/// Reads a PNG file into an RGBA8 [`Image`].
+pub fn read_png(path: &Path) -> Result<Image, GoldenError> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buf = alloc::vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+    buf.truncate(info.buffer_size());
+
+    let data = match info.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf.chunks_exact(3).flat_map(|c| [c[0], c[1], c[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => {
+            buf.chunks_exact(2).flat_map(|c| [c[0], c[0], c[0], c[1]]).collect()
+        },
+        png::ColorType::Grayscale => buf.iter().flat_map(|&c| [c, c, c, 255]).collect(),
+        png::ColorType::Indexed => unreachable!("indexed images are expanded by the decoder"),
+    };
+    Image::from_rgba(info.width, info.height, data)
+        .map_err(|e| GoldenError::Io(io::Error::new(io::ErrorKind::InvalidData, e)))
+}
+
+/// Writes an RGBA8 [`Image`] to a PNG file.
+pub fn write_png(path: &Path, image: &Image) -> Result<(), GoldenError> {
+    let mut encoder =
+        png::Encoder::new(BufWriter::new(File::create(path)?), image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(image.as_bytes())?;
+    Ok(())
+}
+
+/// The result of comparing two images with [`compare`].
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    /// The number of pixels differing by more than the tolerance in any
+    /// channel.
+    pub mismatched_pixels: usize,
+    /// The largest difference of any channel of any pixel.
+    pub max_channel_difference: u8,
+    /// An image highlighting the mismatched pixels in red on top of a dimmed
+    /// grayscale version of the expected image.
+    pub diff: Image,
+}
+
+/// Compares two images pixel by pixel.
+///
+/// A pixel mismatches if any of its channels differ by more than
+/// `tolerance`. Returns `None` if the images differ in size.
+pub fn compare(actual: &Image, expected: &Image, tolerance: u8) -> Option<Comparison> {
+    if actual.width() != expected.width() || actual.height() != expected.height() {
+        return None;
+    }
+    let mut mismatched_pixels = 0;
+    let mut max_channel_difference = 0;
+    let mut diff = Vec::with_capacity(expected.as_bytes().len());
+    for (a, e) in actual.as_bytes().chunks_exact(4).zip(expected.as_bytes().chunks_exact(4)) {
+        let difference = a.iter().zip(e).map(|(&a, &e)| a.max(e) - a.min(e)).max().unwrap_or(0);
+        max_channel_difference = max_channel_difference.max(difference);
+        if difference > tolerance {
+            mismatched_pixels += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let luma = (e[0] as u32 * 77 + e[1] as u32 * 150 + e[2] as u32 * 29) >> 8;
+            let dimmed = (luma / 3) as u8;
+            diff.extend_from_slice(&[dimmed, dimmed, dimmed, 255]);
+        }
+    }
+    let diff = Image::from_rgba(expected.width(), expected.height(), diff)
+        .expect("diff image has the size of the expected image");
+    Some(Comparison { mismatched_pixels, max_channel_difference, diff })
+}
+
+/// The error returned by the golden image functions.
+#[derive(Debug)]
+pub enum GoldenError {
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// A golden image could not be decoded.
+    Decoding(png::DecodingError),
+    /// An image could not be encoded.
+    Encoding(png::EncodingError),
+    /// The golden image does not exist.
+    MissingGolden(PathBuf),
+    /// The actual image differs in size from the golden.
+    SizeMismatch {
+        /// The size of the rendered image.
+        actual: [u32; 2],
+        /// The size of the golden image.
+        expected: [u32; 2],
+        /// Where the rendered image has been written to.
+        actual_path: PathBuf,
+    },
+    /// Too many pixels differ from the golden.
+    Mismatch {
+        /// The number of pixels differing by more than the tolerance.
+        mismatched_pixels: usize,
+        /// The largest difference of any channel of any pixel.
+        max_channel_difference: u8,
+        /// Where the rendered image has been written to.
+        actual_path: PathBuf,
+        /// Where the diff image has been written to.
+        diff_path: PathBuf,
+    },
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenError::Io(e) => e.fmt(f),
+            GoldenError::Decoding(e) => e.fmt(f),
+            GoldenError::Encoding(e) => e.fmt(f),
+            GoldenError::MissingGolden(path) => write!(
+                f,
+                "golden {} does not exist, rerun with {} set to create it",
+                path.display(),
+                UPDATE_GOLDENS_ENV
+            ),
+            GoldenError::SizeMismatch { actual, expected, actual_path } => write!(
+                f,
+                "rendered image is {}x{} but the golden is {}x{}, actual image written to {}",
+                actual[0],
+                actual[1],
+                expected[0],
+                expected[1],
+                actual_path.display()
+            ),
+            GoldenError::Mismatch {
+                mismatched_pixels,
+                max_channel_difference,
+                actual_path,
+                diff_path,
+            } => write!(
+                f,
+                "{} pixels differ from the golden (max channel difference {}), actual image \
+                 written to {}, diff written to {}",
+                mismatched_pixels,
+                max_channel_difference,
+                actual_path.display(),
+                diff_path.display()
+            ),
+        }
+    }
+}
+
+impl Error for GoldenError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GoldenError::Io(e) => Some(e),
+            GoldenError::Decoding(e) => Some(e),
+            GoldenError::Encoding(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for GoldenError {
+    fn from(e: io::Error) -> Self {
+        GoldenError::Io(e)
+    }
+}
+
+impl From<png::DecodingError> for GoldenError {
+    fn from(e: png::DecodingError) -> Self {
+        GoldenError::Decoding(e)
+    }
+}
+
+impl From<png::EncodingError> for GoldenError {
+    fn from(e: png::EncodingError) -> Self {
+        GoldenError::Encoding(e)
+    }
+}
+
+/// A directory of PNG goldens.
+#[derive(Clone, Debug)]
+pub struct GoldenDir {
+    golden_dir: PathBuf,
+    output_dir: PathBuf,
+    tolerance: u8,
+    max_mismatched_pixels: usize,
+    update: bool,
+}
+
+impl GoldenDir {
+    /// Creates a golden directory at the given path.
+    ///
+    /// Failure artifacts are written to the `failures` subdirectory by
+    /// default. The tolerance defaults to a channel difference of 2 with no
+    /// mismatched pixels allowed.
+    pub fn new(golden_dir: impl Into<PathBuf>) -> Self {
+        let golden_dir = golden_dir.into();
+        GoldenDir {
+            output_dir: golden_dir.join("failures"),
+            golden_dir,
+            tolerance: 2,
+            max_mismatched_pixels: 0,
+            update: env::var_os(UPDATE_GOLDENS_ENV).is_some(),
+        }
+    }
+
+    /// Sets the directory failure artifacts are written to.
+    pub fn output_dir(mut self, output_dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = output_dir.into();
+        self
+    }
+
+    /// Sets the largest channel difference for which a pixel still matches.
+    pub fn tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Sets the number of pixels allowed to mismatch.
+    pub fn max_mismatched_pixels(mut self, max_mismatched_pixels: usize) -> Self {
+        self.max_mismatched_pixels = max_mismatched_pixels;
+        self
+    }
+
+    /// The path of the golden with the given name.
+    pub fn golden_path(&self, name: &str) -> PathBuf {
+        self.golden_dir.join(name).with_extension("png")
+    }
+
+    /// Compares `actual` against the golden with the given name.
+    ///
+    /// If `UPDATE_GOLDENS` is set, the golden is written instead. On
+    /// mismatch the actual image is written to `<name>.actual.png` and the
+    /// diff to `<name>.diff.png` in the output directory.
+    pub fn check(&self, name: &str, actual: &Image) -> Result<(), GoldenError> {
+        let golden_path = self.golden_path(name);
+        if self.update {
+            fs::create_dir_all(&self.golden_dir)?;
+            return write_png(&golden_path, actual);
+        }
+        if !golden_path.exists() {
+            return Err(GoldenError::MissingGolden(golden_path));
+        }
+        let expected = read_png(&golden_path)?;
+
+        let comparison = match compare(actual, &expected, self.tolerance) {
+            Some(comparison) => comparison,
+            None => {
+                let actual_path = self.write_artifact(name, "actual", actual)?;
+                return Err(GoldenError::SizeMismatch {
+                    actual: [actual.width(), actual.height()],
+                    expected: [expected.width(), expected.height()],
+                    actual_path,
+                });
+            },
+        };
+        if comparison.mismatched_pixels > self.max_mismatched_pixels {
+            let actual_path = self.write_artifact(name, "actual", actual)?;
+            let diff_path = self.write_artifact(name, "diff", &comparison.diff)?;
+            return Err(GoldenError::Mismatch {
+                mismatched_pixels: comparison.mismatched_pixels,
+                max_channel_difference: comparison.max_channel_difference,
+                actual_path,
+                diff_path,
+            });
+        }
+        Ok(())
+    }
+
+    fn write_artifact(
+        &self,
+        name: &str,
+        kind: &str,
+        image: &Image,
+    ) -> Result<PathBuf, GoldenError> {
+        fs::create_dir_all(&self.output_dir)?;
+        let path = self.output_dir.join(alloc::format!("{}.{}.png", name, kind));
+        write_png(&path, image)?;
+        Ok(path)
+    }
+}
+
+/// Renders scripted imgui frames with a [`SoftwareRenderer`].
+///
+/// The harness pins everything that could make frames nondeterministic: no
+/// ini file is used, the delta time is fixed and the mouse is kept outside of
+/// the display.
+#[derive(Debug)]
+pub struct FrameHarness {
+    context: imgui::Context,
+    renderer: SoftwareRenderer,
+    clear_color: [u8; 4],
+}
+
+impl FrameHarness {
+    /// Creates a harness rendering frames of the given display size and
+    /// framebuffer scale.
+    pub fn new(display_size: [f32; 2], framebuffer_scale: [f32; 2]) -> Self {
+        Self::with_context(imgui::Context::create(), display_size, framebuffer_scale)
+    }
+
+    /// Creates a harness from an existing context, allowing fonts and style
+    /// to be set up beforehand.
+    pub fn with_context(
+        mut context: imgui::Context,
+        display_size: [f32; 2],
+        framebuffer_scale: [f32; 2],
+    ) -> Self {
+        context.set_ini_filename(None);
+        let io = context.io_mut();
+        io.display_size = display_size;
+        io.display_framebuffer_scale = framebuffer_scale;
+        io.delta_time = 1.0 / 60.0;
+        io.mouse_pos = [-f32::MAX, -f32::MAX];
+        let renderer = SoftwareRenderer::new(&mut context);
+        FrameHarness { context, renderer, clear_color: [0x33, 0x33, 0x33, 0xFF] }
+    }
+
+    /// Sets the color the target is cleared to before rendering.
+    pub fn clear_color(mut self, clear_color: [u8; 4]) -> Self {
+        self.clear_color = clear_color;
+        self
+    }
+
+    /// The imgui context of this harness.
+    #[inline]
+    pub fn context_mut(&mut self) -> &mut imgui::Context {
+        &mut self.context
+    }
+
+    /// The renderer of this harness, used to register textures.
+    #[inline]
+    pub fn renderer_mut(&mut self) -> &mut SoftwareRenderer {
+        &mut self.renderer
+    }
+
+    /// Runs `frames` frames built by `build`, rendering the last one.
+    ///
+    /// Running more than one frame lets imgui settle window sizes and layout
+    /// which are only known after a first frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frames` is zero.
+    pub fn render<F>(&mut self, frames: usize, mut build: F) -> Result<Image, UnknownTexture>
+    where
+        F: FnMut(&imgui::Ui<'_>),
+    {
+        assert!(frames > 0, "at least one frame has to be rendered");
+        for _ in 1..frames {
+            let ui = self.context.frame();
+            build(&ui);
+            ui.render();
+        }
+        let ui = self.context.frame();
+        build(&ui);
+        let draw_data = ui.render();
+        self.renderer.render(draw_data, self.clear_color)
+    }
+}
@@ -0,0 +1,648 @@
+//! A platform independent [`Device`] recording every call made to it.
+//!
+//! The [`RecordingDevice`] allows asserting the exact command stream the
+//! [`Renderer`] emits for a given [`DrawData`] without a GPU.
+//!
+//! [`Renderer`]: crate::Renderer
+//! [`DrawData`]: imgui::DrawData
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::{fmt, mem, slice};
+
+use crate::dds::{DdsFormat, DdsTexture};
+use crate::device::{
+    BufferKind, Device, IndexFormat, PipelineDesc, SurfaceDevice, TouchedState, Viewport,
+};
+use crate::effects::EffectError;
+use crate::plan::{ScissorRect, UnknownTexture};
+use crate::texture::{
+    AlphaMode, SamplerDesc, TextureDesc, TextureError, TextureFormat, TextureRect,
+};
+use crate::timing::TimestampData;
+use crate::viewports::{UnknownViewport, ViewportId};
+
+/// A buffer created by a [`RecordingDevice`], identified by creation order.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MockBuffer(pub usize);
+
+/// A texture created by a [`RecordingDevice`], identified by creation order.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MockTexture(pub usize);
+
+/// A sampler created by a [`RecordingDevice`], identified by creation order.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MockSampler(pub usize);
+
+/// A pixel shader created by a [`RecordingDevice`], identified by creation
+/// order.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MockPixelShader(pub usize);
+
+/// A timer query created by a [`RecordingDevice`], identified by creation
+/// order.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MockTimerQuery(pub usize);
+
+/// A render target of a [`RecordingDevice`].
+///
+/// Render targets are created by the user, the id is only used to tell them
+/// apart in the log.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MockRenderTarget {
+    /// The id of the render target.
+    pub id: usize,
+    /// The size of the render target in pixels.
+    pub size: [u32; 2],
+}
+
+/// A window surfaces of a [`RecordingDevice`] can be created for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MockWindow(pub usize);
+
+/// A surface created by a [`RecordingDevice`], identified by creation order.
+///
+/// The render target of a surface shares its id.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MockSurface {
+    target: MockRenderTarget,
+}
+
+impl MockSurface {
+    /// The id of the surface.
+    #[inline]
+    pub fn id(&self) -> usize {
+        self.target.id
+    }
+
+    /// The render target of the surface.
+    #[inline]
+    pub fn target(&self) -> MockRenderTarget {
+        self.target
+    }
+}
+
+/// The error returned by a [`RecordingDevice`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MockError {
+    /// A texture or its data failed validation.
+    Texture(TextureError),
+    /// Mapping a buffer failed, see [`RecordingDevice::set_fail_maps`].
+    MapFailed,
+    /// An effect or its constants failed validation.
+    Effect(EffectError),
+    /// The viewport has no surface.
+    UnknownViewport(ViewportId),
+}
+
+impl fmt::Display for MockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MockError::Texture(e) => e.fmt(f),
+            MockError::MapFailed => write!(f, "mapping a buffer failed"),
+            MockError::Effect(e) => e.fmt(f),
+            MockError::UnknownViewport(id) => write!(f, "unknown viewport id {}", id),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MockError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MockError::Texture(e) => Some(e),
+            MockError::Effect(e) => Some(e),
+            MockError::MapFailed | MockError::UnknownViewport(_) => None,
+        }
+    }
+}
+
+impl From<TextureError> for MockError {
+    fn from(e: TextureError) -> Self {
+        MockError::Texture(e)
+    }
+}
+
+impl From<UnknownViewport> for MockError {
+    fn from(e: UnknownViewport) -> Self {
+        MockError::UnknownViewport(e.0)
+    }
+}
+
+impl From<EffectError> for MockError {
+    fn from(e: EffectError) -> Self {
+        MockError::Effect(e)
+    }
+}
+
+impl From<UnknownTexture> for MockError {
+    fn from(e: UnknownTexture) -> Self {
+        MockError::Texture(e.into())
+    }
+}
+
+/// A call made to a [`RecordingDevice`].
+///
+/// Queries like [`Device::render_target_size`] are not recorded.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Call {
+    /// [`Device::create_pipeline`]
+    CreatePipeline(PipelineDesc),
+    /// [`Device::create_pixel_shader`]
+    CreatePixelShader {
+        /// The created pixel shader.
+        shader: MockPixelShader,
+        /// The bytecode of the shader.
+        bytecode: Vec<u8>,
+    },
+    /// [`Device::create_buffer`]
+    CreateBuffer {
+        /// The created buffer.
+        buffer: MockBuffer,
+        /// The kind of the buffer.
+        kind: BufferKind,
+        /// The size of the buffer in bytes.
+        size: usize,
+    },
+    /// [`Device::create_texture`]
+    CreateTexture {
+        /// The created texture.
+        texture: MockTexture,
+        /// The width of the texture.
+        width: u32,
+        /// The height of the texture.
+        height: u32,
+        /// The pixel format of the texture.
+        format: TextureFormat,
+    },
+    /// [`Device::create_dds_texture`]
+    CreateDdsTexture {
+        /// The created texture.
+        texture: MockTexture,
+        /// The width of the top mip level.
+        width: u32,
+        /// The height of the top mip level.
+        height: u32,
+        /// The number of mip levels.
+        mip_levels: u32,
+        /// The number of array slices.
+        array_size: u32,
+        /// The pixel format of the texture.
+        format: DdsFormat,
+    },
+    /// [`Device::update_texture`]
+    UpdateTexture {
+        /// The updated texture.
+        texture: MockTexture,
+        /// The updated rectangle.
+        rect: TextureRect,
+    },
+    /// [`Device::create_sampler`]
+    CreateSampler {
+        /// The created sampler.
+        sampler: MockSampler,
+        /// The description of the sampler.
+        desc: SamplerDesc,
+    },
+    /// [`Device::map`]
+    Map(MockBuffer),
+    /// [`Device::map_no_overwrite`]
+    MapNoOverwrite(MockBuffer),
+    /// [`Device::unmap`]
+    Unmap(MockBuffer),
+    /// [`Device::set_render_target`]
+    SetRenderTarget(MockRenderTarget),
+    /// [`Device::clear_render_target`]
+    ClearRenderTarget {
+        /// The cleared render target.
+        target: MockRenderTarget,
+        /// The clear color.
+        color: [f32; 4],
+    },
+    /// [`SurfaceDevice::create_surface`]
+    CreateSurface {
+        /// The id of the created surface.
+        surface: usize,
+        /// The window of the surface.
+        window: MockWindow,
+        /// The size of the surface in pixels.
+        size: [u32; 2],
+    },
+    /// [`SurfaceDevice::resize_surface`]
+    ResizeSurface {
+        /// The id of the resized surface.
+        surface: usize,
+        /// The new size of the surface in pixels.
+        size: [u32; 2],
+    },
+    /// [`SurfaceDevice::present`]
+    Present(usize),
+    /// [`Device::set_viewport`]
+    SetViewport(Viewport),
+    /// [`Device::set_pipeline`]
+    SetPipeline,
+    /// [`Device::set_pixel_shader`]
+    SetPixelShader {
+        /// The bound pixel shader, `None` for the pipeline's default.
+        shader: Option<MockPixelShader>,
+        /// The alpha mode of the textures sampled by the default pixel shader.
+        texture_alpha: AlphaMode,
+    },
+    /// [`Device::set_vertex_buffer`]
+    SetVertexBuffer {
+        /// The bound buffer.
+        buffer: MockBuffer,
+        /// The vertex stride in bytes.
+        stride: u32,
+    },
+    /// [`Device::set_index_buffer`]
+    SetIndexBuffer {
+        /// The bound buffer.
+        buffer: MockBuffer,
+        /// The index format.
+        format: IndexFormat,
+    },
+    /// [`Device::set_constant_buffer`]
+    SetConstantBuffer(MockBuffer),
+    /// [`Device::set_pixel_constant_buffer`]
+    SetPixelConstantBuffer(MockBuffer),
+    /// [`Device::set_texture`]
+    SetTexture(MockTexture),
+    /// [`Device::set_sampler`]
+    SetSampler(MockSampler),
+    /// [`Device::set_scissor_rect`]
+    SetScissorRect(ScissorRect),
+    /// [`Device::draw_indexed`]
+    DrawIndexed {
+        /// The number of indices to draw.
+        index_count: u32,
+        /// The location of the first index.
+        start_index: u32,
+        /// The value added to each index.
+        base_vertex: i32,
+    },
+    /// [`Device::capture_state`]
+    CaptureState(TouchedState),
+    /// [`Device::apply_state`], with the state the block was captured with
+    ApplyState(TouchedState),
+    /// [`Device::create_timer_query`]
+    CreateTimerQuery(MockTimerQuery),
+    /// [`Device::begin_timer_query`]
+    BeginTimerQuery(MockTimerQuery),
+    /// [`Device::end_timer_query`]
+    EndTimerQuery(MockTimerQuery),
+}
+
+#[derive(Debug)]
+struct BufferStorage {
+    // u64 backing storage keeps mapped pointers suitably aligned for the
+    // vertex, index and constant data written through them
+    words: Vec<u64>,
+    size: usize,
+}
+
+#[derive(Debug)]
+struct TextureStorage {
+    size: [u32; 2],
+    data: Vec<u8>,
+}
+
+/// A [`Device`] recording all calls made to it.
+///
+/// Buffers and textures are backed by host memory so their contents can be
+/// inspected with [`RecordingDevice::buffer_data`] and
+/// [`RecordingDevice::texture_data`]. Device calls only fail when asked to
+/// with [`RecordingDevice::set_fail_maps`] or
+/// [`RecordingDevice::set_fail_maps_of`].
+#[derive(Debug, Default)]
+pub struct RecordingDevice {
+    log: Vec<Call>,
+    buffers: Vec<BufferStorage>,
+    textures: Vec<TextureStorage>,
+    samplers: Vec<SamplerDesc>,
+    pixel_shaders: usize,
+    fail_maps: bool,
+    fail_maps_of: Option<MockBuffer>,
+    /// The timestamps of the timer queries, once completed.
+    timer_queries: Vec<Option<TimestampData>>,
+    surfaces: usize,
+}
+
+impl RecordingDevice {
+    /// Creates a new device with an empty log.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The calls made to this device in order.
+    #[inline]
+    pub fn log(&self) -> &[Call] {
+        &self.log
+    }
+
+    /// Returns the calls made to this device, clearing the log.
+    pub fn take_log(&mut self) -> Vec<Call> {
+        mem::take(&mut self.log)
+    }
+
+    /// Clears the call log.
+    pub fn clear_log(&mut self) {
+        self.log.clear();
+    }
+
+    /// Makes all following [`Device::map`] and [`Device::map_no_overwrite`]
+    /// calls fail with [`MockError::MapFailed`], or succeed again.
+    pub fn set_fail_maps(&mut self, fail: bool) {
+        self.fail_maps = fail;
+    }
+
+    /// Makes all following maps of the given buffer fail with
+    /// [`MockError::MapFailed`], or no buffer in particular with `None`.
+    pub fn set_fail_maps_of(&mut self, buffer: Option<MockBuffer>) {
+        self.fail_maps_of = buffer;
+    }
+
+    fn map_fails(&self, buffer: &MockBuffer) -> bool {
+        self.fail_maps || self.fail_maps_of == Some(*buffer)
+    }
+
+    /// The current contents of the given buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer was not created by this device.
+    pub fn buffer_data(&self, buffer: MockBuffer) -> &[u8] {
+        let storage = &self.buffers[buffer.0];
+        unsafe { slice::from_raw_parts(storage.words.as_ptr().cast(), storage.size) }
+    }
+
+    /// The size of the given texture.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the texture was not created by this device.
+    pub fn texture_size(&self, texture: MockTexture) -> [u32; 2] {
+        self.textures[texture.0].size
+    }
+
+    /// The description of the given sampler.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sampler was not created by this device.
+    pub fn sampler_desc(&self, sampler: MockSampler) -> SamplerDesc {
+        self.samplers[sampler.0]
+    }
+
+    /// The current tightly packed pixel data of the given texture.
+    ///
+    /// The data of DDS textures is the data of all their subresources in
+    /// order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the texture was not created by this device.
+    pub fn texture_data(&self, texture: MockTexture) -> &[u8] {
+        &self.textures[texture.0].data
+    }
+
+    /// Makes the given timestamps the results of a timer query, as if the
+    /// GPU finished the commands it measures.
+    ///
+    /// The results are reset when the query begins again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the query was not created by this device.
+    pub fn complete_timer_query(&mut self, query: MockTimerQuery, data: TimestampData) {
+        self.timer_queries[query.0] = Some(data);
+    }
+}
+
+impl Device for RecordingDevice {
+    type Error = MockError;
+    type Buffer = MockBuffer;
+    type Texture = MockTexture;
+    type Sampler = MockSampler;
+    type RenderTarget = MockRenderTarget;
+    type Pipeline = ();
+    type PixelShader = MockPixelShader;
+    type StateBlock = TouchedState;
+    type TimerQuery = MockTimerQuery;
+
+    fn create_pipeline(&mut self, desc: &PipelineDesc) -> Result<(), MockError> {
+        self.log.push(Call::CreatePipeline(*desc));
+        Ok(())
+    }
+
+    fn create_pixel_shader(&mut self, bytecode: &[u8]) -> Result<MockPixelShader, MockError> {
+        let shader = MockPixelShader(self.pixel_shaders);
+        self.pixel_shaders += 1;
+        self.log.push(Call::CreatePixelShader { shader, bytecode: bytecode.to_vec() });
+        Ok(shader)
+    }
+
+    fn create_buffer(&mut self, kind: BufferKind, size: usize) -> Result<MockBuffer, MockError> {
+        let buffer = MockBuffer(self.buffers.len());
+        let words = vec![0; (size + mem::size_of::<u64>() - 1) / mem::size_of::<u64>()];
+        self.buffers.push(BufferStorage { words, size });
+        self.log.push(Call::CreateBuffer { buffer, kind, size });
+        Ok(buffer)
+    }
+
+    fn create_texture(
+        &mut self,
+        desc: &TextureDesc,
+        data: &[u8],
+    ) -> Result<MockTexture, MockError> {
+        let texture = MockTexture(self.textures.len());
+        let TextureDesc { width, height, format } = *desc;
+        self.textures.push(TextureStorage { size: [width, height], data: data.to_vec() });
+        self.log.push(Call::CreateTexture { texture, width, height, format });
+        Ok(texture)
+    }
+
+    fn create_dds_texture(&mut self, dds: &DdsTexture<'_>) -> Result<MockTexture, MockError> {
+        let texture = MockTexture(self.textures.len());
+        let data = dds.upload_data().iter().flat_map(|data| data.iter()).copied().collect();
+        self.textures.push(TextureStorage { size: [dds.width, dds.height], data });
+        self.log.push(Call::CreateDdsTexture {
+            texture,
+            width: dds.width,
+            height: dds.height,
+            mip_levels: dds.mip_levels,
+            array_size: dds.array_size,
+            format: dds.format,
+        });
+        Ok(texture)
+    }
+
+    fn update_texture(
+        &mut self,
+        texture: &MockTexture,
+        desc: &TextureDesc,
+        rect: TextureRect,
+        data: &[u8],
+    ) -> Result<(), MockError> {
+        let storage = &mut self.textures[texture.0];
+        let row_len = rect.width as usize * desc.format.bytes_per_pixel();
+        for (y, row) in (rect.y as usize..).zip(data.chunks_exact(row_len)) {
+            let start = y * desc.row_pitch() + rect.x as usize * desc.format.bytes_per_pixel();
+            storage.data[start..start + row_len].copy_from_slice(row);
+        }
+        self.log.push(Call::UpdateTexture { texture: *texture, rect });
+        Ok(())
+    }
+
+    fn create_sampler(&mut self, desc: &SamplerDesc) -> Result<MockSampler, MockError> {
+        let sampler = MockSampler(self.samplers.len());
+        self.samplers.push(*desc);
+        self.log.push(Call::CreateSampler { sampler, desc: *desc });
+        Ok(sampler)
+    }
+
+    fn map(&mut self, buffer: &MockBuffer) -> Result<*mut u8, MockError> {
+        self.log.push(Call::Map(*buffer));
+        if self.map_fails(buffer) {
+            return Err(MockError::MapFailed);
+        }
+        Ok(self.buffers[buffer.0].words.as_mut_ptr().cast())
+    }
+
+    fn map_no_overwrite(&mut self, buffer: &MockBuffer) -> Result<*mut u8, MockError> {
+        self.log.push(Call::MapNoOverwrite(*buffer));
+        if self.map_fails(buffer) {
+            return Err(MockError::MapFailed);
+        }
+        Ok(self.buffers[buffer.0].words.as_mut_ptr().cast())
+    }
+
+    fn unmap(&mut self, buffer: &MockBuffer) {
+        self.log.push(Call::Unmap(*buffer));
+    }
+
+    fn render_target_size(&mut self, target: &MockRenderTarget) -> Result<[u32; 2], MockError> {
+        Ok(target.size)
+    }
+
+    fn set_render_target(&mut self, target: &MockRenderTarget) {
+        self.log.push(Call::SetRenderTarget(*target));
+    }
+
+    fn clear_render_target(&mut self, target: &MockRenderTarget, color: [f32; 4]) {
+        self.log.push(Call::ClearRenderTarget { target: *target, color });
+    }
+
+    fn set_viewport(&mut self, viewport: Viewport) {
+        self.log.push(Call::SetViewport(viewport));
+    }
+
+    fn set_pipeline(&mut self, _pipeline: &()) {
+        self.log.push(Call::SetPipeline);
+    }
+
+    fn set_pixel_shader(
+        &mut self,
+        _pipeline: &(),
+        shader: Option<&MockPixelShader>,
+        texture_alpha: AlphaMode,
+    ) {
+        self.log.push(Call::SetPixelShader { shader: shader.copied(), texture_alpha });
+    }
+
+    fn set_vertex_buffer(&mut self, buffer: &MockBuffer, stride: u32) {
+        self.log.push(Call::SetVertexBuffer { buffer: *buffer, stride });
+    }
+
+    fn set_index_buffer(&mut self, buffer: &MockBuffer, format: IndexFormat) {
+        self.log.push(Call::SetIndexBuffer { buffer: *buffer, format });
+    }
+
+    fn set_constant_buffer(&mut self, buffer: &MockBuffer) {
+        self.log.push(Call::SetConstantBuffer(*buffer));
+    }
+
+    fn set_pixel_constant_buffer(&mut self, buffer: &MockBuffer) {
+        self.log.push(Call::SetPixelConstantBuffer(*buffer));
+    }
+
+    fn set_texture(&mut self, texture: &MockTexture) {
+        self.log.push(Call::SetTexture(*texture));
+    }
+
+    fn set_sampler(&mut self, sampler: &MockSampler) {
+        self.log.push(Call::SetSampler(*sampler));
+    }
+
+    fn set_scissor_rect(&mut self, rect: ScissorRect) {
+        self.log.push(Call::SetScissorRect(rect));
+    }
+
+    fn draw_indexed(&mut self, index_count: u32, start_index: u32, base_vertex: i32) {
+        self.log.push(Call::DrawIndexed { index_count, start_index, base_vertex });
+    }
+
+    fn capture_state(&mut self, touched: &TouchedState) -> TouchedState {
+        self.log.push(Call::CaptureState(*touched));
+        *touched
+    }
+
+    fn apply_state(&mut self, state: &TouchedState) {
+        self.log.push(Call::ApplyState(*state));
+    }
+
+    fn create_timer_query(&mut self) -> Result<MockTimerQuery, MockError> {
+        let query = MockTimerQuery(self.timer_queries.len());
+        self.timer_queries.push(None);
+        self.log.push(Call::CreateTimerQuery(query));
+        Ok(query)
+    }
+
+    fn begin_timer_query(&mut self, query: &MockTimerQuery) {
+        self.timer_queries[query.0] = None;
+        self.log.push(Call::BeginTimerQuery(*query));
+    }
+
+    fn end_timer_query(&mut self, query: &MockTimerQuery) {
+        self.log.push(Call::EndTimerQuery(*query));
+    }
+
+    fn timer_query_data(&mut self, query: &MockTimerQuery) -> Option<TimestampData> {
+        self.timer_queries[query.0]
+    }
+}
+
+impl SurfaceDevice for RecordingDevice {
+    type Window = MockWindow;
+    type Surface = MockSurface;
+
+    fn create_surface(
+        &mut self,
+        window: &MockWindow,
+        size: [u32; 2],
+    ) -> Result<MockSurface, MockError> {
+        let surface = MockSurface { target: MockRenderTarget { id: self.surfaces, size } };
+        self.surfaces += 1;
+        self.log.push(Call::CreateSurface { surface: surface.id(), window: *window, size });
+        Ok(surface)
+    }
+
+    fn resize_surface(
+        &mut self,
+        surface: &mut MockSurface,
+        size: [u32; 2],
+    ) -> Result<(), MockError> {
+        surface.target.size = size;
+        self.log.push(Call::ResizeSurface { surface: surface.id(), size });
+        Ok(())
+    }
+
+    fn surface_target(surface: &MockSurface) -> Option<&MockRenderTarget> {
+        Some(&surface.target)
+    }
+
+    fn present(&mut self, surface: &MockSurface) -> Result<(), MockError> {
+        self.log.push(Call::Present(surface.id()));
+        Ok(())
+    }
+}
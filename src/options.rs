@@ -0,0 +1,359 @@
+//! Construction time configuration of the [`Renderer`].
+//!
+//! [`RendererOptions`] collects everything fixed when a renderer is created,
+//! [`RendererBuilder`] sets them one at a time. Options are validated before
+//! the device is called, so invalid options never create device objects.
+
+use alloc::string::String;
+use core::{fmt, mem};
+
+use imgui::{DrawIdx, DrawVert};
+
+use crate::color::ColorSpace;
+use crate::device::{DepthStencilDesc, Device, RasterizerDesc};
+use crate::texture::{AlphaMode, SamplerDesc};
+use crate::Renderer;
+
+/// The number of vertices the vertex buffer holds initially by default.
+pub const DEFAULT_VERTEX_CAPACITY: usize = 5000;
+
+/// The number of indices the index buffer holds initially by default.
+pub const DEFAULT_INDEX_CAPACITY: usize = 10000;
+
+/// How the vertex and index buffers grow when a frame exceeds their
+/// capacity.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GrowthPolicy {
+    /// Grows a buffer to the required size plus its initial capacity.
+    Linear,
+    /// Grows a buffer to exactly the required size.
+    Exact,
+    /// Grows a buffer by a factor greater than 1.0 of its capacity, or to
+    /// the required size if that's larger.
+    ///
+    /// Growing UIs reallocate a logarithmic number of times.
+    Geometric(f32),
+}
+
+impl Default for GrowthPolicy {
+    fn default() -> Self {
+        GrowthPolicy::Linear
+    }
+}
+
+impl GrowthPolicy {
+    /// The capacity to reallocate a buffer of `capacity` elements created
+    /// with `initial` elements with to hold `required` elements.
+    ///
+    /// Never less than `required` or `initial`.
+    pub fn capacity(self, required: usize, capacity: usize, initial: usize) -> usize {
+        let capacity = match self {
+            GrowthPolicy::Linear => required.saturating_add(initial),
+            GrowthPolicy::Exact => required,
+            // float to int casts saturate
+            GrowthPolicy::Geometric(factor) => {
+                required.max((capacity as f64 * f64::from(factor)).ceil() as usize)
+            },
+        };
+        capacity.max(initial)
+    }
+}
+
+/// When the vertex and index buffers shrink after a spike.
+///
+/// Buffers shrink after a number of consecutive idle frames to fit the
+/// largest of these frames according to the [`GrowthPolicy`], but never
+/// below their initial capacity.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShrinkPolicy {
+    /// The number of consecutive idle frames after which a buffer shrinks.
+    pub idle_frames: u32,
+    /// The fraction of a buffer's capacity in `0.0..1.0` frames require at
+    /// most to count as idle.
+    ///
+    /// Keeping it well below the usage right after growing avoids
+    /// reallocating back and forth.
+    pub threshold: f32,
+}
+
+impl ShrinkPolicy {
+    /// Creates a new shrink policy.
+    #[inline]
+    pub const fn new(idle_frames: u32, threshold: f32) -> Self {
+        ShrinkPolicy { idle_frames, threshold }
+    }
+}
+
+impl Default for ShrinkPolicy {
+    /// Shrinks after 300 frames, five seconds at 60 Hz, using at most a
+    /// quarter of the capacity.
+    fn default() -> Self {
+        ShrinkPolicy::new(300, 0.25)
+    }
+}
+
+/// The configuration of a [`Renderer`] fixed at construction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RendererOptions {
+    /// The number of vertices the vertex buffer holds initially.
+    pub vertex_capacity: usize,
+    /// The number of indices the index buffer holds initially.
+    pub index_capacity: usize,
+    /// How the vertex and index buffers grow.
+    pub growth_policy: GrowthPolicy,
+    /// When the vertex and index buffers shrink, `None` to never shrink
+    /// them.
+    pub shrink_policy: Option<ShrinkPolicy>,
+    /// The sampler textures are drawn with unless set otherwise with
+    /// [`Renderer::set_texture_sampler`].
+    pub default_sampler: SamplerDesc,
+    /// The alpha mode colors are blended and output in, see
+    /// [`Renderer::with_device_and_alpha`].
+    pub alpha: AlphaMode,
+    /// The color space of the render targets drawn into, see
+    /// [`Renderer::set_color_space`].
+    pub color_space: ColorSpace,
+    /// The rasterizer state draw calls are made with.
+    pub rasterizer: RasterizerDesc,
+    /// The depth stencil state draw calls are made with.
+    pub depth_stencil: DepthStencilDesc,
+    /// The renderer name reported to imgui.
+    pub renderer_name: String,
+}
+
+impl Default for RendererOptions {
+    fn default() -> Self {
+        RendererOptions {
+            vertex_capacity: DEFAULT_VERTEX_CAPACITY,
+            index_capacity: DEFAULT_INDEX_CAPACITY,
+            growth_policy: GrowthPolicy::default(),
+            shrink_policy: None,
+            default_sampler: SamplerDesc::default(),
+            alpha: AlphaMode::default(),
+            color_space: ColorSpace::default(),
+            rasterizer: RasterizerDesc::default(),
+            depth_stencil: DepthStencilDesc::default(),
+            renderer_name: String::from(concat!("imgui_dx11_renderer@", env!("CARGO_PKG_VERSION"))),
+        }
+    }
+}
+
+impl RendererOptions {
+    /// Checks the options for values no device could create a renderer with.
+    pub fn validate(&self) -> Result<(), OptionsError> {
+        if self.vertex_capacity == 0 || self.index_capacity == 0 {
+            return Err(OptionsError::ZeroCapacity);
+        }
+        // buffer sizes are 32 bit in d3d11
+        let too_large = |capacity: usize, size: usize| {
+            capacity.checked_mul(size).map_or(true, |bytes| bytes > u32::MAX as usize)
+        };
+        if too_large(self.vertex_capacity, mem::size_of::<DrawVert>())
+            || too_large(self.index_capacity, mem::size_of::<DrawIdx>())
+        {
+            return Err(OptionsError::CapacityTooLarge);
+        }
+        if let GrowthPolicy::Geometric(factor) = self.growth_policy {
+            // also rejects NaN
+            if !(factor > 1.0 && factor.is_finite()) {
+                return Err(OptionsError::InvalidGrowthFactor);
+            }
+        }
+        if let Some(shrink) = self.shrink_policy {
+            if shrink.idle_frames == 0 || !(0.0..1.0).contains(&shrink.threshold) {
+                return Err(OptionsError::InvalidShrinkPolicy);
+            }
+        }
+        if self.depth_stencil.depth_write && self.depth_stencil.depth_test.is_none() {
+            return Err(OptionsError::InvalidDepthStencil);
+        }
+        if self.renderer_name.contains('\0') {
+            return Err(OptionsError::InvalidRendererName);
+        }
+        Ok(())
+    }
+}
+
+/// Builds a [`Renderer`] out of [`RendererOptions`] set one at a time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RendererBuilder {
+    options: RendererOptions,
+}
+
+impl RendererBuilder {
+    /// Creates a builder with the default options.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of vertices the vertex buffer holds initially.
+    #[inline]
+    pub fn vertex_capacity(mut self, capacity: usize) -> Self {
+        self.options.vertex_capacity = capacity;
+        self
+    }
+
+    /// Sets the number of indices the index buffer holds initially.
+    #[inline]
+    pub fn index_capacity(mut self, capacity: usize) -> Self {
+        self.options.index_capacity = capacity;
+        self
+    }
+
+    /// Sets how the vertex and index buffers grow.
+    #[inline]
+    pub fn growth_policy(mut self, policy: GrowthPolicy) -> Self {
+        self.options.growth_policy = policy;
+        self
+    }
+
+    /// Sets when the vertex and index buffers shrink, `None` to never shrink
+    /// them.
+    #[inline]
+    pub fn shrink_policy(mut self, policy: Option<ShrinkPolicy>) -> Self {
+        self.options.shrink_policy = policy;
+        self
+    }
+
+    /// Sets the sampler textures are drawn with by default.
+    #[inline]
+    pub fn default_sampler(mut self, desc: SamplerDesc) -> Self {
+        self.options.default_sampler = desc;
+        self
+    }
+
+    /// Sets the alpha mode colors are blended and output in.
+    #[inline]
+    pub fn alpha(mut self, alpha: AlphaMode) -> Self {
+        self.options.alpha = alpha;
+        self
+    }
+
+    /// Sets the color space of the render targets drawn into.
+    #[inline]
+    pub fn color_space(mut self, color_space: ColorSpace) -> Self {
+        self.options.color_space = color_space;
+        self
+    }
+
+    /// Sets the rasterizer state draw calls are made with.
+    #[inline]
+    pub fn rasterizer(mut self, desc: RasterizerDesc) -> Self {
+        self.options.rasterizer = desc;
+        self
+    }
+
+    /// Sets the depth stencil state draw calls are made with.
+    #[inline]
+    pub fn depth_stencil(mut self, desc: DepthStencilDesc) -> Self {
+        self.options.depth_stencil = desc;
+        self
+    }
+
+    /// Sets the renderer name reported to imgui.
+    #[inline]
+    pub fn renderer_name(mut self, name: impl Into<String>) -> Self {
+        self.options.renderer_name = name.into();
+        self
+    }
+
+    /// The options set so far.
+    #[inline]
+    pub fn options(&self) -> &RendererOptions {
+        &self.options
+    }
+
+    /// Validates the options and creates a renderer issuing its commands to
+    /// the given [`Device`].
+    pub fn build<D: Device>(
+        self,
+        im_ctx: &mut imgui::Context,
+        device: D,
+    ) -> Result<Renderer<D>, BuildError<D::Error>> {
+        Renderer::with_options(im_ctx, device, &self.options)
+    }
+}
+
+impl From<RendererOptions> for RendererBuilder {
+    fn from(options: RendererOptions) -> Self {
+        RendererBuilder { options }
+    }
+}
+
+/// The error returned for [`RendererOptions`] no renderer can be created
+/// with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OptionsError {
+    /// A buffer capacity is zero.
+    ZeroCapacity,
+    /// A buffer capacity exceeds the maximum buffer size.
+    CapacityTooLarge,
+    /// A geometric growth factor isn't a finite number greater than 1.0.
+    InvalidGrowthFactor,
+    /// A shrink policy waits for zero frames or its threshold is outside
+    /// `0.0..1.0`.
+    InvalidShrinkPolicy,
+    /// Depth writes are enabled without a depth test.
+    InvalidDepthStencil,
+    /// The renderer name contains a nul character.
+    InvalidRendererName,
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionsError::ZeroCapacity => write!(f, "buffer capacities must not be zero"),
+            OptionsError::CapacityTooLarge => {
+                write!(f, "buffer capacity exceeds the maximum buffer size")
+            },
+            OptionsError::InvalidGrowthFactor => {
+                write!(f, "growth factor must be a finite number greater than 1.0")
+            },
+            OptionsError::InvalidShrinkPolicy => write!(f, "invalid buffer shrink policy"),
+            OptionsError::InvalidDepthStencil => {
+                write!(f, "depth writes require a depth test")
+            },
+            OptionsError::InvalidRendererName => {
+                write!(f, "renderer name contains a nul character")
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OptionsError {}
+
+/// The error returned when building a [`Renderer`] fails.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BuildError<E> {
+    /// The options are invalid.
+    Options(OptionsError),
+    /// Creating the device objects failed.
+    Device(E),
+}
+
+impl<E: fmt::Display> fmt::Display for BuildError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Options(e) => e.fmt(f),
+            BuildError::Device(e) => e.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for BuildError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BuildError::Options(e) => Some(e),
+            BuildError::Device(e) => Some(e),
+        }
+    }
+}
+
+impl<E> From<OptionsError> for BuildError<E> {
+    fn from(e: OptionsError) -> Self {
+        BuildError::Options(e)
+    }
+}
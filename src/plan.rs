@@ -0,0 +1,354 @@
+//! Platform independent planning of the draw calls required to render a
+//! [`DrawData`].
+//!
+//! A [`DrawPlan`] is a flat list of [`DrawOp`]s which a backend executes in
+//! order. Building the plan resolves texture ids, computes the scissor
+//! rectangles and the buffer offsets of every draw, so none of this logic
+//! depends on a graphics API.
+
+use alloc::vec::Vec;
+use core::{fmt, mem};
+
+use imgui::internal::RawWrapper;
+use imgui::{sys, DrawCmd, DrawCmdParams, DrawData, TextureId, Textures};
+
+use crate::device::TouchedState;
+use crate::effects::{self, EffectId};
+use crate::FONT_TEX_ID;
+
+/// A scissor rectangle in framebuffer pixel coordinates.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ScissorRect {
+    /// The left edge of the rectangle.
+    pub left: i32,
+    /// The top edge of the rectangle.
+    pub top: i32,
+    /// The right edge of the rectangle, exclusive.
+    pub right: i32,
+    /// The bottom edge of the rectangle, exclusive.
+    pub bottom: i32,
+}
+
+impl ScissorRect {
+    /// Computes the framebuffer scissor rectangle of an imgui clip rectangle.
+    ///
+    /// `clip_off` is the display position and `clip_scale` the framebuffer
+    /// scale of the [`DrawData`] the clip rectangle belongs to.
+    #[inline]
+    pub fn from_clip_rect(clip_rect: [f32; 4], clip_off: [f32; 2], clip_scale: [f32; 2]) -> Self {
+        ScissorRect {
+            left: ((clip_rect[0] - clip_off[0]) * clip_scale[0]) as i32,
+            top: ((clip_rect[1] - clip_off[1]) * clip_scale[1]) as i32,
+            right: ((clip_rect[2] - clip_off[0]) * clip_scale[0]) as i32,
+            bottom: ((clip_rect[3] - clip_off[1]) * clip_scale[1]) as i32,
+        }
+    }
+}
+
+/// Computes the orthographic projection mapping the display rectangle of a
+/// [`DrawData`] to clip space.
+///
+/// The matrix is laid out the way the vertex shader's constant buffer
+/// expects it, that is `clip = m[0] * x + m[1] * y + m[2] * z + m[3] * w`.
+pub fn orthographic_projection(display_pos: [f32; 2], display_size: [f32; 2]) -> [[f32; 4]; 4] {
+    let l = display_pos[0];
+    let r = display_pos[0] + display_size[0];
+    let t = display_pos[1];
+    let b = display_pos[1] + display_size[1];
+    [
+        [2.0 / (r - l), 0.0, 0.0, 0.0],
+        [0.0, 2.0 / (t - b), 0.0, 0.0],
+        [0.0, 0.0, 0.5, 0.0],
+        [(r + l) / (l - r), (t + b) / (b - t), 0.5, 1.0],
+    ]
+}
+
+/// A user callback of a draw list, see [`DrawCmd::RawCallback`].
+#[derive(Copy, Clone, Debug)]
+pub struct RawCallback {
+    callback: unsafe extern "C" fn(*const sys::ImDrawList, *const sys::ImDrawCmd),
+    draw_list: *const sys::ImDrawList,
+    raw_cmd: *const sys::ImDrawCmd,
+}
+
+impl RawCallback {
+    /// Invokes the callback.
+    ///
+    /// # Safety
+    ///
+    /// The [`DrawData`] the plan was built from must still be alive.
+    #[inline]
+    pub unsafe fn invoke(&self) {
+        (self.callback)(self.draw_list, self.raw_cmd)
+    }
+
+    /// The raw draw list this callback belongs to.
+    #[inline]
+    pub fn draw_list(&self) -> *const sys::ImDrawList {
+        self.draw_list
+    }
+
+    /// The raw draw command holding this callback.
+    #[inline]
+    pub fn raw_cmd(&self) -> *const sys::ImDrawCmd {
+        self.raw_cmd
+    }
+}
+
+impl PartialEq for RawCallback {
+    // the draw command uniquely identifies the callback, comparing function
+    // pointers is unreliable
+    fn eq(&self, other: &Self) -> bool {
+        self.draw_list == other.draw_list && self.raw_cmd == other.raw_cmd
+    }
+}
+
+/// A single operation of a [`DrawPlan`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DrawOp {
+    /// Binds the font atlas texture.
+    SetFontTexture,
+    /// Binds the user texture registered under the given id.
+    SetTexture(TextureId),
+    /// Sets the scissor rectangle.
+    SetScissor(ScissorRect),
+    /// Draws indexed triangles out of the frame's vertex and index buffers.
+    DrawIndexed {
+        /// The number of indices to draw.
+        index_count: u32,
+        /// The location of the first index in the index buffer.
+        start_index: u32,
+        /// The value added to each index before reading the vertex buffer.
+        base_vertex: i32,
+    },
+    /// Resets the render state to the renderer's defaults.
+    ResetRenderState,
+    /// Draws the following commands with the effect of an effect marker, or
+    /// ends the effect of the previous marker for `None`.
+    ///
+    /// The effect of a marker ends with its draw list.
+    SetEffect(Option<EffectId>),
+    /// Invokes a user callback.
+    Callback(RawCallback),
+}
+
+/// The error returned when a [`DrawData`] references a texture that is not
+/// registered with the renderer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UnknownTexture(pub TextureId);
+
+impl fmt::Display for UnknownTexture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown texture id {}", self.0.id())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnknownTexture {}
+
+/// The sequence of [`DrawOp`]s required to render a [`DrawData`].
+///
+/// The plan assumes the vertex and index buffers of all draw lists are
+/// uploaded back to back in draw list order. Draws honor the vertex and index
+/// offsets of their commands, so draw lists exceeding the range of 16 bit
+/// indices are rendered correctly.
+///
+/// Commands drawing nothing, as they have no elements or are clipped
+/// entirely, are skipped. Consecutive commands with the same texture and
+/// scissor rectangle drawing contiguous ranges of the index buffer are
+/// merged into a single draw, and scissor rectangles are only set when they
+/// change. User callbacks may change any state, so they end such runs and
+/// the texture and scissor rectangle are set again after them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DrawPlan {
+    ops: Vec<DrawOp>,
+    skipped: u32,
+}
+
+impl DrawPlan {
+    /// Creates an empty plan.
+    #[inline]
+    pub const fn new() -> Self {
+        DrawPlan { ops: Vec::new(), skipped: 0 }
+    }
+
+    /// Builds the plan for the given [`DrawData`], resolving texture ids
+    /// against `textures`.
+    pub fn build<T>(draw_data: &DrawData, textures: &Textures<T>) -> Result<Self, UnknownTexture> {
+        let mut plan = Self::new();
+        plan.rebuild(draw_data, textures)?;
+        Ok(plan)
+    }
+
+    /// Replaces the contents of this plan with the plan for the given
+    /// [`DrawData`], reusing the allocation.
+    ///
+    /// On error the plan is left empty.
+    pub fn rebuild<T>(
+        &mut self,
+        draw_data: &DrawData,
+        textures: &Textures<T>,
+    ) -> Result<(), UnknownTexture> {
+        self.ops.clear();
+        self.skipped = 0;
+        let res = self.push_ops(draw_data, textures);
+        if res.is_err() {
+            self.ops.clear();
+            self.skipped = 0;
+        }
+        res
+    }
+
+    fn push_ops<T>(
+        &mut self,
+        draw_data: &DrawData,
+        textures: &Textures<T>,
+    ) -> Result<(), UnknownTexture> {
+        let clip_off = draw_data.display_pos;
+        let clip_scale = draw_data.framebuffer_scale;
+        // offsets of the current draw list's buffers in the combined buffers
+        let mut list_vertex_offset = 0;
+        let mut list_index_offset = 0;
+        // `None` once a callback may have bound another texture
+        let mut last_tex = Some(TextureId::from(FONT_TEX_ID));
+        let mut last_scissor = None;
+        self.ops.push(DrawOp::SetFontTexture);
+        // imgui leaves the draw list array null for frames without draw lists
+        if draw_data.draw_lists_count() == 0 {
+            return Ok(());
+        }
+        for draw_list in draw_data.draw_lists() {
+            let mut has_effect = false;
+            // whether the previous command started an effect marker
+            let mut in_marker = false;
+            for cmd in draw_list.commands() {
+                let marker = mem::replace(&mut in_marker, false);
+                match cmd {
+                    DrawCmd::Elements {
+                        count,
+                        cmd_params: DrawCmdParams { clip_rect, texture_id, vtx_offset, idx_offset },
+                    } => {
+                        let rect = ScissorRect::from_clip_rect(clip_rect, clip_off, clip_scale);
+                        if count == 0 || rect.right <= rect.left || rect.bottom <= rect.top {
+                            self.skipped += 1;
+                            continue;
+                        }
+                        if last_tex != Some(texture_id) {
+                            if texture_id.id() == FONT_TEX_ID {
+                                self.ops.push(DrawOp::SetFontTexture);
+                            } else if textures.get(texture_id).is_some() {
+                                self.ops.push(DrawOp::SetTexture(texture_id));
+                            } else {
+                                return Err(UnknownTexture(texture_id));
+                            }
+                            last_tex = Some(texture_id);
+                        }
+
+                        if last_scissor != Some(rect) {
+                            self.ops.push(DrawOp::SetScissor(rect));
+                            last_scissor = Some(rect);
+                        }
+                        self.push_draw(
+                            count as u32,
+                            (list_index_offset + idx_offset) as u32,
+                            (list_vertex_offset + vtx_offset) as i32,
+                        );
+                    },
+                    DrawCmd::ResetRenderState => {
+                        self.ops.push(DrawOp::ResetRenderState);
+                        last_scissor = None;
+                    },
+                    DrawCmd::RawCallback { callback, raw_cmd } => {
+                        if marker {
+                            let effect = effects::marker_effect(raw_cmd);
+                            has_effect = effect.is_some();
+                            self.ops.push(DrawOp::SetEffect(effect));
+                        } else if effects::is_effect_marker(raw_cmd) {
+                            in_marker = true;
+                        } else {
+                            self.ops.push(DrawOp::Callback(RawCallback {
+                                callback,
+                                draw_list: unsafe { draw_list.raw() },
+                                raw_cmd,
+                            }));
+                            last_tex = None;
+                            last_scissor = None;
+                        }
+                    },
+                }
+            }
+            if has_effect {
+                self.ops.push(DrawOp::SetEffect(None));
+            }
+            list_vertex_offset += draw_list.vtx_buffer().len();
+            list_index_offset += draw_list.idx_buffer().len();
+        }
+        Ok(())
+    }
+
+    /// Pushes a draw, extending the previous draw instead if it directly
+    /// precedes it in the index buffer with the same base vertex.
+    fn push_draw(&mut self, index_count: u32, start_index: u32, base_vertex: i32) {
+        if let Some(DrawOp::DrawIndexed {
+            index_count: last_count,
+            start_index: last_start,
+            base_vertex: last_base,
+        }) = self.ops.last_mut()
+        {
+            if *last_base == base_vertex && *last_start + *last_count == start_index {
+                *last_count += index_count;
+                return;
+            }
+        }
+        self.ops.push(DrawOp::DrawIndexed { index_count, start_index, base_vertex });
+    }
+
+    /// Whether executing this plan sets up the render state at all, that is
+    /// whether it draws or invokes callbacks expecting the render state.
+    pub fn draws(&self) -> bool {
+        self.ops.iter().any(|op| {
+            matches!(
+                op,
+                DrawOp::DrawIndexed { .. } | DrawOp::ResetRenderState | DrawOp::Callback(_)
+            )
+        })
+    }
+
+    /// The device state executing this plan touches, not including the
+    /// render targets bound when rendering into a target.
+    ///
+    /// Plans which don't [draw](DrawPlan::draws) touch nothing. All others
+    /// touch the pipeline, the viewport, the scissor rectangle, the first
+    /// vertex buffer, texture and sampler slots, the renderer's constants in
+    /// the first vertex shader and second pixel shader constant buffer slots
+    /// and effect constants in the first pixel shader slot.
+    pub fn touched_state(&self) -> TouchedState {
+        if !self.draws() {
+            return TouchedState::default();
+        }
+        TouchedState {
+            render_targets: false,
+            viewports: true,
+            scissor_rects: true,
+            pipeline: true,
+            index_buffer: true,
+            vertex_buffers: 1,
+            vs_constant_buffers: 1,
+            ps_constant_buffers: 2,
+            ps_textures: 1,
+            ps_samplers: 1,
+        }
+    }
+
+    /// The number of draw commands skipped as they draw nothing.
+    #[inline]
+    pub fn skipped_commands(&self) -> u32 {
+        self.skipped
+    }
+
+    /// The operations of this plan in execution order.
+    #[inline]
+    pub fn ops(&self) -> &[DrawOp] {
+        &self.ops
+    }
+}
@@ -0,0 +1,1146 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::hash::{Hash, Hasher};
+use core::{mem, slice};
+
+use imgui::internal::RawCast;
+use imgui::{sys, BackendFlags, DrawData, DrawIdx, DrawVert, FontAtlas, TextureId, Textures};
+
+use crate::capacity::{CapacityPlanner, ReallocStats};
+use crate::color::{self, ColorSpace};
+use crate::dds::DdsTexture;
+#[cfg(feature = "decode")]
+use crate::decode::{self, DecodedImage, LoadTextureError};
+use crate::device::{
+    BufferKind, Device, IndexFormat, PipelineDesc, StatePolicy, TouchedState, Viewport,
+};
+use crate::effects::{EffectError, EffectId};
+use crate::options::{BuildError, RendererOptions};
+use crate::plan::{orthographic_projection, DrawOp, DrawPlan, UnknownTexture};
+use crate::ring::{RingAllocation, RingBuffer};
+use crate::stats::FrameStats;
+use crate::texture::{
+    premultiply_alpha, AlphaMode, SamplerDesc, TextureDesc, TextureError, TextureFormat,
+    TextureRect,
+};
+use crate::timing::{GpuTimer, DEFAULT_HISTORY_LEN, DEFAULT_TIMER_QUERIES};
+use crate::FONT_TEX_ID;
+
+/// The constants of the vertex shader, also bound to the second slot of the
+/// pixel shader.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C)]
+struct VertexConstantBuffer {
+    mvp: [[f32; 4]; 4],
+    paper_white: f32,
+    _padding: [f32; 3],
+}
+
+/// A DirectX 11 renderer for (Imgui-rs)[https://docs.rs/imgui/*/imgui/].
+///
+/// The renderer issues all of its commands through a [`Device`], which
+/// defaults to the d3d11 device on windows. Other targets have no default
+/// device.
+#[derive(Debug)]
+pub struct Renderer<
+    #[cfg(windows)] D: Device = crate::dx11::Dx11Device,
+    #[cfg(not(windows))] D: Device,
+> {
+    device: D,
+    pipeline_desc: PipelineDesc,
+    pipeline: D::Pipeline,
+    paper_white: f32,
+    state_policy: StatePolicy,
+    constant_buffer: D::Buffer,
+    /// The constants last written, rewritten only when they change.
+    constants: Option<VertexConstantBuffer>,
+    font_texture: D::Texture,
+    vertex_buffer: Buffer<D::Buffer>,
+    index_buffer: Buffer<D::Buffer>,
+    vertex_ring: RingBuffer,
+    index_ring: RingBuffer,
+    /// The offsets of the current frame's data in the streaming buffers.
+    vertex_offset: usize,
+    index_offset: usize,
+    vertex_planner: CapacityPlanner,
+    index_planner: CapacityPlanner,
+    default_sampler: SamplerDesc,
+    textures: Textures<D::Texture>,
+    texture_descs: BTreeMap<usize, TextureDesc>,
+    samplers: BTreeMap<SamplerDesc, D::Sampler>,
+    texture_samplers: BTreeMap<usize, SamplerDesc>,
+    texture_alphas: BTreeMap<usize, AlphaMode>,
+    effects: BTreeMap<EffectId, Effect<D::PixelShader, D::Buffer>>,
+    next_effect: usize,
+    texture_effects: BTreeMap<usize, EffectId>,
+    plan: DrawPlan,
+    frame_stats: FrameStats,
+    gpu_timer: Option<GpuTimer<D::TimerQuery>>,
+    font_atlas_fingerprint: u64,
+}
+
+impl<D: Device> Renderer<D> {
+    /// Creates a new renderer issuing its commands to the given [`Device`].
+    pub fn with_device(im_ctx: &mut imgui::Context, device: D) -> Result<Self, D::Error> {
+        Self::with_device_and_alpha(im_ctx, device, AlphaMode::Straight)
+    }
+
+    /// Creates a new renderer issuing its commands to the given [`Device`],
+    /// blending and outputting colors in the given alpha mode.
+    ///
+    /// With [`AlphaMode::Premultiplied`] the rendered colors are multiplied by
+    /// their alpha, as expected when compositing the render target over other
+    /// content. Textures are still assumed to hold straight alpha unless set
+    /// otherwise with [`Renderer::set_texture_alpha`].
+    pub fn with_device_and_alpha(
+        im_ctx: &mut imgui::Context,
+        device: D,
+        alpha: AlphaMode,
+    ) -> Result<Self, D::Error> {
+        Self::with_valid_options(im_ctx, device, &RendererOptions { alpha, ..Default::default() })
+    }
+
+    /// Creates a new renderer issuing its commands to the given [`Device`],
+    /// configured by the given options.
+    ///
+    /// The options are validated before the device is called, see
+    /// [`RendererOptions::validate`].
+    pub fn with_options(
+        im_ctx: &mut imgui::Context,
+        device: D,
+        options: &RendererOptions,
+    ) -> Result<Self, BuildError<D::Error>> {
+        options.validate()?;
+        Self::with_valid_options(im_ctx, device, options).map_err(BuildError::Device)
+    }
+
+    fn with_valid_options(
+        im_ctx: &mut imgui::Context,
+        mut device: D,
+        options: &RendererOptions,
+    ) -> Result<Self, D::Error> {
+        let pipeline_desc = PipelineDesc {
+            alpha: options.alpha,
+            color_space: options.color_space,
+            rasterizer: options.rasterizer,
+            depth_stencil: options.depth_stencil,
+        };
+        let pipeline = device.create_pipeline(&pipeline_desc)?;
+        let constant_buffer =
+            device.create_buffer(BufferKind::Constant, mem::size_of::<VertexConstantBuffer>())?;
+        let default_sampler = options.default_sampler;
+        let mut samplers = BTreeMap::new();
+        samplers.insert(default_sampler, device.create_sampler(&default_sampler)?);
+        let font_texture = Self::create_font_texture(im_ctx.fonts(), &mut device, &pipeline_desc)?;
+        let font_atlas_fingerprint = font_atlas_fingerprint(&im_ctx.fonts());
+        let vertex_buffer = Self::create_vertex_buffer(&mut device, options.vertex_capacity)?;
+        let index_buffer = Self::create_index_buffer(&mut device, options.index_capacity)?;
+
+        im_ctx.io_mut().backend_flags |= BackendFlags::RENDERER_HAS_VTX_OFFSET;
+        // validated options hold no nul characters
+        im_ctx.set_renderer_name(Some(options.renderer_name.parse().unwrap()));
+
+        Ok(Renderer {
+            device,
+            pipeline_desc,
+            pipeline,
+            paper_white: color::DEFAULT_PAPER_WHITE_NITS,
+            state_policy: StatePolicy::Full,
+            constant_buffer,
+            constants: None,
+            font_texture,
+            vertex_ring: RingBuffer::new(vertex_buffer.len()),
+            index_ring: RingBuffer::new(index_buffer.len()),
+            vertex_offset: 0,
+            index_offset: 0,
+            vertex_buffer,
+            index_buffer,
+            vertex_planner: CapacityPlanner::new(
+                options.vertex_capacity,
+                options.growth_policy,
+                options.shrink_policy,
+            ),
+            index_planner: CapacityPlanner::new(
+                options.index_capacity,
+                options.growth_policy,
+                options.shrink_policy,
+            ),
+            default_sampler,
+            textures: Textures::new(),
+            texture_descs: BTreeMap::new(),
+            samplers,
+            texture_samplers: BTreeMap::new(),
+            texture_alphas: BTreeMap::new(),
+            effects: BTreeMap::new(),
+            next_effect: 0,
+            texture_effects: BTreeMap::new(),
+            plan: DrawPlan::new(),
+            frame_stats: FrameStats::default(),
+            gpu_timer: None,
+            font_atlas_fingerprint,
+        })
+    }
+
+    /// The alpha mode this renderer blends and outputs colors in.
+    #[inline]
+    pub fn alpha(&self) -> AlphaMode {
+        self.pipeline_desc.alpha
+    }
+
+    /// The color space of the render targets this renderer draws into.
+    #[inline]
+    pub fn color_space(&self) -> ColorSpace {
+        self.pipeline_desc.color_space
+    }
+
+    /// Sets the color space of the render targets this renderer draws into,
+    /// recreating the pipeline and the font texture.
+    ///
+    /// [`ColorSpace::Linear`] is meant for `_SRGB` render targets. Imgui's
+    /// sRGB colors are then converted to linear, so the output matches the
+    /// one of [`ColorSpace::Srgb`] on `UNORM` render targets. The HDR color
+    /// spaces additionally output white at the [paper white] level.
+    ///
+    /// [paper white]: Renderer::set_paper_white
+    pub fn set_color_space(
+        &mut self,
+        im_ctx: &mut imgui::Context,
+        color_space: ColorSpace,
+    ) -> Result<(), D::Error> {
+        let desc = PipelineDesc { color_space, ..self.pipeline_desc };
+        self.pipeline = self.device.create_pipeline(&desc)?;
+        self.pipeline_desc = desc;
+        self.reload_font_texture(im_ctx)
+    }
+
+    /// The brightness of white UI elements in nits for the HDR color spaces.
+    #[inline]
+    pub fn paper_white(&self) -> f32 {
+        self.paper_white
+    }
+
+    /// Sets the brightness of white UI elements in nits for the HDR color
+    /// spaces, clamped to `1.0..=10000.0`.
+    ///
+    /// Defaults to [`DEFAULT_PAPER_WHITE_NITS`], takes effect with the next
+    /// render call.
+    ///
+    /// [`DEFAULT_PAPER_WHITE_NITS`]: color::DEFAULT_PAPER_WHITE_NITS
+    pub fn set_paper_white(&mut self, nits: f32) {
+        // NaN falls back to the default
+        let nits = if nits.is_nan() { color::DEFAULT_PAPER_WHITE_NITS } else { nits };
+        self.paper_white = nits.clamp(1.0, color::PQ_MAX_NITS);
+    }
+
+    /// How much of the device state is preserved around render calls.
+    #[inline]
+    pub fn state_policy(&self) -> StatePolicy {
+        self.state_policy
+    }
+
+    /// Sets how much of the device state is preserved around render calls,
+    /// [`StatePolicy::Full`] by default.
+    ///
+    /// With [`StatePolicy::None`] the renderer's state stays bound after
+    /// rendering, including the render target bound by
+    /// [`Renderer::render_to`].
+    #[inline]
+    pub fn set_state_policy(&mut self, policy: StatePolicy) {
+        self.state_policy = policy;
+    }
+
+    /// How often the vertex buffer has been reallocated.
+    #[inline]
+    pub fn vertex_buffer_reallocs(&self) -> ReallocStats {
+        self.vertex_planner.stats()
+    }
+
+    /// How often the index buffer has been reallocated.
+    #[inline]
+    pub fn index_buffer_reallocs(&self) -> ReallocStats {
+        self.index_planner.stats()
+    }
+
+    /// The statistics of the last call to [`Renderer::render`] or
+    /// [`Renderer::render_to`].
+    #[inline]
+    pub fn last_frame_stats(&self) -> FrameStats {
+        self.frame_stats
+    }
+
+    /// Starts measuring the GPU time of every render call with the default
+    /// number of queries and history length.
+    ///
+    /// See [`Renderer::enable_gpu_timing_with`].
+    pub fn enable_gpu_timing(&mut self) -> Result<(), D::Error> {
+        self.enable_gpu_timing_with(DEFAULT_TIMER_QUERIES, DEFAULT_HISTORY_LEN)
+    }
+
+    /// Starts measuring the GPU time of every render call, keeping the
+    /// timings of the last `history_len` calls.
+    ///
+    /// Results are read back without waiting for the GPU once available,
+    /// usually a few calls later. Calls made while all `queries` are waiting
+    /// for their results aren't measured. Restarts the measurements if timing
+    /// was enabled already.
+    pub fn enable_gpu_timing_with(
+        &mut self,
+        queries: usize,
+        history_len: usize,
+    ) -> Result<(), D::Error> {
+        let queries = (0..queries)
+            .map(|_| self.device.create_timer_query())
+            .collect::<Result<Vec<_>, _>>()?;
+        self.gpu_timer = Some(GpuTimer::new(queries, history_len));
+        Ok(())
+    }
+
+    /// Stops measuring the GPU time of render calls, discarding the timings.
+    #[inline]
+    pub fn disable_gpu_timing(&mut self) {
+        self.gpu_timer = None;
+    }
+
+    /// The GPU timer measuring render calls, if enabled.
+    ///
+    /// Its [history](GpuTimer::history) holds the GPU time of the UI pass of
+    /// the most recent render calls read back.
+    #[inline]
+    pub fn gpu_timer(&self) -> Option<&GpuTimer<D::TimerQuery>> {
+        self.gpu_timer.as_ref()
+    }
+
+    /// The device this renderer issues its commands to.
+    #[inline]
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// The device this renderer issues its commands to.
+    #[inline]
+    pub fn device_mut(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    /// The textures registry of this renderer.
+    ///
+    /// The texture slot at !0 is reserved for the font texture, therefore the
+    /// renderer will ignore any texture inserted into said slot.
+    #[inline]
+    pub fn textures_mut(&mut self) -> &mut Textures<D::Texture> {
+        &mut self.textures
+    }
+
+    /// The textures registry of this renderer.
+    #[inline]
+    pub fn textures(&self) -> &Textures<D::Texture> {
+        &self.textures
+    }
+
+    /// Creates a texture out of tightly packed pixel data and registers it,
+    /// returning its id.
+    ///
+    /// The size of the data is validated against the dimensions and format
+    /// before the device is called.
+    pub fn create_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        data: &[u8],
+    ) -> Result<TextureId, D::Error> {
+        let desc = TextureDesc::new(width, height, format);
+        desc.validate_data(data)?;
+        let texture = self.device.create_texture(&desc, data)?;
+        let id = self.textures.insert(texture);
+        self.texture_descs.insert(id.id(), desc);
+        Ok(id)
+    }
+
+    /// Overwrites a rectangle of a texture created by
+    /// [`Renderer::create_texture`] with tightly packed pixel data in the
+    /// texture's format.
+    ///
+    /// Textures inserted through [`Renderer::textures_mut`] can't be updated
+    /// as their format is unknown, replacing a created texture that way
+    /// leaves the description of the original in place.
+    pub fn update_texture(
+        &mut self,
+        id: TextureId,
+        rect: TextureRect,
+        data: &[u8],
+    ) -> Result<(), D::Error> {
+        let texture = self.textures.get(id).ok_or(TextureError::UnknownTexture(id))?;
+        let desc = self.texture_descs.get(&id.id()).ok_or(TextureError::Unmanaged(id))?;
+        desc.validate_update(rect, data)?;
+        self.device.update_texture(texture, desc, rect, data)
+    }
+
+    /// Decodes a PNG, JPEG or BMP image file and registers it as an RGBA8
+    /// texture, returning its id.
+    ///
+    /// The alpha mode of the texture is set to the one it's decoded with.
+    /// With a linear [color space] the texture is uploaded as sRGB with
+    /// straight alpha, the pixel shader premultiplies the decoded texels.
+    ///
+    /// [color space]: Renderer::color_space
+    #[cfg(feature = "decode")]
+    pub fn load_image_texture(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        alpha: AlphaMode,
+    ) -> Result<TextureId, LoadTextureError<D::Error>> {
+        let alpha = self.decoded_texture_alpha(alpha);
+        let image = decode::load_image(path.as_ref(), alpha)?;
+        self.create_decoded_texture(image, alpha)
+    }
+
+    /// Decodes an in-memory PNG, JPEG or BMP image and registers it as an
+    /// RGBA8 texture, returning its id.
+    ///
+    /// The alpha mode is handled the same way as in
+    /// [`Renderer::load_image_texture`].
+    #[cfg(feature = "decode")]
+    pub fn load_image_texture_from_memory(
+        &mut self,
+        bytes: &[u8],
+        alpha: AlphaMode,
+    ) -> Result<TextureId, LoadTextureError<D::Error>> {
+        let alpha = self.decoded_texture_alpha(alpha);
+        let image = decode::decode_image(bytes, alpha)?;
+        self.create_decoded_texture(image, alpha)
+    }
+
+    /// The alpha mode images requested in `alpha` are decoded in.
+    #[cfg(feature = "decode")]
+    fn decoded_texture_alpha(&self, alpha: AlphaMode) -> AlphaMode {
+        if self.color_space().is_linear() {
+            // premultiplying sRGB encoded texels would darken them once
+            // decoded, just like for the font texture
+            AlphaMode::Straight
+        } else {
+            alpha
+        }
+    }
+
+    #[cfg(feature = "decode")]
+    fn create_decoded_texture(
+        &mut self,
+        image: DecodedImage,
+        alpha: AlphaMode,
+    ) -> Result<TextureId, LoadTextureError<D::Error>> {
+        let format = if self.color_space().is_linear() {
+            TextureFormat::Rgba8Srgb
+        } else {
+            TextureFormat::Rgba8
+        };
+        let id = self
+            .create_texture(image.width, image.height, format, &image.pixels)
+            .map_err(LoadTextureError::Device)?;
+        self.set_texture_alpha(id, alpha);
+        Ok(id)
+    }
+
+    /// Creates a texture out of a parsed DDS file and registers it, returning
+    /// its id.
+    ///
+    /// The pixel data is uploaded in its block-compressed format with all
+    /// mip levels. Texture arrays are bound as a whole, sampling them requires
+    /// a pixel shader declaring a `Texture2DArray`. DDS textures can't be
+    /// modified with [`Renderer::update_texture`].
+    pub fn create_dds_texture(&mut self, dds: &DdsTexture<'_>) -> Result<TextureId, D::Error> {
+        let texture = self.device.create_dds_texture(dds)?;
+        Ok(self.textures.insert(texture))
+    }
+
+    /// The description of a texture created by [`Renderer::create_texture`].
+    pub fn texture_desc(&self, id: TextureId) -> Option<&TextureDesc> {
+        self.texture_descs.get(&id.id())
+    }
+
+    /// Sets the sampler the texture is drawn with.
+    ///
+    /// Sampler states are cached, one is only created for descriptions not
+    /// used before. The font texture's sampler is set through the id in
+    /// [`FontAtlas::tex_id`].
+    pub fn set_texture_sampler(
+        &mut self,
+        id: TextureId,
+        desc: SamplerDesc,
+    ) -> Result<(), D::Error> {
+        if !self.samplers.contains_key(&desc) {
+            let sampler = self.device.create_sampler(&desc)?;
+            self.samplers.insert(desc, sampler);
+        }
+        if desc == self.default_sampler {
+            self.texture_samplers.remove(&id.id());
+        } else {
+            self.texture_samplers.insert(id.id(), desc);
+        }
+        Ok(())
+    }
+
+    /// The sampler the texture is drawn with, the [default sampler] unless
+    /// set with [`Renderer::set_texture_sampler`].
+    ///
+    /// [default sampler]: RendererOptions::default_sampler
+    pub fn texture_sampler(&self, id: TextureId) -> SamplerDesc {
+        self.texture_samplers.get(&id.id()).copied().unwrap_or(self.default_sampler)
+    }
+
+    /// Sets whether the colors of the texture are premultiplied by alpha,
+    /// textures are assumed to hold straight alpha by default.
+    ///
+    /// Textures not matching the renderer's [alpha mode] are converted while
+    /// sampling them. The font texture is uploaded in the renderer's alpha
+    /// mode.
+    ///
+    /// [alpha mode]: Renderer::alpha
+    pub fn set_texture_alpha(&mut self, id: TextureId, alpha: AlphaMode) {
+        if alpha == self.default_texture_alpha(id) {
+            self.texture_alphas.remove(&id.id());
+        } else {
+            self.texture_alphas.insert(id.id(), alpha);
+        }
+    }
+
+    /// Whether the colors of the texture are premultiplied by alpha.
+    pub fn texture_alpha(&self, id: TextureId) -> AlphaMode {
+        let alpha = self.texture_alphas.get(&id.id()).copied();
+        alpha.unwrap_or_else(|| self.default_texture_alpha(id))
+    }
+
+    fn default_texture_alpha(&self, id: TextureId) -> AlphaMode {
+        if id.id() == FONT_TEX_ID {
+            font_texture_alpha(&self.pipeline_desc)
+        } else {
+            AlphaMode::Straight
+        }
+    }
+
+    /// Unregisters a texture, returning it.
+    pub fn remove_texture(&mut self, id: TextureId) -> Option<D::Texture> {
+        self.texture_descs.remove(&id.id());
+        self.texture_samplers.remove(&id.id());
+        self.texture_alphas.remove(&id.id());
+        self.texture_effects.remove(&id.id());
+        self.textures.remove(id)
+    }
+
+    /// Creates an effect out of compiled pixel shader bytecode and optional
+    /// constant data, returning its id.
+    ///
+    /// The constant data is bound to the first constant buffer slot of the
+    /// pixel shader, zero padded to a multiple of 16 bytes.
+    pub fn create_effect(
+        &mut self,
+        bytecode: &[u8],
+        constants: Option<&[u8]>,
+    ) -> Result<EffectId, D::Error> {
+        let shader = self.device.create_pixel_shader(bytecode)?;
+        let constants = match constants.filter(|data| !data.is_empty()) {
+            Some(data) => {
+                let size = (data.len() + 15) / 16 * 16;
+                let buffer = self.device.create_buffer(BufferKind::Constant, size)?;
+                Self::write_constants(&mut self.device, &buffer, size, data)?;
+                Some(Buffer(buffer, data.len()))
+            },
+            None => None,
+        };
+        let id = EffectId(self.next_effect);
+        self.next_effect += 1;
+        self.effects.insert(id, Effect { shader, constants });
+        Ok(id)
+    }
+
+    /// Overwrites the constant data of an effect.
+    ///
+    /// Fails with an [`EffectError`] converted into the device's error if
+    /// the effect doesn't exist or was created with constant data of a
+    /// different size.
+    pub fn update_effect_constants(
+        &mut self,
+        effect: EffectId,
+        constants: &[u8],
+    ) -> Result<(), D::Error> {
+        let effect = self.effects.get(&effect).ok_or(EffectError::UnknownEffect(effect))?;
+        let expected = effect.constants.as_ref().map_or(0, |buffer| buffer.len());
+        let buffer = effect.constants.as_ref().filter(|_| expected == constants.len());
+        let buffer =
+            buffer.ok_or(EffectError::DataSizeMismatch { expected, actual: constants.len() })?;
+        let size = (buffer.len() + 15) / 16 * 16;
+        Self::write_constants(&mut self.device, buffer.get_buf(), size, constants)
+    }
+
+    /// Removes an effect, returning whether it existed.
+    ///
+    /// Textures using the effect are drawn with the default pixel shader
+    /// again.
+    pub fn remove_effect(&mut self, effect: EffectId) -> bool {
+        self.texture_effects.retain(|_, e| *e != effect);
+        self.effects.remove(&effect).is_some()
+    }
+
+    /// Draws every draw of the texture with the effect, or with the default
+    /// pixel shader for `None`.
+    ///
+    /// The font texture's effect is set through the id in
+    /// [`FontAtlas::tex_id`]. Draws following an effect marker use the
+    /// marker's effect instead.
+    pub fn set_texture_effect(
+        &mut self,
+        id: TextureId,
+        effect: Option<EffectId>,
+    ) -> Result<(), EffectError> {
+        match effect {
+            Some(effect) => {
+                if !self.effects.contains_key(&effect) {
+                    return Err(EffectError::UnknownEffect(effect));
+                }
+                self.texture_effects.insert(id.id(), effect);
+            },
+            None => {
+                self.texture_effects.remove(&id.id());
+            },
+        }
+        Ok(())
+    }
+
+    /// The effect the texture is drawn with.
+    pub fn texture_effect(&self, id: TextureId) -> Option<EffectId> {
+        self.texture_effects.get(&id.id()).copied()
+    }
+
+    /// Rebuilds the font atlas of the context and replaces the font texture
+    /// with the rebuilt one.
+    ///
+    /// This has to be called after modifying the fonts of the atlas, for
+    /// example when changing the font size on a DPI change.
+    pub fn reload_font_texture(&mut self, im_ctx: &mut imgui::Context) -> Result<(), D::Error> {
+        let mut fonts = im_ctx.fonts();
+        // clearing the pixel data forces the atlas to be rebuilt
+        fonts.clear_tex_data();
+        self.font_texture =
+            Self::create_font_texture(fonts, &mut self.device, &self.pipeline_desc)?;
+        self.font_atlas_fingerprint = font_atlas_fingerprint(&im_ctx.fonts());
+        Ok(())
+    }
+
+    /// Reloads the font texture if the font atlas of the context has been
+    /// modified since the font texture was last uploaded, returning whether
+    /// it was reloaded.
+    ///
+    /// Calling this before every [`imgui::Context::frame`] picks up font
+    /// changes automatically. Modifications are detected by comparing the
+    /// font configurations and texture parameters of the atlas, which is
+    /// cheap compared to rebuilding it.
+    pub fn reload_font_texture_if_changed(
+        &mut self,
+        im_ctx: &mut imgui::Context,
+    ) -> Result<bool, D::Error> {
+        let fonts = im_ctx.fonts();
+        if fonts.is_built() && font_atlas_fingerprint(&fonts) == self.font_atlas_fingerprint {
+            return Ok(false);
+        }
+        drop(fonts);
+        self.reload_font_texture(im_ctx)?;
+        Ok(true)
+    }
+
+    /// Renders the given [`Ui`] with this renderer.
+    ///
+    /// The viewport spans the framebuffer size of the [`DrawData`], its
+    /// display size scaled by its framebuffer scale, matching the scissor
+    /// rectangles which are in framebuffer pixels.
+    ///
+    /// Should the [`DrawData`] contain an invalid texture index the renderer
+    /// will return an [`UnknownTexture`] error converted into the device's
+    /// error type, `DXGI_ERROR_INVALID_CALL` for d3d11, and immediately stop
+    /// rendering.
+    ///
+    /// [`Ui`]: https://docs.rs/imgui/*/imgui/struct.Ui.html
+    pub fn render(&mut self, draw_data: &DrawData) -> Result<(), D::Error> {
+        let viewport = Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: draw_data.display_size[0] * draw_data.framebuffer_scale[0],
+            height: draw_data.display_size[1] * draw_data.framebuffer_scale[1],
+        };
+        self.render_with(draw_data, viewport, None)
+    }
+
+    /// Renders the given [`Ui`] into `target`, optionally clearing it first.
+    ///
+    /// The target is bound for the duration of the call, the viewport covers
+    /// the whole target and the previously bound render targets are restored
+    /// afterwards. The display of the [`DrawData`] is stretched onto the
+    /// target, so for crisp results its display size multiplied by the
+    /// framebuffer scale should match the size of the target.
+    ///
+    /// The clear color is given in sRGB like all imgui colors, it's converted
+    /// to the renderer's [color space] like vertex colors are.
+    ///
+    /// [color space]: Renderer::color_space
+    ///
+    /// Errors are handled the same way as in [`Renderer::render`].
+    ///
+    /// [`Ui`]: https://docs.rs/imgui/*/imgui/struct.Ui.html
+    pub fn render_to(
+        &mut self,
+        draw_data: &DrawData,
+        target: &D::RenderTarget,
+        clear_color: Option<[f32; 4]>,
+    ) -> Result<(), D::Error> {
+        let [width, height] = self.device.render_target_size(target)?;
+        let viewport = Viewport { x: 0.0, y: 0.0, width: width as f32, height: height as f32 };
+        let color_space = self.color_space();
+        let clear_color = match clear_color {
+            Some(c) if color_space.is_linear() => {
+                let linear = color::srgb_color_to_linear(c);
+                Some(color::output_color(
+                    linear,
+                    color_space,
+                    AlphaMode::Straight,
+                    self.paper_white,
+                ))
+            },
+            clear_color => clear_color,
+        };
+        self.render_with(draw_data, viewport, Some((target, clear_color)))
+    }
+
+    fn render_with(
+        &mut self,
+        draw_data: &DrawData,
+        viewport: Viewport,
+        target: Option<(&D::RenderTarget, Option<[f32; 4]>)>,
+    ) -> Result<(), D::Error> {
+        self.frame_stats = FrameStats::default();
+        if let Some(timer) = &mut self.gpu_timer {
+            let device = &mut self.device;
+            timer.next_frame(|query| device.timer_query_data(query));
+        }
+        if draw_data.display_size[0] <= 0.0 || draw_data.display_size[1] <= 0.0 {
+            return Ok(());
+        }
+        self.plan.rebuild(draw_data, &self.textures)?;
+        self.frame_stats.skipped_commands = self.plan.skipped_commands();
+        if let Some(len) = self.vertex_planner.plan(draw_data.total_vtx_count as usize) {
+            self.vertex_buffer = Self::create_vertex_buffer(&mut self.device, len)?;
+            self.vertex_ring = RingBuffer::new(len);
+            self.vertex_planner.reallocated(len);
+            self.frame_stats.buffer_reallocs += 1;
+        }
+        if let Some(len) = self.index_planner.plan(draw_data.total_idx_count as usize) {
+            self.index_buffer = Self::create_index_buffer(&mut self.device, len)?;
+            self.index_ring = RingBuffer::new(len);
+            self.index_planner.reallocated(len);
+            self.frame_stats.buffer_reallocs += 1;
+        }
+        let mut touched = self.plan.touched_state();
+        touched.render_targets = target.is_some();
+        let state = match self.state_policy {
+            StatePolicy::Full => Some(self.device.capture_state(&TouchedState::ALL)),
+            StatePolicy::Touched if !touched.is_empty() => {
+                Some(self.device.capture_state(&touched))
+            },
+            StatePolicy::Touched | StatePolicy::None => None,
+        };
+
+        let res = self.draw(draw_data, viewport, target);
+        // the captured state is restored even if drawing failed
+        if let Some(state) = state {
+            self.device.apply_state(&state);
+        }
+        res
+    }
+
+    /// Binds the target and executes the plan.
+    fn draw(
+        &mut self,
+        draw_data: &DrawData,
+        viewport: Viewport,
+        target: Option<(&D::RenderTarget, Option<[f32; 4]>)>,
+    ) -> Result<(), D::Error> {
+        if let Some((target, clear_color)) = target {
+            self.device.set_render_target(target);
+            if let Some(color) = clear_color {
+                self.device.clear_render_target(target, color);
+            }
+        }
+        // plans without draws leave the render state untouched
+        if !self.plan.draws() {
+            return Ok(());
+        }
+        self.write_buffers(draw_data)?;
+        let timed = self.begin_gpu_timing();
+        self.setup_render_state(viewport);
+        let res = self.render_impl(viewport);
+        if timed {
+            self.end_gpu_timing();
+        }
+        res
+    }
+
+    fn render_impl(&mut self, viewport: Viewport) -> Result<(), D::Error> {
+        let mut bound = self.initial_bound_state();
+        for i in 0..self.plan.ops().len() {
+            match self.plan.ops()[i] {
+                DrawOp::SetFontTexture => {
+                    self.device.set_texture(&self.font_texture);
+                    self.frame_stats.texture_binds += 1;
+                    bound.texture = TextureId::from(FONT_TEX_ID);
+                    self.bind_texture_state(&mut bound);
+                },
+                DrawOp::SetTexture(texture_id) => {
+                    let texture =
+                        self.textures.get(texture_id).ok_or(UnknownTexture(texture_id))?;
+                    self.device.set_texture(texture);
+                    self.frame_stats.texture_binds += 1;
+                    bound.texture = texture_id;
+                    self.bind_texture_state(&mut bound);
+                },
+                DrawOp::SetScissor(r) => {
+                    self.device.set_scissor_rect(r);
+                    self.frame_stats.scissor_changes += 1;
+                },
+                DrawOp::DrawIndexed { index_count, start_index, base_vertex } => {
+                    // the buffers hold at most 32 bit sized data
+                    self.device.draw_indexed(
+                        index_count,
+                        start_index + self.index_offset as u32,
+                        base_vertex + self.vertex_offset as i32,
+                    );
+                    self.frame_stats.draw_calls += 1;
+                    self.frame_stats.triangles += index_count / 3;
+                },
+                DrawOp::ResetRenderState => {
+                    self.setup_render_state(viewport);
+                    // the texture stays bound, its sampler and effect are reapplied
+                    bound = BoundState { texture: bound.texture, ..self.initial_bound_state() };
+                    self.bind_texture_state(&mut bound);
+                },
+                DrawOp::SetEffect(effect) => {
+                    bound.marker_effect = effect;
+                    self.bind_texture_state(&mut bound);
+                },
+                DrawOp::Callback(callback) => {
+                    unsafe { callback.invoke() };
+                    self.frame_stats.callbacks += 1;
+                    // the callback may have bound its own sampler and shader
+                    bound.stale = true;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Begins measuring the GPU time of the render call, returning whether a
+    /// query was issued.
+    fn begin_gpu_timing(&mut self) -> bool {
+        match self.gpu_timer.as_mut().and_then(|timer| timer.issue()) {
+            Some(query) => {
+                self.device.begin_timer_query(query);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn end_gpu_timing(&mut self) {
+        if let Some(query) = self.gpu_timer.as_ref().and_then(|timer| timer.newest()) {
+            self.device.end_timer_query(query);
+        }
+    }
+
+    /// The state bound by [`Renderer::setup_render_state`].
+    fn initial_bound_state(&self) -> BoundState {
+        BoundState {
+            texture: TextureId::from(FONT_TEX_ID),
+            sampler: self.default_sampler,
+            marker_effect: None,
+            effect: None,
+            texture_alpha: self.alpha(),
+            stale: false,
+        }
+    }
+
+    fn setup_render_state(&mut self, viewport: Viewport) {
+        let draw_fmt =
+            if mem::size_of::<DrawIdx>() == 2 { IndexFormat::U16 } else { IndexFormat::U32 };
+        let stride = mem::size_of::<DrawVert>() as u32;
+
+        let device = &mut self.device;
+        device.set_viewport(viewport);
+        device.set_vertex_buffer(self.vertex_buffer.get_buf(), stride);
+        device.set_index_buffer(self.index_buffer.get_buf(), draw_fmt);
+        device.set_constant_buffer(&self.constant_buffer);
+        device.set_pipeline(&self.pipeline);
+        device.set_sampler(&self.samplers[&self.default_sampler]);
+    }
+
+    /// Binds the sampler and pixel shader the bound texture is drawn with,
+    /// unless they are bound already.
+    fn bind_texture_state(&mut self, bound: &mut BoundState) {
+        let stale = mem::replace(&mut bound.stale, false);
+        let sampler = self.texture_sampler(bound.texture);
+        if stale || sampler != bound.sampler {
+            self.device.set_sampler(&self.samplers[&sampler]);
+            bound.sampler = sampler;
+        }
+
+        // effects removed after the frame was built fall back to the default
+        let effect = bound
+            .marker_effect
+            .or_else(|| self.texture_effect(bound.texture))
+            .filter(|effect| self.effects.contains_key(effect));
+        let texture_alpha = self.texture_alpha(bound.texture);
+        // effects sample textures as they are, only the default pixel shader
+        // depends on the texture's alpha mode
+        if stale
+            || effect != bound.effect
+            || (effect.is_none() && texture_alpha != bound.texture_alpha)
+        {
+            let effects = &self.effects;
+            let effect_state = effect.map(|effect| &effects[&effect]);
+            let device = &mut self.device;
+            device.set_pixel_shader(&self.pipeline, effect_state.map(|e| &e.shader), texture_alpha);
+            if let Some(constants) = effect_state.and_then(|e| e.constants.as_ref()) {
+                device.set_pixel_constant_buffer(constants.get_buf());
+            }
+            bound.effect = effect;
+            bound.texture_alpha = texture_alpha;
+        }
+    }
+
+    fn write_constants(
+        device: &mut D,
+        buffer: &D::Buffer,
+        size: usize,
+        data: &[u8],
+    ) -> Result<(), D::Error> {
+        let ptr = device.map(buffer)?;
+        // the buffer has been sized to hold the padded data
+        let dst = unsafe { slice::from_raw_parts_mut(ptr, size) };
+        dst[..data.len()].copy_from_slice(data);
+        dst[data.len()..].fill(0);
+        device.unmap(buffer);
+        Ok(())
+    }
+
+    fn create_vertex_buffer(device: &mut D, len: usize) -> Result<Buffer<D::Buffer>, D::Error> {
+        device
+            .create_buffer(BufferKind::Vertex, len * mem::size_of::<DrawVert>())
+            .map(|buf| Buffer(buf, len))
+    }
+
+    fn create_index_buffer(device: &mut D, len: usize) -> Result<Buffer<D::Buffer>, D::Error> {
+        device
+            .create_buffer(BufferKind::Index, len * mem::size_of::<DrawIdx>())
+            .map(|buf| Buffer(buf, len))
+    }
+
+    /// Maps a streaming buffer for writing an allocation.
+    fn map_streaming(
+        device: &mut D,
+        buffer: &D::Buffer,
+        allocation: RingAllocation,
+    ) -> Result<*mut u8, D::Error> {
+        if allocation.discard {
+            device.map(buffer)
+        } else {
+            device.map_no_overwrite(buffer)
+        }
+    }
+
+    fn write_buffers(&mut self, draw_data: &DrawData) -> Result<(), D::Error> {
+        let vtx_count = draw_data.total_vtx_count as usize;
+        let idx_count = draw_data.total_idx_count as usize;
+        // the rings only advance once both buffers are mapped
+        let (mut vertex_ring, mut index_ring) = (self.vertex_ring.clone(), self.index_ring.clone());
+        let (vtx_alloc, idx_alloc) =
+            match (vertex_ring.allocate(vtx_count), index_ring.allocate(idx_count)) {
+                (Some(vtx_alloc), Some(idx_alloc)) => (vtx_alloc, idx_alloc),
+                // render_with grows the buffers to hold the total vertex and
+                // index counts before writing them, failing if it can't
+                _ => unreachable!("streaming buffers smaller than the frame"),
+            };
+        let vtx_ptr =
+            Self::map_streaming(&mut self.device, self.vertex_buffer.get_buf(), vtx_alloc)?;
+        let idx_ptr =
+            match Self::map_streaming(&mut self.device, self.index_buffer.get_buf(), idx_alloc) {
+                Ok(ptr) => ptr,
+                Err(e) => {
+                    self.device.unmap(self.vertex_buffer.get_buf());
+                    return Err(e);
+                },
+            };
+        self.vertex_ring = vertex_ring;
+        self.index_ring = index_ring;
+        self.vertex_offset = vtx_alloc.offset;
+        self.index_offset = idx_alloc.offset;
+
+        let (mut vtx_dst, mut idx_dst) = unsafe {
+            (
+                slice::from_raw_parts_mut(
+                    vtx_ptr.cast::<DrawVert>().add(vtx_alloc.offset),
+                    vtx_count,
+                ),
+                slice::from_raw_parts_mut(
+                    idx_ptr.cast::<DrawIdx>().add(idx_alloc.offset),
+                    idx_count,
+                ),
+            )
+        };
+
+        for (vbuf, ibuf) in
+            draw_data.draw_lists().map(|draw_list| (draw_list.vtx_buffer(), draw_list.idx_buffer()))
+        {
+            vtx_dst[..vbuf.len()].copy_from_slice(vbuf);
+            idx_dst[..ibuf.len()].copy_from_slice(ibuf);
+            vtx_dst = &mut vtx_dst[vbuf.len()..];
+            idx_dst = &mut idx_dst[ibuf.len()..];
+        }
+
+        self.device.unmap(self.vertex_buffer.get_buf());
+        self.device.unmap(self.index_buffer.get_buf());
+        self.frame_stats.vertices = vtx_count as u32;
+        self.frame_stats.indices = idx_count as u32;
+        self.frame_stats.bytes_mapped +=
+            (vtx_count * mem::size_of::<DrawVert>() + idx_count * mem::size_of::<DrawIdx>()) as u64;
+
+        let mvp = orthographic_projection(draw_data.display_pos, draw_data.display_size);
+        let constants =
+            VertexConstantBuffer { mvp, paper_white: self.paper_white, _padding: [0.0; 3] };
+        // constant buffers can only be discarded, avoid it when nothing changed
+        if self.constants != Some(constants) {
+            let cb_ptr = self.device.map(&self.constant_buffer)?;
+            unsafe { *cb_ptr.cast::<VertexConstantBuffer>() = constants };
+            self.device.unmap(&self.constant_buffer);
+            self.constants = Some(constants);
+            self.frame_stats.bytes_mapped += mem::size_of::<VertexConstantBuffer>() as u64;
+        }
+
+        Ok(())
+    }
+
+    fn create_font_texture(
+        mut fonts: imgui::FontAtlasRefMut<'_>,
+        device: &mut D,
+        pipeline_desc: &PipelineDesc,
+    ) -> Result<D::Texture, D::Error> {
+        let fa_tex = fonts.build_rgba32_texture();
+        let format = if pipeline_desc.color_space.is_linear() {
+            TextureFormat::Rgba8Srgb
+        } else {
+            TextureFormat::Rgba8
+        };
+        let desc = TextureDesc::new(fa_tex.width, fa_tex.height, format);
+        let font_texture = match font_texture_alpha(pipeline_desc) {
+            AlphaMode::Straight => device.create_texture(&desc, fa_tex.data)?,
+            AlphaMode::Premultiplied => {
+                let mut data = fa_tex.data.to_vec();
+                premultiply_alpha(&mut data);
+                device.create_texture(&desc, &data)?
+            },
+        };
+        fonts.tex_id = TextureId::from(FONT_TEX_ID);
+        Ok(font_texture)
+    }
+}
+
+/// The alpha mode the font texture is uploaded in.
+fn font_texture_alpha(pipeline_desc: &PipelineDesc) -> AlphaMode {
+    if pipeline_desc.color_space.is_linear() {
+        // premultiplying sRGB encoded texels would darken them once decoded,
+        // the pixel shader premultiplies the decoded texels instead
+        AlphaMode::Straight
+    } else {
+        pipeline_desc.alpha
+    }
+}
+
+/// Hashes everything about the font atlas that influences its texture.
+fn font_atlas_fingerprint(fonts: &FontAtlas) -> u64 {
+    let atlas: &sys::ImFontAtlas = unsafe { fonts.raw() };
+    let configs = match atlas.ConfigData.Size {
+        0 => &[][..],
+        len => unsafe { slice::from_raw_parts(atlas.ConfigData.Data, len as usize) },
+    };
+    let mut hasher = FnvHasher(FnvHasher::OFFSET_BASIS);
+    (atlas.TexReady, atlas.Flags, atlas.TexDesiredWidth, atlas.TexGlyphPadding).hash(&mut hasher);
+    (atlas.TexWidth, atlas.TexHeight, atlas.Fonts.Size, atlas.CustomRects.Size).hash(&mut hasher);
+    for config in configs {
+        (config.FontData as usize, config.FontDataSize, config.FontNo).hash(&mut hasher);
+        (config.SizePixels.to_bits(), config.OversampleH, config.OversampleV).hash(&mut hasher);
+        (config.PixelSnapH, config.MergeMode, config.FontBuilderFlags).hash(&mut hasher);
+        (config.GlyphRanges as usize, config.RasterizerMultiply.to_bits()).hash(&mut hasher);
+        let [spacing_x, spacing_y]: [f32; 2] = config.GlyphExtraSpacing.into();
+        let [offset_x, offset_y]: [f32; 2] = config.GlyphOffset.into();
+        [
+            spacing_x,
+            spacing_y,
+            offset_x,
+            offset_y,
+            config.GlyphMinAdvanceX,
+            config.GlyphMaxAdvanceX,
+        ]
+        .iter()
+        .for_each(|v| v.to_bits().hash(&mut hasher));
+    }
+    hasher.finish()
+}
+
+/// The 64 bit FNV-1a hash, `core` doesn't offer a hasher.
+struct FnvHasher(u64);
+
+impl FnvHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Effect<S, B> {
+    shader: S,
+    /// The constant buffer and the unpadded size of the constant data.
+    constants: Option<Buffer<B>>,
+}
+
+/// The texture related state bound while executing a draw plan.
+struct BoundState {
+    texture: TextureId,
+    sampler: SamplerDesc,
+    /// The effect of the last effect marker.
+    marker_effect: Option<EffectId>,
+    effect: Option<EffectId>,
+    /// The alpha mode of the textures the bound pixel shader samples.
+    texture_alpha: AlphaMode,
+    /// Whether a user callback may have replaced the sampler and shader.
+    stale: bool,
+}
+
+#[derive(Debug)]
+struct Buffer<B>(B, usize);
+
+impl<B> Buffer<B> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.1
+    }
+    #[inline]
+    fn get_buf(&self) -> &B {
+        &self.0
+    }
+}
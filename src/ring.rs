@@ -0,0 +1,63 @@
+//! Platform independent suballocation of the streaming vertex and index
+//! buffers.
+//!
+//! The data of consecutive render calls is written back to back into the
+//! same buffers, mapped without overwriting the data of draws issued before.
+//! Only once a buffer is full writing starts over at its beginning with its
+//! previous contents discarded, which lets the driver hand out fresh memory
+//! instead of renaming the buffer on every call.
+
+/// A range of a [`RingBuffer`] to write into.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RingAllocation {
+    /// The offset of the range in elements.
+    pub offset: usize,
+    /// Whether the buffer's previous contents have to be discarded, as
+    /// writing wrapped around to the beginning.
+    pub discard: bool,
+}
+
+/// Suballocates ranges of a buffer of a fixed number of elements in a ring.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RingBuffer {
+    capacity: usize,
+    head: usize,
+}
+
+impl RingBuffer {
+    /// Creates a ring over a buffer of `capacity` elements.
+    ///
+    /// The first allocation discards the buffer's contents.
+    #[inline]
+    pub const fn new(capacity: usize) -> Self {
+        RingBuffer { capacity, head: capacity }
+    }
+
+    /// The number of elements of the buffer.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The offset of the next allocation, unless it wraps around.
+    #[inline]
+    pub fn head(&self) -> usize {
+        self.head
+    }
+
+    /// Allocates `len` consecutive elements.
+    ///
+    /// Returns `None` if the buffer can't hold `len` elements at all.
+    pub fn allocate(&mut self, len: usize) -> Option<RingAllocation> {
+        if len > self.capacity {
+            return None;
+        }
+        let allocation = if len <= self.capacity - self.head {
+            RingAllocation { offset: self.head, discard: false }
+        } else {
+            RingAllocation { offset: 0, discard: true }
+        };
+        self.head = allocation.offset + len;
+        Some(allocation)
+    }
+}
@@ -0,0 +1,558 @@
+//! A CPU reference implementation of the renderer.
+//!
+//! The [`SoftwareRenderer`] executes the same [`DrawPlan`] as the d3d11
+//! renderer, using the same projection, the same blend equation, scissor
+//! clipping and font atlas. It exists to have a GPU-free reference of what
+//! the d3d11 path is supposed to produce, it is not meant to be fast.
+//!
+//! Sampling mirrors the d3d11 renderer's sampler, that is bilinear filtering
+//! with wrapping texture coordinates. Rasterization follows the d3d11 top-left
+//! fill rule with pixel centers at half-integer coordinates.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use imgui::{BackendFlags, DrawData, DrawIdx, DrawVert, TextureId, Textures};
+
+use crate::plan::{orthographic_projection, DrawOp, DrawPlan, ScissorRect, UnknownTexture};
+use crate::FONT_TEX_ID;
+
+/// An RGBA8 image, used both as texture and render target of the
+/// [`SoftwareRenderer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+/// The error returned when the pixel data passed to [`Image::from_rgba`] does
+/// not match the image dimensions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InvalidImageSize {
+    /// The expected length of the pixel data in bytes.
+    pub expected: usize,
+    /// The actual length of the pixel data in bytes.
+    pub actual: usize,
+}
+
+impl fmt::Display for InvalidImageSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {} bytes of pixel data, got {}", self.expected, self.actual)
+    }
+}
+
+impl std::error::Error for InvalidImageSize {}
+
+impl Image {
+    /// Creates a new image filled with transparent black.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::filled(width, height, [0; 4])
+    }
+
+    /// Creates a new image filled with the given color.
+    pub fn filled(width: u32, height: u32, color: [u8; 4]) -> Self {
+        let mut data = vec![0; width as usize * height as usize * 4];
+        data.chunks_exact_mut(4).for_each(|px| px.copy_from_slice(&color));
+        Image { width, height, data }
+    }
+
+    /// Creates an image from tightly packed RGBA8 pixel data.
+    pub fn from_rgba(width: u32, height: u32, data: Vec<u8>) -> Result<Self, InvalidImageSize> {
+        let expected = width as usize * height as usize * 4;
+        if data.len() != expected {
+            return Err(InvalidImageSize { expected, actual: data.len() });
+        }
+        Ok(Image { width, height, data })
+    }
+
+    /// The width of this image in pixels.
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The height of this image in pixels.
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The tightly packed RGBA8 pixel data of this image.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consumes the image, returning its RGBA8 pixel data.
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Returns the pixel at the given position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is out of bounds.
+    #[inline]
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        [self.data[i], self.data[i + 1], self.data[i + 2], self.data[i + 3]]
+    }
+
+    /// Sets the pixel at the given position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is out of bounds.
+    #[inline]
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        self.data[i..i + 4].copy_from_slice(&color);
+    }
+
+    /// Fills the whole image with the given color.
+    pub fn clear(&mut self, color: [u8; 4]) {
+        self.data.chunks_exact_mut(4).for_each(|px| px.copy_from_slice(&color));
+    }
+
+    fn texel(&self, x: i64, y: i64) -> [f32; 4] {
+        let x = x.rem_euclid(self.width as i64) as u32;
+        let y = y.rem_euclid(self.height as i64) as u32;
+        unorm_to_float(self.pixel(x, y))
+    }
+
+    /// Samples the image with bilinear filtering and wrapping texture
+    /// coordinates.
+    fn sample(&self, uv: [f32; 2]) -> [f32; 4] {
+        if self.width == 0 || self.height == 0 {
+            return [0.0; 4];
+        }
+        let x = uv[0] * self.width as f32 - 0.5;
+        let y = uv[1] * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let t00 = self.texel(x0, y0);
+        let t10 = self.texel(x0 + 1, y0);
+        let t01 = self.texel(x0, y0 + 1);
+        let t11 = self.texel(x0 + 1, y0 + 1);
+        let mut out = [0.0; 4];
+        for c in 0..4 {
+            let top = t00[c] + (t10[c] - t00[c]) * fx;
+            let bottom = t01[c] + (t11[c] - t01[c]) * fx;
+            out[c] = top + (bottom - top) * fy;
+        }
+        out
+    }
+
+    /// Blends `src` onto the pixel at the given position with the renderer's
+    /// blend state.
+    fn blend(&mut self, x: u32, y: u32, src: [f32; 4]) {
+        let dst = unorm_to_float(self.pixel(x, y));
+        let a = src[3];
+        // SrcBlend = SRC_ALPHA, DestBlend = INV_SRC_ALPHA
+        // SrcBlendAlpha = ONE, DestBlendAlpha = INV_SRC_ALPHA
+        let out = [
+            src[0] * a + dst[0] * (1.0 - a),
+            src[1] * a + dst[1] * (1.0 - a),
+            src[2] * a + dst[2] * (1.0 - a),
+            src[3] + dst[3] * (1.0 - a),
+        ];
+        self.set_pixel(x, y, float_to_unorm(out));
+    }
+}
+
+fn unorm_to_float(c: [u8; 4]) -> [f32; 4] {
+    [c[0] as f32 / 255.0, c[1] as f32 / 255.0, c[2] as f32 / 255.0, c[3] as f32 / 255.0]
+}
+
+fn float_to_unorm(c: [f32; 4]) -> [u8; 4] {
+    let conv = |v: f32| (v.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+    [conv(c[0]), conv(c[1]), conv(c[2]), conv(c[3])]
+}
+
+/// A vertex after the vertex shader and viewport transform.
+#[derive(Copy, Clone, Debug)]
+struct ScreenVertex {
+    pos: [f32; 2],
+    uv: [f32; 2],
+    col: [f32; 4],
+}
+
+/// A pixel rectangle, right and bottom are exclusive.
+#[derive(Copy, Clone, Debug)]
+struct Bounds {
+    left: i64,
+    top: i64,
+    right: i64,
+    bottom: i64,
+}
+
+impl Bounds {
+    fn intersect(self, other: Bounds) -> Bounds {
+        Bounds {
+            left: self.left.max(other.left),
+            top: self.top.max(other.top),
+            right: self.right.min(other.right),
+            bottom: self.bottom.min(other.bottom),
+        }
+    }
+}
+
+impl From<ScissorRect> for Bounds {
+    fn from(r: ScissorRect) -> Self {
+        Bounds {
+            left: r.left as i64,
+            top: r.top as i64,
+            right: r.right as i64,
+            bottom: r.bottom as i64,
+        }
+    }
+}
+
+/// A software renderer producing RGBA8 images out of [`DrawData`].
+#[derive(Debug)]
+pub struct SoftwareRenderer {
+    font_texture: Image,
+    textures: Textures<Image>,
+    plan: DrawPlan,
+    vertices: Vec<DrawVert>,
+    indices: Vec<DrawIdx>,
+}
+
+impl SoftwareRenderer {
+    /// Creates a new software renderer, building the font atlas of the given
+    /// context.
+    pub fn new(im_ctx: &mut imgui::Context) -> Self {
+        let font_texture = Self::create_font_texture(im_ctx.fonts());
+        im_ctx.io_mut().backend_flags |= BackendFlags::RENDERER_HAS_VTX_OFFSET;
+        SoftwareRenderer {
+            font_texture,
+            textures: Textures::new(),
+            plan: DrawPlan::new(),
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    /// The textures registry of this renderer.
+    ///
+    /// The texture slot at !0 is reserved for the font texture, therefore the
+    /// renderer will ignore any texture inserted into said slot.
+    #[inline]
+    pub fn textures_mut(&mut self) -> &mut Textures<Image> {
+        &mut self.textures
+    }
+
+    /// The textures registry of this renderer.
+    #[inline]
+    pub fn textures(&self) -> &Textures<Image> {
+        &self.textures
+    }
+
+    /// The font atlas texture of this renderer.
+    #[inline]
+    pub fn font_texture(&self) -> &Image {
+        &self.font_texture
+    }
+
+    /// Renders the given [`DrawData`] into a new image of the draw data's
+    /// framebuffer size, cleared to `clear_color`.
+    pub fn render(
+        &mut self,
+        draw_data: &DrawData,
+        clear_color: [u8; 4],
+    ) -> Result<Image, UnknownTexture> {
+        let width = (draw_data.display_size[0] * draw_data.framebuffer_scale[0]).max(0.0) as u32;
+        let height = (draw_data.display_size[1] * draw_data.framebuffer_scale[1]).max(0.0) as u32;
+        let mut target = Image::filled(width, height, clear_color);
+        self.render_into(draw_data, &mut target)?;
+        Ok(target)
+    }
+
+    /// Renders the given [`DrawData`] onto `target`.
+    ///
+    /// The viewport spans the framebuffer size of the draw data, its display
+    /// size scaled by its framebuffer scale, starting at the top left corner
+    /// of the target. This matches the scissor rectangles, which are in
+    /// framebuffer pixels, and the size of the images [`render`] creates.
+    /// User callbacks are not invoked, as they expect to issue d3d11 commands.
+    ///
+    /// [`render`]: SoftwareRenderer::render
+    ///
+    /// Should the [`DrawData`] contain an invalid texture index nothing will
+    /// be rendered and an error is returned.
+    pub fn render_into(
+        &mut self,
+        draw_data: &DrawData,
+        target: &mut Image,
+    ) -> Result<(), UnknownTexture> {
+        let viewport = [
+            draw_data.display_size[0] * draw_data.framebuffer_scale[0],
+            draw_data.display_size[1] * draw_data.framebuffer_scale[1],
+        ];
+        if viewport[0] <= 0.0 || viewport[1] <= 0.0 {
+            return Ok(());
+        }
+        self.plan.rebuild(draw_data, &self.textures)?;
+        self.write_buffers(draw_data);
+
+        let mvp = orthographic_projection(draw_data.display_pos, draw_data.display_size);
+        // pixels are covered by the viewport if their center lies inside of it
+        let clip = Bounds {
+            left: 0,
+            top: 0,
+            right: ((viewport[0] - 0.5).ceil() as i64).min(target.width as i64),
+            bottom: ((viewport[1] - 0.5).ceil() as i64).min(target.height as i64),
+        };
+
+        let mut texture = &self.font_texture;
+        let mut scissor = clip;
+        for op in self.plan.ops() {
+            match *op {
+                DrawOp::SetFontTexture => texture = &self.font_texture,
+                DrawOp::SetTexture(texture_id) => {
+                    texture = self.textures.get(texture_id).ok_or(UnknownTexture(texture_id))?
+                },
+                DrawOp::SetScissor(r) => scissor = clip.intersect(r.into()),
+                DrawOp::DrawIndexed { index_count, start_index, base_vertex } => {
+                    let start = start_index as usize;
+                    let indices = &self.indices[start..start + index_count as usize];
+                    for tri in indices.chunks_exact(3) {
+                        let v = |i: DrawIdx| {
+                            let vtx = &self.vertices[(i as i64 + base_vertex as i64) as usize];
+                            transform_vertex(vtx, &mvp, viewport)
+                        };
+                        draw_triangle(target, scissor, texture, [v(tri[0]), v(tri[1]), v(tri[2])]);
+                    }
+                },
+                // the render state of the software renderer consists of the
+                // bound texture and scissor rect only, neither of which are
+                // touched by a reset
+                DrawOp::ResetRenderState => (),
+                // effects are pixel shaders, which the software renderer lacks
+                DrawOp::SetEffect(_) => (),
+                DrawOp::Callback(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn write_buffers(&mut self, draw_data: &DrawData) {
+        self.vertices.clear();
+        self.indices.clear();
+        for draw_list in draw_data.draw_lists() {
+            self.vertices.extend_from_slice(draw_list.vtx_buffer());
+            self.indices.extend_from_slice(draw_list.idx_buffer());
+        }
+    }
+
+    fn create_font_texture(mut fonts: imgui::FontAtlasRefMut<'_>) -> Image {
+        let fa_tex = fonts.build_rgba32_texture();
+        let image = Image::from_rgba(fa_tex.width, fa_tex.height, fa_tex.data.to_vec())
+            .expect("imgui returned a font atlas of invalid size");
+        fonts.tex_id = TextureId::from(FONT_TEX_ID);
+        image
+    }
+}
+
+/// Applies the vertex shader and viewport transform to the given vertex.
+fn transform_vertex(vtx: &DrawVert, mvp: &[[f32; 4]; 4], viewport: [f32; 2]) -> ScreenVertex {
+    let [x, y] = vtx.pos;
+    let clip_x = mvp[0][0] * x + mvp[1][0] * y + mvp[3][0];
+    let clip_y = mvp[0][1] * x + mvp[1][1] * y + mvp[3][1];
+    let clip_w = mvp[0][3] * x + mvp[1][3] * y + mvp[3][3];
+    let (ndc_x, ndc_y) = (clip_x / clip_w, clip_y / clip_w);
+    ScreenVertex {
+        pos: [(ndc_x + 1.0) * 0.5 * viewport[0], (1.0 - ndc_y) * 0.5 * viewport[1]],
+        uv: vtx.uv,
+        col: unorm_to_float(vtx.col),
+    }
+}
+
+fn edge(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// Whether the edge from `a` to `b` of a positively oriented triangle is a
+/// top or left edge in the sense of the top-left fill rule.
+fn is_top_left(a: [f32; 2], b: [f32; 2]) -> bool {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    (dy == 0.0 && dx > 0.0) || dy < 0.0
+}
+
+fn draw_triangle(target: &mut Image, clip: Bounds, texture: &Image, mut v: [ScreenVertex; 3]) {
+    let mut area = edge(v[0].pos, v[1].pos, v[2].pos);
+    if area == 0.0 || !area.is_finite() {
+        return;
+    }
+    if area < 0.0 {
+        v.swap(1, 2);
+        area = -area;
+    }
+
+    let min_x = v[0].pos[0].min(v[1].pos[0]).min(v[2].pos[0]);
+    let max_x = v[0].pos[0].max(v[1].pos[0]).max(v[2].pos[0]);
+    let min_y = v[0].pos[1].min(v[1].pos[1]).min(v[2].pos[1]);
+    let max_y = v[0].pos[1].max(v[1].pos[1]).max(v[2].pos[1]);
+    let bounds = clip.intersect(Bounds {
+        left: (min_x - 0.5).ceil() as i64,
+        top: (min_y - 0.5).ceil() as i64,
+        right: (max_x - 0.5).floor() as i64 + 1,
+        bottom: (max_y - 0.5).floor() as i64 + 1,
+    });
+
+    let edges = [(1, 2, 0), (2, 0, 1), (0, 1, 2)];
+    for py in bounds.top..bounds.bottom {
+        for px in bounds.left..bounds.right {
+            let p = [px as f32 + 0.5, py as f32 + 0.5];
+            let mut weights = [0.0; 3];
+            let covered = edges.iter().all(|&(a, b, opposite)| {
+                let w = edge(v[a].pos, v[b].pos, p);
+                weights[opposite] = w / area;
+                w > 0.0 || (w == 0.0 && is_top_left(v[a].pos, v[b].pos))
+            });
+            if !covered {
+                continue;
+            }
+
+            let interpolate = |attr: &dyn Fn(&ScreenVertex) -> f32| {
+                weights[0] * attr(&v[0]) + weights[1] * attr(&v[1]) + weights[2] * attr(&v[2])
+            };
+            let uv = [interpolate(&|v| v.uv[0]), interpolate(&|v| v.uv[1])];
+            let tex = texture.sample(uv);
+            let mut col = [0.0; 4];
+            for (c, out) in col.iter_mut().enumerate() {
+                *out = interpolate(&|v| v.col[c]) * tex[c];
+            }
+            target.blend(px as u32, py as u32, col);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [u8; 4] = [0xFF; 4];
+
+    fn vertex(pos: [f32; 2], col: [u8; 4]) -> ScreenVertex {
+        ScreenVertex { pos, uv: [0.5, 0.5], col: unorm_to_float(col) }
+    }
+
+    fn no_clip(target: &Image) -> Bounds {
+        Bounds { left: 0, top: 0, right: target.width as i64, bottom: target.height as i64 }
+    }
+
+    fn pixels(image: &Image) -> Vec<[u8; 4]> {
+        image.as_bytes().chunks_exact(4).map(|px| [px[0], px[1], px[2], px[3]]).collect()
+    }
+
+    #[test]
+    fn single_triangle() {
+        let mut target = Image::filled(4, 4, [0, 0, 0, 0xFF]);
+        let clip = no_clip(&target);
+        let white = Image::filled(1, 1, WHITE);
+        let red = [0xFF, 0, 0, 0xFF];
+        let v = [vertex([0.0, 0.0], red), vertex([4.0, 0.0], red), vertex([0.0, 4.0], red)];
+        draw_triangle(&mut target, clip, &white, v);
+        // pixel centers on the diagonal lie on its bottom right edge
+        for y in 0..4 {
+            for x in 0..4 {
+                let expected = if x + y < 3 { red } else { [0, 0, 0, 0xFF] };
+                assert_eq!(target.pixel(x, y), expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn shared_edges_are_drawn_once() {
+        // a half transparent quad turns any pixel drawn twice brighter
+        let mut target = Image::filled(4, 4, [0, 0, 0, 0xFF]);
+        let clip = no_clip(&target);
+        let white = Image::filled(1, 1, WHITE);
+        let col = [0xFF, 0xFF, 0xFF, 0x80];
+        let [a, b, c, d] = [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0]];
+        draw_triangle(&mut target, clip, &white, [vertex(a, col), vertex(b, col), vertex(d, col)]);
+        // the second triangle is wound the other way around
+        draw_triangle(&mut target, clip, &white, [vertex(b, col), vertex(d, col), vertex(c, col)]);
+        assert!(pixels(&target).iter().all(|&px| px == [0x80, 0x80, 0x80, 0xFF]));
+    }
+
+    #[test]
+    fn clipped_quad() {
+        let background = [0x10, 0x20, 0x30, 0xFF];
+        let mut target = Image::filled(8, 8, background);
+        let clip = Bounds { left: 2, top: 3, right: 5, bottom: 7 };
+        let white = Image::filled(1, 1, WHITE);
+        let green = [0, 0xFF, 0, 0xFF];
+        let [a, b, c, d] = [[-2.0, -2.0], [10.0, -2.0], [10.0, 10.0], [-2.0, 10.0]];
+        draw_triangle(
+            &mut target,
+            clip,
+            &white,
+            [vertex(a, green), vertex(b, green), vertex(c, green)],
+        );
+        draw_triangle(
+            &mut target,
+            clip,
+            &white,
+            [vertex(a, green), vertex(c, green), vertex(d, green)],
+        );
+        for y in 0..8 {
+            for x in 0..8 {
+                let inside = (2..5).contains(&x) && (3..7).contains(&y);
+                let expected = if inside { green } else { background };
+                assert_eq!(target.pixel(x, y), expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn alpha_blending() {
+        // SRC_ALPHA / INV_SRC_ALPHA for colors, ONE / INV_SRC_ALPHA for alpha
+        let mut target = Image::filled(1, 1, [0, 0, 0xFF, 0]);
+        target.blend(0, 0, unorm_to_float([0xFF, 0, 0, 0x80]));
+        assert_eq!(target.pixel(0, 0), [0x80, 0, 0x7F, 0x80]);
+
+        // vertex colors are modulated by the texture before blending
+        let mut target = Image::filled(2, 2, [0, 0, 0, 0xFF]);
+        let clip = no_clip(&target);
+        let texture = Image::filled(1, 1, [0xFF, 0x80, 0, 0xFF]);
+        let col = [0xFF, 0xFF, 0xFF, 0x80];
+        let [a, b, c] = [[0.0, 0.0], [8.0, 0.0], [0.0, 8.0]];
+        draw_triangle(
+            &mut target,
+            clip,
+            &texture,
+            [vertex(a, col), vertex(b, col), vertex(c, col)],
+        );
+        assert!(pixels(&target).iter().all(|&px| px == [0x80, 0x40, 0, 0xFF]));
+    }
+
+    #[test]
+    fn sampling() {
+        let mut texture = Image::new(2, 2);
+        texture.set_pixel(0, 0, [0xFF, 0, 0, 0xFF]);
+        texture.set_pixel(1, 0, [0, 0xFF, 0, 0xFF]);
+        texture.set_pixel(0, 1, [0, 0, 0xFF, 0xFF]);
+        texture.set_pixel(1, 1, [0xFF, 0xFF, 0xFF, 0]);
+        let sample = |uv| float_to_unorm(texture.sample(uv));
+        // texel centers
+        assert_eq!(sample([0.25, 0.25]), [0xFF, 0, 0, 0xFF]);
+        assert_eq!(sample([0.75, 0.75]), [0xFF, 0xFF, 0xFF, 0]);
+        // coordinates wrap
+        assert_eq!(sample([1.25, -0.75]), [0xFF, 0, 0, 0xFF]);
+        assert_eq!(sample([-0.25, 2.25]), [0, 0xFF, 0, 0xFF]);
+        // bilinear filtering between texel centers, across the wrapped edge
+        assert_eq!(sample([0.5, 0.25]), [0x80, 0x80, 0, 0xFF]);
+        assert_eq!(sample([0.0, 0.25]), [0x80, 0x80, 0, 0xFF]);
+        assert_eq!(sample([0.5, 0.5]), [0x80, 0x80, 0x80, 0xBF]);
+    }
+}
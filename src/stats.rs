@@ -0,0 +1,62 @@
+//! Statistics of the work done by a render call.
+//!
+//! [`Renderer::last_frame_stats`] returns the [`FrameStats`] of the last call
+//! to [`Renderer::render`] or [`Renderer::render_to`], which lets profilers
+//! attribute CPU and GPU time to the UI.
+//!
+//! [`Renderer::last_frame_stats`]: crate::Renderer::last_frame_stats
+//! [`Renderer::render`]: crate::Renderer::render
+//! [`Renderer::render_to`]: crate::Renderer::render_to
+
+use core::ops::{Add, AddAssign};
+
+/// The work done by a single render call.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FrameStats {
+    /// The number of draw calls issued.
+    pub draw_calls: u32,
+    /// The number of triangles drawn.
+    pub triangles: u32,
+    /// The number of vertices uploaded to the vertex buffer.
+    pub vertices: u32,
+    /// The number of indices uploaded to the index buffer.
+    pub indices: u32,
+    /// The number of textures bound.
+    pub texture_binds: u32,
+    /// The number of scissor rectangles set.
+    pub scissor_changes: u32,
+    /// The number of draw commands skipped as they draw nothing.
+    pub skipped_commands: u32,
+    /// The number of user callbacks invoked.
+    pub callbacks: u32,
+    /// The number of vertex and index buffer reallocations.
+    pub buffer_reallocs: u32,
+    /// The number of bytes written to mapped buffers.
+    pub bytes_mapped: u64,
+}
+
+impl Add for FrameStats {
+    type Output = FrameStats;
+
+    /// Sums up the statistics of several render calls, for example of a
+    /// frame rendering multiple imgui contexts.
+    fn add(mut self, rhs: FrameStats) -> FrameStats {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign for FrameStats {
+    fn add_assign(&mut self, rhs: FrameStats) {
+        self.draw_calls += rhs.draw_calls;
+        self.triangles += rhs.triangles;
+        self.vertices += rhs.vertices;
+        self.indices += rhs.indices;
+        self.texture_binds += rhs.texture_binds;
+        self.scissor_changes += rhs.scissor_changes;
+        self.skipped_commands += rhs.skipped_commands;
+        self.callbacks += rhs.callbacks;
+        self.buffer_reallocs += rhs.buffer_reallocs;
+        self.bytes_mapped += rhs.bytes_mapped;
+    }
+}
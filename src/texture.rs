@@ -0,0 +1,292 @@
+//! Descriptions of textures created from pixel data and the validation of
+//! their uploads.
+//!
+//! Everything in here is checked on the CPU before any device call is made,
+//! so invalid uploads are reported as a [`TextureError`] instead of failing
+//! inside the graphics API.
+
+use core::fmt;
+
+use imgui::TextureId;
+
+use crate::plan::UnknownTexture;
+
+/// The maximum width and height of a texture, the d3d11 limit of feature
+/// level 11.
+pub const MAX_TEXTURE_DIMENSION: u32 = 16384;
+
+/// The pixel format of a texture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TextureFormat {
+    /// 8 bit red, green, blue and alpha channels.
+    Rgba8,
+    /// 8 bit blue, green, red and alpha channels.
+    Bgra8,
+    /// 8 bit sRGB encoded red, green and blue channels and a linear alpha
+    /// channel, converted to linear when sampled.
+    Rgba8Srgb,
+    /// 8 bit sRGB encoded blue, green and red channels and a linear alpha
+    /// channel, converted to linear when sampled.
+    Bgra8Srgb,
+    /// 16 bit floating point red, green, blue and alpha channels.
+    Rgba16Float,
+    /// 32 bit floating point red, green, blue and alpha channels.
+    Rgba32Float,
+}
+
+impl TextureFormat {
+    /// The size of a single pixel in bytes.
+    #[inline]
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            TextureFormat::Rgba8
+            | TextureFormat::Bgra8
+            | TextureFormat::Rgba8Srgb
+            | TextureFormat::Bgra8Srgb => 4,
+            TextureFormat::Rgba16Float => 8,
+            TextureFormat::Rgba32Float => 16,
+        }
+    }
+}
+
+/// A rectangle of texels in a texture.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TextureRect {
+    /// The left edge of the rectangle.
+    pub x: u32,
+    /// The top edge of the rectangle.
+    pub y: u32,
+    /// The width of the rectangle.
+    pub width: u32,
+    /// The height of the rectangle.
+    pub height: u32,
+}
+
+impl TextureRect {
+    /// Creates a new rectangle.
+    #[inline]
+    pub const fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        TextureRect { x, y, width, height }
+    }
+}
+
+/// The size and format of a texture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureDesc {
+    /// The width of the texture.
+    pub width: u32,
+    /// The height of the texture.
+    pub height: u32,
+    /// The pixel format of the texture.
+    pub format: TextureFormat,
+}
+
+impl TextureDesc {
+    /// Creates a new description.
+    #[inline]
+    pub const fn new(width: u32, height: u32, format: TextureFormat) -> Self {
+        TextureDesc { width, height, format }
+    }
+
+    /// The rectangle covering the whole texture.
+    #[inline]
+    pub const fn rect(&self) -> TextureRect {
+        TextureRect::new(0, 0, self.width, self.height)
+    }
+
+    /// The size of a row of texels in bytes.
+    #[inline]
+    pub const fn row_pitch(&self) -> usize {
+        self.width as usize * self.format.bytes_per_pixel()
+    }
+
+    /// Checks that the texture can be created with the given tightly packed
+    /// pixel data.
+    pub fn validate_data(&self, data: &[u8]) -> Result<(), TextureError> {
+        if self.width == 0
+            || self.height == 0
+            || self.width > MAX_TEXTURE_DIMENSION
+            || self.height > MAX_TEXTURE_DIMENSION
+        {
+            return Err(TextureError::InvalidDimensions { width: self.width, height: self.height });
+        }
+        check_data_size(self.width, self.height, self.format, data)
+    }
+
+    /// Checks that the rectangle of the texture can be overwritten with the
+    /// given tightly packed pixel data.
+    pub fn validate_update(&self, rect: TextureRect, data: &[u8]) -> Result<(), TextureError> {
+        let in_bounds = |pos: u32, size: u32, max: u32| {
+            size > 0 && pos.checked_add(size).map_or(false, |end| end <= max)
+        };
+        if !in_bounds(rect.x, rect.width, self.width)
+            || !in_bounds(rect.y, rect.height, self.height)
+        {
+            return Err(TextureError::RectOutOfBounds {
+                rect,
+                width: self.width,
+                height: self.height,
+            });
+        }
+        check_data_size(rect.width, rect.height, self.format, data)
+    }
+}
+
+fn check_data_size(
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    data: &[u8],
+) -> Result<(), TextureError> {
+    let expected = width as usize * height as usize * format.bytes_per_pixel();
+    if data.len() != expected {
+        return Err(TextureError::DataSizeMismatch { expected, actual: data.len() });
+    }
+    Ok(())
+}
+
+/// The filter used when sampling a texture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Filter {
+    /// Nearest neighbor sampling, keeping pixel art crisp.
+    Point,
+    /// Bilinear filtering between texels and mip levels.
+    Linear,
+    /// Anisotropic filtering with the given maximum anisotropy, clamped to
+    /// `1..=16`.
+    Anisotropic(u8),
+}
+
+/// How texture coordinates outside of `0.0..=1.0` are resolved.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AddressMode {
+    /// Coordinates are clamped to the edge texels.
+    Clamp,
+    /// The texture repeats.
+    Wrap,
+    /// The texture repeats, mirrored every other time.
+    Mirror,
+    /// Coordinates outside of the texture sample transparent black.
+    Border,
+}
+
+/// The sampler state a texture is drawn with.
+///
+/// The default is linear filtering with wrapping coordinates.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SamplerDesc {
+    /// The filter used for minification, magnification and between mip
+    /// levels.
+    pub filter: Filter,
+    /// The address mode of both texture coordinates.
+    pub address_mode: AddressMode,
+}
+
+impl SamplerDesc {
+    /// Creates a new sampler description.
+    #[inline]
+    pub const fn new(filter: Filter, address_mode: AddressMode) -> Self {
+        SamplerDesc { filter, address_mode }
+    }
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        SamplerDesc::new(Filter::Linear, AddressMode::Wrap)
+    }
+}
+
+/// Whether color channels are stored as is or multiplied by alpha.
+///
+/// Describes both the pixel data of textures and the output of the
+/// renderer, see [`Renderer::with_device_and_alpha`].
+///
+/// [`Renderer::with_device_and_alpha`]: crate::Renderer::with_device_and_alpha
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AlphaMode {
+    /// The color channels are independent of alpha.
+    Straight,
+    /// The color channels are multiplied by alpha.
+    Premultiplied,
+}
+
+impl Default for AlphaMode {
+    fn default() -> Self {
+        AlphaMode::Straight
+    }
+}
+
+/// Multiplies the color channels of tightly packed RGBA8 pixels by their
+/// alpha, rounding to the nearest value.
+pub fn premultiply_alpha(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = u32::from(pixel[3]);
+        for c in &mut pixel[..3] {
+            *c = ((u32::from(*c) * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+/// The error returned when creating or updating a texture fails validation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextureError {
+    /// The texture is empty or exceeds [`MAX_TEXTURE_DIMENSION`].
+    InvalidDimensions {
+        /// The requested width.
+        width: u32,
+        /// The requested height.
+        height: u32,
+    },
+    /// The pixel data doesn't have the size of the tightly packed texels.
+    DataSizeMismatch {
+        /// The expected length of the pixel data in bytes.
+        expected: usize,
+        /// The actual length of the pixel data in bytes.
+        actual: usize,
+    },
+    /// The updated rectangle is empty or exceeds the texture.
+    RectOutOfBounds {
+        /// The updated rectangle.
+        rect: TextureRect,
+        /// The width of the texture.
+        width: u32,
+        /// The height of the texture.
+        height: u32,
+    },
+    /// No texture is registered under the id.
+    UnknownTexture(TextureId),
+    /// The texture wasn't created by the renderer, so its description is
+    /// unknown.
+    Unmanaged(TextureId),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TextureError::InvalidDimensions { width, height } => {
+                write!(f, "invalid texture dimensions {}x{}", width, height)
+            },
+            TextureError::DataSizeMismatch { expected, actual } => {
+                write!(f, "expected {} bytes of pixel data, got {}", expected, actual)
+            },
+            TextureError::RectOutOfBounds { rect, width, height } => write!(
+                f,
+                "rectangle {}x{} at ({}, {}) exceeds the {}x{} texture",
+                rect.width, rect.height, rect.x, rect.y, width, height
+            ),
+            TextureError::UnknownTexture(id) => write!(f, "unknown texture id {}", id.id()),
+            TextureError::Unmanaged(id) => {
+                write!(f, "texture id {} was not created by the renderer", id.id())
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TextureError {}
+
+impl From<UnknownTexture> for TextureError {
+    fn from(err: UnknownTexture) -> Self {
+        TextureError::UnknownTexture(err.0)
+    }
+}
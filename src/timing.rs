@@ -0,0 +1,281 @@
+//! Platform independent bookkeeping of GPU timer queries.
+//!
+//! GPU timestamps become available a few frames after the commands they
+//! measure were issued. A [`QueryRing`] cycles through a fixed set of timer
+//! queries, reading them back in issue order without ever waiting for the
+//! GPU, and a [`TimingHistory`] keeps the durations of the most recent
+//! frames. [`GpuTimer`] combines both the way the [`Renderer`] uses them.
+//!
+//! [`Renderer`]: crate::Renderer
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+/// The number of timer queries the [`Renderer`] cycles through by default.
+///
+/// [`Renderer`]: crate::Renderer
+pub const DEFAULT_TIMER_QUERIES: usize = 6;
+
+/// The number of durations a [`TimingHistory`] keeps by default.
+pub const DEFAULT_HISTORY_LEN: usize = 120;
+
+/// The timestamps read back from a timer query.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TimestampData {
+    /// The timestamp at the beginning of the measured commands.
+    pub begin: u64,
+    /// The timestamp at the end of the measured commands.
+    pub end: u64,
+    /// The frequency of the timestamp counter in Hz.
+    pub frequency: u64,
+    /// Whether the counter was unreliable in between, for example because
+    /// the GPU changed its clock.
+    pub disjoint: bool,
+}
+
+impl TimestampData {
+    /// The time between both timestamps in milliseconds, `None` if the
+    /// timestamps are unreliable.
+    pub fn milliseconds(&self) -> Option<f64> {
+        if self.disjoint || self.frequency == 0 || self.end < self.begin {
+            return None;
+        }
+        Some((self.end - self.begin) as f64 * 1000.0 / self.frequency as f64)
+    }
+}
+
+/// The GPU time a single frame took.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GpuTiming {
+    /// The frame the commands were issued in.
+    pub frame: u64,
+    /// The GPU time of the commands in milliseconds.
+    pub milliseconds: f64,
+    /// The number of frames it took until the result was read back.
+    pub latency: u64,
+}
+
+/// A fixed set of timer queries, read back in the order they were issued.
+#[derive(Clone, Debug)]
+pub struct QueryRing<Q> {
+    queries: Vec<Q>,
+    /// The slots and frames of the queries waiting for their results, oldest
+    /// first.
+    in_flight: VecDeque<(usize, u64)>,
+    next: usize,
+    dropped: u64,
+}
+
+impl<Q> QueryRing<Q> {
+    /// Creates a ring cycling through the given queries.
+    pub fn new(queries: Vec<Q>) -> Self {
+        QueryRing { queries, in_flight: VecDeque::new(), next: 0, dropped: 0 }
+    }
+
+    /// The number of queries of the ring.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.queries.len()
+    }
+
+    /// The number of queries waiting for their results.
+    #[inline]
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// The number of frames which weren't measured as all queries were in
+    /// flight.
+    #[inline]
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Returns the query measuring the given frame, or `None` if all queries
+    /// are still waiting for their results.
+    pub fn issue(&mut self, frame: u64) -> Option<&Q> {
+        if self.in_flight.len() == self.queries.len() {
+            self.dropped += 1;
+            return None;
+        }
+        let slot = self.next;
+        self.next = (slot + 1) % self.queries.len();
+        self.in_flight.push_back((slot, frame));
+        Some(&self.queries[slot])
+    }
+
+    /// The query issued last, if it's still in flight.
+    pub fn newest(&self) -> Option<&Q> {
+        self.in_flight.back().map(|&(slot, _)| &self.queries[slot])
+    }
+
+    /// Reads back the oldest query in flight with `read`, returning the frame
+    /// it measured and its timestamps once they're available.
+    ///
+    /// `read` returns `None` while the results aren't available, newer
+    /// queries are only read once the older ones completed.
+    pub fn poll<F>(&mut self, read: F) -> Option<(u64, TimestampData)>
+    where
+        F: FnOnce(&Q) -> Option<TimestampData>,
+    {
+        let &(slot, frame) = self.in_flight.front()?;
+        let data = read(&self.queries[slot])?;
+        self.in_flight.pop_front();
+        Some((frame, data))
+    }
+}
+
+/// The GPU times of the most recent frames.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimingHistory {
+    timings: VecDeque<GpuTiming>,
+    capacity: usize,
+}
+
+impl TimingHistory {
+    /// Creates a history keeping the timings of up to `capacity` frames.
+    pub fn new(capacity: usize) -> Self {
+        TimingHistory { timings: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    /// The number of timings this history keeps at most.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of timings in this history.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.timings.len()
+    }
+
+    /// Whether this history holds no timings.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.timings.is_empty()
+    }
+
+    /// Adds a timing, dropping the oldest one if the history is full.
+    pub fn push(&mut self, timing: GpuTiming) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.timings.len() == self.capacity {
+            self.timings.pop_front();
+        }
+        self.timings.push_back(timing);
+    }
+
+    /// Removes all timings.
+    pub fn clear(&mut self) {
+        self.timings.clear();
+    }
+
+    /// The most recent timing.
+    #[inline]
+    pub fn latest(&self) -> Option<&GpuTiming> {
+        self.timings.back()
+    }
+
+    /// The timings, oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &GpuTiming> + ExactSizeIterator + '_ {
+        self.timings.iter()
+    }
+
+    /// The average GPU time in milliseconds.
+    pub fn average_ms(&self) -> Option<f64> {
+        if self.timings.is_empty() {
+            return None;
+        }
+        let sum: f64 = self.timings.iter().map(|timing| timing.milliseconds).sum();
+        Some(sum / self.timings.len() as f64)
+    }
+
+    /// The longest GPU time in milliseconds.
+    pub fn max_ms(&self) -> Option<f64> {
+        self.timings.iter().map(|timing| timing.milliseconds).reduce(f64::max)
+    }
+}
+
+/// Measures the GPU time of a sequence of frames with a [`QueryRing`],
+/// collecting the results in a [`TimingHistory`].
+///
+/// Every frame calls [`GpuTimer::next_frame`] to read back the completed
+/// queries, then [`GpuTimer::issue`] for the query measuring it.
+#[derive(Clone, Debug)]
+pub struct GpuTimer<Q> {
+    ring: QueryRing<Q>,
+    history: TimingHistory,
+    frame: u64,
+    disjoint: u64,
+}
+
+impl<Q> GpuTimer<Q> {
+    /// Creates a timer cycling through the given queries, keeping the
+    /// timings of up to `history_len` frames.
+    pub fn new(queries: Vec<Q>, history_len: usize) -> Self {
+        GpuTimer {
+            ring: QueryRing::new(queries),
+            history: TimingHistory::new(history_len),
+            frame: 0,
+            disjoint: 0,
+        }
+    }
+
+    /// The ring of queries.
+    #[inline]
+    pub fn ring(&self) -> &QueryRing<Q> {
+        &self.ring
+    }
+
+    /// The timings read back so far.
+    #[inline]
+    pub fn history(&self) -> &TimingHistory {
+        &self.history
+    }
+
+    /// The current frame, counting from zero.
+    #[inline]
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// The number of frames whose timestamps were unreliable.
+    #[inline]
+    pub fn disjoint(&self) -> u64 {
+        self.disjoint
+    }
+
+    /// Advances to the next frame, reading back all completed queries with
+    /// `read`.
+    pub fn next_frame<F>(&mut self, mut read: F)
+    where
+        F: FnMut(&Q) -> Option<TimestampData>,
+    {
+        self.frame += 1;
+        while let Some((frame, data)) = self.ring.poll(&mut read) {
+            match data.milliseconds() {
+                Some(milliseconds) => self.history.push(GpuTiming {
+                    frame,
+                    milliseconds,
+                    latency: self.frame - frame,
+                }),
+                None => self.disjoint += 1,
+            }
+        }
+    }
+
+    /// Returns the query measuring the current frame, or `None` if all
+    /// queries are still in flight.
+    #[inline]
+    pub fn issue(&mut self) -> Option<&Q> {
+        self.ring.issue(self.frame)
+    }
+
+    /// The query issued last, if it's still in flight.
+    #[inline]
+    pub fn newest(&self) -> Option<&Q> {
+        self.ring.newest()
+    }
+}
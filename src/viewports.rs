@@ -0,0 +1,153 @@
+//! Rendering of secondary viewports, the platform windows of Dear ImGui's
+//! multi-viewport feature.
+//!
+//! [`Viewports`] owns a surface, a swapchain for d3d11, per secondary
+//! viewport and implements what Dear ImGui's renderer viewport callbacks
+//! require: creating, resizing, rendering, presenting and destroying the
+//! surface of a viewport. Each viewport's [`DrawData`] is rendered with its
+//! own display position, so draw data of viewports placed anywhere on the
+//! desktop ends up in the right spot of their window.
+//!
+//! The imgui bindings this crate is built against don't expose the
+//! multi-viewport platform interface, the callbacks of a docking enabled
+//! binding map one to one onto the methods of [`Viewports`]:
+//!
+//! - `Renderer_CreateWindow`: [`Viewports::create`]
+//! - `Renderer_DestroyWindow`: [`Viewports::destroy`]
+//! - `Renderer_SetWindowSize`: [`Viewports::resize`]
+//! - `Renderer_RenderWindow`: [`Viewports::render`]
+//! - `Renderer_SwapBuffers`: [`Viewports::present`]
+
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+use imgui::DrawData;
+
+use crate::device::SurfaceDevice;
+use crate::Renderer;
+
+/// The id of a viewport, imgui's `ImGuiViewport::ID`.
+pub type ViewportId = u32;
+
+/// The error returned when a viewport has no surface, for example because it
+/// was destroyed already.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UnknownViewport(pub ViewportId);
+
+impl fmt::Display for UnknownViewport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown viewport id {}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnknownViewport {}
+
+/// The surfaces of the secondary viewports rendered by a [`Renderer`].
+#[derive(Debug)]
+pub struct Viewports<D: SurfaceDevice> {
+    surfaces: BTreeMap<ViewportId, D::Surface>,
+}
+
+impl<D: SurfaceDevice> Default for Viewports<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: SurfaceDevice> Viewports<D> {
+    /// Creates an empty set of viewports.
+    pub fn new() -> Self {
+        Viewports { surfaces: BTreeMap::new() }
+    }
+
+    /// The number of viewports.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.surfaces.len()
+    }
+
+    /// Whether there are no viewports.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.surfaces.is_empty()
+    }
+
+    /// Whether a surface exists for the viewport.
+    #[inline]
+    pub fn contains(&self, id: ViewportId) -> bool {
+        self.surfaces.contains_key(&id)
+    }
+
+    /// The surface of the viewport.
+    #[inline]
+    pub fn surface(&self, id: ViewportId) -> Option<&D::Surface> {
+        self.surfaces.get(&id)
+    }
+
+    /// Creates the surface of a viewport presenting to `window`, replacing
+    /// any previous surface of the viewport.
+    pub fn create(
+        &mut self,
+        renderer: &mut Renderer<D>,
+        id: ViewportId,
+        window: &D::Window,
+        size: [u32; 2],
+    ) -> Result<(), D::Error> {
+        let surface = renderer.device_mut().create_surface(window, size)?;
+        self.surfaces.insert(id, surface);
+        Ok(())
+    }
+
+    /// Resizes the surface of a viewport.
+    ///
+    /// Fails with an [`UnknownViewport`] error converted into the device's
+    /// error if the viewport doesn't exist.
+    pub fn resize(
+        &mut self,
+        renderer: &mut Renderer<D>,
+        id: ViewportId,
+        size: [u32; 2],
+    ) -> Result<(), D::Error> {
+        let surface = self.surfaces.get_mut(&id).ok_or(UnknownViewport(id))?;
+        renderer.device_mut().resize_surface(surface, size)
+    }
+
+    /// Renders the draw data of a viewport into its surface, clearing it
+    /// first unless `clear_color` is `None`.
+    ///
+    /// Nothing is rendered should the surface have lost its render target due
+    /// to a failed resize. Unknown viewports are handled the same way as in
+    /// [`Viewports::resize`].
+    pub fn render(
+        &mut self,
+        renderer: &mut Renderer<D>,
+        id: ViewportId,
+        draw_data: &DrawData,
+        clear_color: Option<[f32; 4]>,
+    ) -> Result<(), D::Error> {
+        let surface = self.surfaces.get(&id).ok_or(UnknownViewport(id))?;
+        match D::surface_target(surface) {
+            Some(target) => renderer.render_to(draw_data, target, clear_color),
+            None => Ok(()),
+        }
+    }
+
+    /// Presents the surface of a viewport.
+    ///
+    /// Unknown viewports are handled the same way as in [`Viewports::resize`].
+    pub fn present(&mut self, renderer: &mut Renderer<D>, id: ViewportId) -> Result<(), D::Error> {
+        let surface = self.surfaces.get(&id).ok_or(UnknownViewport(id))?;
+        renderer.device_mut().present(surface)
+    }
+
+    /// Destroys the surface of a viewport, returning whether it existed.
+    pub fn destroy(&mut self, id: ViewportId) -> bool {
+        self.surfaces.remove(&id).is_some()
+    }
+
+    /// Destroys the surfaces of all viewports.
+    pub fn clear(&mut self) {
+        self.surfaces.clear();
+    }
+}
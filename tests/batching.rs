@@ -0,0 +1,313 @@
+//! Merging of consecutive compatible draw commands, tested over synthetic
+//! draw lists.
+//!
+//! imgui merges compatible commands itself, `ImDrawList_AddDrawCmd` forces
+//! new commands drawing contiguous ranges with the same state.
+
+use std::mem;
+
+use imgui::{sys, DrawCmd, DrawData, DrawListMut, ImColor32, TextureId, Textures};
+use imgui_dx11_renderer::mock::{Call, MockTexture, RecordingDevice};
+use imgui_dx11_renderer::plan::{DrawOp, DrawPlan, ScissorRect};
+use imgui_dx11_renderer::Renderer;
+
+const FONT_TEX_ID: usize = !0;
+
+type RawCallbackFn = unsafe extern "C" fn(*const sys::ImDrawList, *const sys::ImDrawCmd);
+
+unsafe extern "C" fn noop_callback(_: *const sys::ImDrawList, _: *const sys::ImDrawCmd) {}
+
+fn context() -> imgui::Context {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    ctx.fonts().build_rgba32_texture();
+    // the id the renderers assign to the font atlas
+    ctx.fonts().tex_id = TextureId::from(FONT_TEX_ID);
+    ctx
+}
+
+fn rect(draw_list: &DrawListMut<'_>, i: usize) {
+    let min = [(i % 16) as f32 * 20.0, (i / 16) as f32 * 20.0];
+    let max = [min[0] + 10.0, min[1] + 10.0];
+    draw_list.add_rect(min, max, ImColor32::WHITE).filled(true).build();
+}
+
+fn split() {
+    unsafe { sys::ImDrawList_AddDrawCmd(sys::igGetBackgroundDrawList()) }
+}
+
+fn add_callback(callback: RawCallbackFn) {
+    unsafe {
+        sys::ImDrawList_AddCallback(
+            sys::igGetBackgroundDrawList(),
+            Some(callback),
+            std::ptr::null_mut(),
+        )
+    }
+}
+
+fn elements(draw_data: &DrawData) -> usize {
+    draw_data
+        .draw_lists()
+        .flat_map(|list| list.commands())
+        .filter(|cmd| matches!(cmd, DrawCmd::Elements { .. }))
+        .count()
+}
+
+fn count_ops(plan: &DrawPlan, f: impl Fn(&DrawOp) -> bool) -> usize {
+    plan.ops().iter().filter(|op| f(op)).count()
+}
+
+/// Every index drawn along with its texture, scissor rectangle and base
+/// vertex, in draw order.
+type Coverage = Vec<(usize, ScissorRect, u32, i32)>;
+
+/// The coverage of the draw data, one command at a time.
+fn draw_data_coverage(draw_data: &DrawData) -> Coverage {
+    let mut coverage = Vec::new();
+    let (mut list_vtx_offset, mut list_idx_offset) = (0, 0);
+    for draw_list in draw_data.draw_lists() {
+        for cmd in draw_list.commands() {
+            if let DrawCmd::Elements { count, cmd_params } = cmd {
+                let rect = ScissorRect::from_clip_rect(
+                    cmd_params.clip_rect,
+                    draw_data.display_pos,
+                    draw_data.framebuffer_scale,
+                );
+                if rect.right <= rect.left || rect.bottom <= rect.top {
+                    continue;
+                }
+                let start = list_idx_offset + cmd_params.idx_offset;
+                let base = (list_vtx_offset + cmd_params.vtx_offset) as i32;
+                coverage.extend(
+                    (start..start + count)
+                        .map(|i| (cmd_params.texture_id.id(), rect, i as u32, base)),
+                );
+            }
+        }
+        list_vtx_offset += draw_list.vtx_buffer().len();
+        list_idx_offset += draw_list.idx_buffer().len();
+    }
+    coverage
+}
+
+/// The coverage of executing the plan.
+fn plan_coverage(plan: &DrawPlan) -> Coverage {
+    let mut coverage = Vec::new();
+    let (mut texture, mut scissor) = (0, None);
+    for op in plan.ops() {
+        match *op {
+            DrawOp::SetFontTexture => texture = FONT_TEX_ID,
+            DrawOp::SetTexture(id) => texture = id.id(),
+            DrawOp::SetScissor(rect) => scissor = Some(rect),
+            DrawOp::DrawIndexed { index_count, start_index, base_vertex } => {
+                let rect = scissor.expect("draw without a scissor rectangle");
+                coverage.extend(
+                    (start_index..start_index + index_count)
+                        .map(|i| (texture, rect, i, base_vertex)),
+                );
+            },
+            _ => {},
+        }
+    }
+    coverage
+}
+
+#[test]
+fn contiguous_commands_merge() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    let ui = ctx.frame();
+    let draw_list = ui.get_background_draw_list();
+    for i in 0..8 {
+        rect(&draw_list, i);
+        split();
+    }
+    rect(&draw_list, 8);
+    mem::drop(draw_list);
+    let draw_data = ui.render();
+    assert_eq!(elements(draw_data), 9);
+
+    let plan = DrawPlan::build(draw_data, renderer.textures()).unwrap();
+    assert_eq!(
+        plan.ops(),
+        [
+            DrawOp::SetFontTexture,
+            DrawOp::SetScissor(ScissorRect { left: 0, top: 0, right: 320, bottom: 200 }),
+            DrawOp::DrawIndexed {
+                index_count: draw_data.total_idx_count as u32,
+                start_index: 0,
+                base_vertex: 0
+            },
+        ]
+    );
+
+    renderer.render(draw_data).unwrap();
+    assert_eq!(renderer.last_frame_stats().draw_calls, 1);
+    assert_eq!(renderer.last_frame_stats().scissor_changes, 1);
+}
+
+#[test]
+fn state_changes_end_runs() {
+    let mut ctx = context();
+    let mut textures = Textures::new();
+    let user_texture = textures.insert(());
+    let ui = ctx.frame();
+    let draw_list = ui.get_background_draw_list();
+    rect(&draw_list, 0);
+    split();
+    draw_list.add_image(user_texture, [0.0, 0.0], [32.0, 32.0]).build();
+    rect(&draw_list, 1);
+    draw_list.with_clip_rect([0.0, 0.0], [100.0, 100.0], || rect(&draw_list, 2));
+    rect(&draw_list, 3);
+    mem::drop(draw_list);
+    let draw_data = ui.render();
+
+    let plan = DrawPlan::build(draw_data, &textures).unwrap();
+    let full = ScissorRect { left: 0, top: 0, right: 320, bottom: 200 };
+    let clipped = ScissorRect { left: 0, top: 0, right: 100, bottom: 100 };
+    let draw =
+        |index_count, start_index| DrawOp::DrawIndexed { index_count, start_index, base_vertex: 0 };
+    assert_eq!(
+        plan.ops(),
+        [
+            DrawOp::SetFontTexture,
+            DrawOp::SetScissor(full),
+            draw(6, 0),
+            // the scissor rectangle isn't set again for the image
+            DrawOp::SetTexture(user_texture),
+            draw(6, 6),
+            DrawOp::SetFontTexture,
+            draw(6, 12),
+            DrawOp::SetScissor(clipped),
+            draw(6, 18),
+            DrawOp::SetScissor(full),
+            draw(6, 24),
+        ]
+    );
+    assert_eq!(plan_coverage(&plan), draw_data_coverage(draw_data));
+}
+
+#[test]
+fn callbacks_end_runs() {
+    let mut ctx = context();
+    let textures = Textures::<()>::new();
+    let ui = ctx.frame();
+    let draw_list = ui.get_background_draw_list();
+    rect(&draw_list, 0);
+    add_callback(noop_callback);
+    rect(&draw_list, 1);
+    add_callback(unsafe { mem::transmute::<isize, RawCallbackFn>(-1) });
+    rect(&draw_list, 2);
+    mem::drop(draw_list);
+    let draw_data = ui.render();
+
+    let plan = DrawPlan::build(draw_data, &textures).unwrap();
+    // callbacks may change the texture and scissor rectangle, they're set
+    // again after them
+    assert_eq!(count_ops(&plan, |op| matches!(op, DrawOp::DrawIndexed { .. })), 3);
+    assert_eq!(count_ops(&plan, |op| matches!(op, DrawOp::SetScissor(_))), 3);
+    assert!(matches!(
+        plan.ops()[3..],
+        [
+            DrawOp::Callback(_),
+            DrawOp::SetFontTexture,
+            DrawOp::SetScissor(_),
+            DrawOp::DrawIndexed { .. },
+            DrawOp::ResetRenderState,
+            DrawOp::SetScissor(_),
+            DrawOp::DrawIndexed { .. },
+        ]
+    ));
+}
+
+#[test]
+fn callbacks_rebind_textures() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    let user_texture = renderer.textures_mut().insert(MockTexture(7));
+    let ui = ctx.frame();
+    let draw_list = ui.get_background_draw_list();
+    draw_list.add_image(user_texture, [0.0, 0.0], [32.0, 32.0]).build();
+    add_callback(noop_callback);
+    draw_list.add_image(user_texture, [32.0, 0.0], [64.0, 32.0]).build();
+    mem::drop(draw_list);
+    let draw_data = ui.render();
+
+    let plan = DrawPlan::build(draw_data, renderer.textures()).unwrap();
+    assert_eq!(count_ops(&plan, |op| *op == DrawOp::SetTexture(user_texture)), 2);
+    assert!(matches!(
+        plan.ops()[4..],
+        [
+            DrawOp::Callback(_),
+            DrawOp::SetTexture(_),
+            DrawOp::SetScissor(_),
+            DrawOp::DrawIndexed { .. },
+        ]
+    ));
+
+    // the texture, its sampler and the pixel shader are bound again
+    renderer.device_mut().clear_log();
+    renderer.render(draw_data).unwrap();
+    let log = renderer.device().log();
+    let draws: Vec<usize> = log
+        .iter()
+        .enumerate()
+        .filter(|(_, call)| matches!(call, Call::DrawIndexed { .. }))
+        .map(|(i, _)| i)
+        .collect();
+    assert_eq!(draws.len(), 2);
+    let between = &log[draws[0] + 1..draws[1]];
+    assert!(between.contains(&Call::SetTexture(MockTexture(7))));
+    assert!(between.iter().any(|call| matches!(call, Call::SetSampler(_))));
+    assert!(between.iter().any(|call| matches!(call, Call::SetPixelShader { .. })));
+}
+
+#[test]
+fn dense_frame_coverage() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    let user_texture = renderer.textures_mut().insert(MockTexture(7));
+    let ui = ctx.frame();
+    let draw_list = ui.get_background_draw_list();
+    // a table-like pattern of cells, clipped columns and images
+    for i in 0..200 {
+        match i % 7 {
+            0 | 3 => split(),
+            1 => draw_list.add_image(user_texture, [0.0, 0.0], [8.0, 8.0]).build(),
+            5 => {
+                let x = (i % 4) as f32 * 80.0;
+                draw_list
+                    .with_clip_rect_intersect([x, 0.0], [x + 80.0, 200.0], || rect(&draw_list, i));
+            },
+            6 => draw_list.with_clip_rect([10.0, 10.0], [10.0, 50.0], || rect(&draw_list, i)),
+            _ => {},
+        }
+        rect(&draw_list, i);
+    }
+    mem::drop(draw_list);
+    let draw_data = ui.render();
+
+    let plan = DrawPlan::build(draw_data, renderer.textures()).unwrap();
+    let draws = count_ops(&plan, |op| matches!(op, DrawOp::DrawIndexed { .. }));
+    assert!(draws < elements(draw_data) - plan.skipped_commands() as usize);
+    assert_eq!(plan_coverage(&plan), draw_data_coverage(draw_data));
+
+    // no two consecutive scissor rectangles are the same
+    let scissors: Vec<ScissorRect> = plan
+        .ops()
+        .iter()
+        .filter_map(|op| match op {
+            DrawOp::SetScissor(rect) => Some(*rect),
+            _ => None,
+        })
+        .collect();
+    assert!(scissors.windows(2).all(|pair| pair[0] != pair[1]));
+
+    renderer.device_mut().clear_log();
+    renderer.render(draw_data).unwrap();
+    let device_draws =
+        renderer.device().log().iter().filter(|call| matches!(call, Call::DrawIndexed { .. }));
+    assert_eq!(device_draws.count(), draws);
+}
@@ -0,0 +1,145 @@
+//! Planning of the vertex and index buffer capacities and the reallocation
+//! counters.
+
+use imgui::Window;
+use imgui_dx11_renderer::capacity::{CapacityPlanner, ReallocStats};
+use imgui_dx11_renderer::device::BufferKind;
+use imgui_dx11_renderer::mock::{Call, RecordingDevice};
+use imgui_dx11_renderer::options::{GrowthPolicy, RendererBuilder, ShrinkPolicy};
+use imgui_dx11_renderer::Renderer;
+
+/// Plans the given sizes, reallocating whenever the planner asks to, and
+/// returns the capacities after every frame.
+fn run(planner: &mut CapacityPlanner, sizes: &[usize]) -> Vec<usize> {
+    sizes
+        .iter()
+        .map(|&size| {
+            if let Some(capacity) = planner.plan(size) {
+                assert!(capacity >= size);
+                planner.reallocated(capacity);
+            }
+            planner.capacity()
+        })
+        .collect()
+}
+
+#[test]
+fn linear_growth_never_shrinks() {
+    let mut planner = CapacityPlanner::new(100, GrowthPolicy::Linear, None);
+    assert_eq!(
+        run(&mut planner, &[50, 100, 101, 150, 250, 0, 0]),
+        [100, 100, 201, 201, 350, 350, 350]
+    );
+    assert_eq!(planner.stats(), ReallocStats { grows: 2, shrinks: 0 });
+}
+
+#[test]
+fn geometric_growth() {
+    // a UI growing by a little every frame
+    let mut planner = CapacityPlanner::new(100, GrowthPolicy::Geometric(2.0), None);
+    let sizes: Vec<usize> = (1..=100).map(|i| i * 16).collect();
+    let capacities = run(&mut planner, &sizes);
+    assert_eq!(*capacities.last().unwrap(), 1600);
+    assert_eq!(planner.stats().grows, 4);
+
+    let mut linear = CapacityPlanner::new(100, GrowthPolicy::Linear, None);
+    run(&mut linear, &sizes);
+    assert_eq!(linear.stats().grows, 14);
+
+    // sizes beyond the grown capacity are honored
+    let mut planner = CapacityPlanner::new(100, GrowthPolicy::Geometric(1.5), None);
+    assert_eq!(run(&mut planner, &[120, 1000]), [150, 1000]);
+}
+
+#[test]
+fn shrink_after_idle_frames() {
+    let shrink = ShrinkPolicy::new(3, 0.25);
+    let mut planner = CapacityPlanner::new(100, GrowthPolicy::Exact, Some(shrink));
+    // a spike, then three frames using at most a quarter of the capacity
+    assert_eq!(run(&mut planner, &[1000, 200, 250, 180]), [1000, 1000, 1000, 250]);
+    assert_eq!(planner.stats(), ReallocStats { grows: 1, shrinks: 1 });
+
+    // never below the initial capacity
+    assert_eq!(run(&mut planner, &[10, 10, 10]), [250, 250, 100]);
+    assert_eq!(run(&mut planner, &[0, 0, 0, 0]), [100; 4]);
+    assert_eq!(planner.stats(), ReallocStats { grows: 1, shrinks: 2 });
+}
+
+#[test]
+fn shrink_hysteresis() {
+    let shrink = ShrinkPolicy::new(3, 0.25);
+    let mut planner = CapacityPlanner::new(100, GrowthPolicy::Geometric(2.0), Some(shrink));
+    assert_eq!(run(&mut planner, &[800]), [800]);
+    // a busy frame restarts the idle window
+    assert_eq!(run(&mut planner, &[150, 150, 400, 150, 150]), [800; 5]);
+    // the shrunk capacity keeps the growth policy's headroom
+    assert_eq!(run(&mut planner, &[150]), [300]);
+    // usage right below the shrunk capacity doesn't grow it back
+    assert_eq!(run(&mut planner, &[290; 10]), [300; 10]);
+    assert_eq!(planner.stats(), ReallocStats { grows: 1, shrinks: 1 });
+}
+
+#[test]
+fn failed_reallocation() {
+    let mut planner = CapacityPlanner::new(100, GrowthPolicy::Linear, None);
+    assert_eq!(planner.plan(150), Some(250));
+    // the buffer couldn't be reallocated, the next frame asks again
+    assert_eq!(planner.capacity(), 100);
+    assert_eq!(planner.plan(150), Some(250));
+    assert_eq!(planner.stats().total(), 0);
+}
+
+/// Renders the second frame of a window, or of nothing.
+fn render(ctx: &mut imgui::Context, renderer: &mut Renderer<RecordingDevice>, window: bool) {
+    let build = |ui: &imgui::Ui| {
+        if window {
+            Window::new("Spike").build(ui, || ui.text("Text"));
+        }
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    renderer.render(ui.render()).unwrap();
+}
+
+#[test]
+fn renderer_reallocations() {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    let mut renderer = RendererBuilder::new()
+        .vertex_capacity(1)
+        .index_capacity(1)
+        .growth_policy(GrowthPolicy::Geometric(2.0))
+        .shrink_policy(Some(ShrinkPolicy::new(2, 0.25)))
+        .build(&mut ctx, RecordingDevice::new())
+        .unwrap();
+    renderer.device_mut().clear_log();
+    assert_eq!(renderer.vertex_buffer_reallocs(), ReallocStats::default());
+
+    render(&mut ctx, &mut renderer, true);
+    let grown = ReallocStats { grows: 1, shrinks: 0 };
+    assert_eq!(
+        (renderer.vertex_buffer_reallocs(), renderer.index_buffer_reallocs()),
+        (grown, grown)
+    );
+
+    render(&mut ctx, &mut renderer, false);
+    render(&mut ctx, &mut renderer, false);
+    let shrunk = ReallocStats { grows: 1, shrinks: 1 };
+    assert_eq!(
+        (renderer.vertex_buffer_reallocs(), renderer.index_buffer_reallocs()),
+        (shrunk, shrunk)
+    );
+    let created: Vec<(BufferKind, usize)> = renderer
+        .device()
+        .log()
+        .iter()
+        .filter_map(|call| match *call {
+            Call::CreateBuffer { kind, size, .. } => Some((kind, size)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(created.len(), 4);
+    assert_eq!(created[2..], [(BufferKind::Vertex, 20), (BufferKind::Index, 2)]);
+}
@@ -0,0 +1,200 @@
+//! Round trip and replay tests of frame captures.
+
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use imgui::{sys, Condition, DrawCmd, DrawData, Window};
+use imgui_dx11_renderer::capture::{CaptureError, FrameCapture, FORMAT_VERSION, MAGIC};
+use imgui_dx11_renderer::mock::RecordingDevice;
+use imgui_dx11_renderer::Renderer;
+
+type RawCallbackFn = unsafe extern "C" fn(*const sys::ImDrawList, *const sys::ImDrawCmd);
+
+static CALLBACK_INVOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn count_callback(_: *const sys::ImDrawList, _: *const sys::ImDrawCmd) {
+    CALLBACK_INVOCATIONS.fetch_add(1, Ordering::SeqCst);
+}
+
+fn build_frame(ui: &imgui::Ui) {
+    Window::new("Capture").position([10.0, 10.0], Condition::Always).build(ui, || {
+        ui.text("Captured text");
+        unsafe {
+            let draw_list = sys::igGetWindowDrawList();
+            sys::ImDrawList_AddCallback(draw_list, Some(count_callback), std::ptr::null_mut());
+            sys::ImDrawList_AddCallback(
+                draw_list,
+                Some(mem::transmute::<isize, RawCallbackFn>(-1)),
+                std::ptr::null_mut(),
+            );
+        }
+        ui.button("Button");
+    });
+    Window::new("Second window").position([100.0, 100.0], Condition::Always).build(ui, || {
+        ui.text("More text");
+    });
+}
+
+fn context() -> imgui::Context {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    ctx.io_mut().display_framebuffer_scale = [2.0, 2.0];
+    ctx.fonts().build_rgba32_texture();
+    ctx
+}
+
+fn assert_same_frame(a: &DrawData, b: &DrawData) {
+    assert_eq!(a.display_pos, b.display_pos);
+    assert_eq!(a.display_size, b.display_size);
+    assert_eq!(a.framebuffer_scale, b.framebuffer_scale);
+    assert_eq!(a.total_vtx_count, b.total_vtx_count);
+    assert_eq!(a.total_idx_count, b.total_idx_count);
+    assert_eq!(a.draw_lists_count(), b.draw_lists_count());
+    for (a, b) in a.draw_lists().zip(b.draw_lists()) {
+        assert_eq!(a.vtx_buffer(), b.vtx_buffer());
+        assert_eq!(a.idx_buffer(), b.idx_buffer());
+        assert_eq!(a.commands().count(), b.commands().count());
+        for (a, b) in a.commands().zip(b.commands()) {
+            match (a, b) {
+                (
+                    DrawCmd::Elements { count: a_count, cmd_params: a_params },
+                    DrawCmd::Elements { count: b_count, cmd_params: b_params },
+                ) => assert_eq!((a_count, a_params), (b_count, b_params)),
+                (DrawCmd::ResetRenderState, DrawCmd::ResetRenderState) => {},
+                (DrawCmd::RawCallback { .. }, DrawCmd::RawCallback { .. }) => {},
+                _ => panic!("command kinds differ"),
+            }
+        }
+    }
+}
+
+#[test]
+fn round_trip() {
+    let mut ctx = context();
+    build_frame(&ctx.frame());
+    let ui = ctx.frame();
+    build_frame(&ui);
+    let draw_data = ui.render();
+
+    let capture = FrameCapture::capture(draw_data);
+    assert_same_frame(draw_data, capture.draw_data());
+    let bytes = capture.to_bytes();
+    assert_eq!(bytes[..8], MAGIC);
+    let loaded = FrameCapture::from_bytes(&bytes).unwrap();
+    assert_same_frame(draw_data, loaded.draw_data());
+    assert_eq!(loaded.to_bytes(), bytes);
+}
+
+#[test]
+fn replay_command_stream() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    build_frame(&ctx.frame());
+    let ui = ctx.frame();
+    build_frame(&ui);
+    let draw_data = ui.render();
+
+    let capture = FrameCapture::from_bytes(&FrameCapture::capture(draw_data).to_bytes()).unwrap();
+    renderer.device_mut().clear_log();
+    renderer.render(draw_data).unwrap();
+    let original = renderer.device_mut().take_log();
+    let invocations = CALLBACK_INVOCATIONS.load(Ordering::SeqCst);
+    assert_eq!(invocations, 1);
+
+    // a fresh renderer, the original one streams the replay behind the
+    // original frame's data
+    let mut replayer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    replayer.device_mut().clear_log();
+    replayer.render(capture.draw_data()).unwrap();
+    assert_eq!(replayer.device().log(), &original[..]);
+    // user callbacks are replayed as no-ops
+    assert_eq!(CALLBACK_INVOCATIONS.load(Ordering::SeqCst), invocations);
+}
+
+#[test]
+fn empty_frame() {
+    let mut ctx = context();
+    let draw_data = ctx.frame().render();
+    let capture = FrameCapture::capture(draw_data);
+    let loaded = FrameCapture::from_bytes(&capture.to_bytes()).unwrap();
+    assert_eq!(loaded.draw_data().draw_lists().count(), 0);
+}
+
+fn header() -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    for v in [0.0f32, 0.0, 320.0, 200.0, 1.0, 1.0] {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    bytes
+}
+
+#[test]
+fn invalid_captures() {
+    let mut bytes = header();
+    bytes[0] = b'X';
+    assert!(matches!(FrameCapture::from_bytes(&bytes), Err(CaptureError::InvalidMagic)));
+
+    let mut bytes = header();
+    bytes[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    assert!(matches!(
+        FrameCapture::from_bytes(&bytes),
+        Err(CaptureError::UnsupportedVersion(v)) if v == FORMAT_VERSION + 1
+    ));
+
+    assert!(matches!(FrameCapture::from_bytes(&header()), Err(CaptureError::UnexpectedEof)));
+
+    let mut bytes = header();
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    assert!(FrameCapture::from_bytes(&bytes).is_ok());
+    bytes.push(0);
+    assert!(matches!(FrameCapture::from_bytes(&bytes), Err(CaptureError::TrailingData)));
+
+    // a huge draw list count must not be trusted for allocation
+    let mut bytes = header();
+    bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+    assert!(matches!(FrameCapture::from_bytes(&bytes), Err(CaptureError::UnexpectedEof)));
+
+    // one draw list without vertices, drawing a triangle
+    let mut bytes = header();
+    for v in [1u32, 0, 3, 0, 0, 0, 1] {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    bytes.push(0);
+    bytes.extend_from_slice(&3u32.to_le_bytes());
+    for v in [0.0f32, 0.0, 320.0, 200.0] {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    bytes.extend_from_slice(&0u64.to_le_bytes());
+    bytes.extend_from_slice(&[0; 8]);
+    assert!(matches!(
+        FrameCapture::from_bytes(&bytes),
+        Err(CaptureError::OutOfBounds { draw_list: 0, command: 0 })
+    ));
+
+    let tag = bytes.len() - 37;
+    bytes[tag] = 7;
+    assert!(matches!(FrameCapture::from_bytes(&bytes), Err(CaptureError::InvalidCommandTag(7))));
+}
+
+#[cfg(feature = "software")]
+#[test]
+fn replay_software() {
+    use imgui_dx11_renderer::software::SoftwareRenderer;
+
+    let mut ctx = context();
+    let mut renderer = SoftwareRenderer::new(&mut ctx);
+    build_frame(&ctx.frame());
+    let ui = ctx.frame();
+    build_frame(&ui);
+    let draw_data = ui.render();
+    let expected = renderer.render(draw_data, [0x20, 0x30, 0x40, 0xFF]).unwrap();
+
+    let path = std::env::temp_dir().join("imgui_dx11_renderer_replay_software.imcap");
+    FrameCapture::capture(draw_data).save(&path).unwrap();
+    let capture = FrameCapture::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let actual = renderer.render(capture.draw_data(), [0x20, 0x30, 0x40, 0xFF]).unwrap();
+    assert_eq!(actual, expected);
+}
@@ -0,0 +1,177 @@
+//! sRGB and PQ transfer functions and rendering into linear and HDR render
+//! targets.
+
+use imgui::Window;
+use imgui_dx11_renderer::color::{
+    linear_to_srgb, output_color, pq_decode, pq_encode, rec709_to_rec2020, srgb_color_to_linear,
+    srgb_to_linear, ColorSpace, DEFAULT_PAPER_WHITE_NITS,
+};
+use imgui_dx11_renderer::device::PipelineDesc;
+use imgui_dx11_renderer::mock::{Call, MockBuffer, MockRenderTarget, MockTexture, RecordingDevice};
+use imgui_dx11_renderer::texture::{AlphaMode, TextureFormat};
+use imgui_dx11_renderer::Renderer;
+
+/// The sRGB transfer functions computed with the standard library's powers.
+fn reference_srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn reference_linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[test]
+fn transfer_functions() {
+    assert_eq!(srgb_to_linear(0.0), 0.0);
+    assert_eq!(linear_to_srgb(0.0), 0.0);
+    assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+    assert!((linear_to_srgb(1.0) - 1.0).abs() < 1e-6);
+    assert!((srgb_to_linear(0.5) - 0.214_041).abs() < 1e-5);
+    assert!((linear_to_srgb(0.5) - 0.735_357).abs() < 1e-5);
+
+    for i in 0..=1000 {
+        let c = i as f32 / 1000.0;
+        assert!((srgb_to_linear(c) - reference_srgb_to_linear(c)).abs() < 1e-5, "{}", c);
+        assert!((linear_to_srgb(c) - reference_linear_to_srgb(c)).abs() < 1e-5, "{}", c);
+    }
+}
+
+#[test]
+fn eight_bit_round_trip() {
+    for i in 0..=255u8 {
+        let c = f32::from(i) / 255.0;
+        let round_trip = (linear_to_srgb(srgb_to_linear(c)) * 255.0).round();
+        assert_eq!(round_trip, f32::from(i));
+    }
+    assert_eq!(srgb_color_to_linear([1.0, 0.0, 0.0, 0.5]), [srgb_to_linear(1.0), 0.0, 0.0, 0.5]);
+}
+
+#[test]
+fn linear_color_space() {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    let device = RecordingDevice::new();
+    let mut renderer =
+        Renderer::with_device_and_alpha(&mut ctx, device, AlphaMode::Premultiplied).unwrap();
+    assert_eq!(renderer.color_space(), ColorSpace::Srgb);
+    renderer.device_mut().clear_log();
+
+    renderer.set_color_space(&mut ctx, ColorSpace::Linear).unwrap();
+    assert_eq!(renderer.color_space(), ColorSpace::Linear);
+    let log = renderer.device_mut().take_log();
+    let desc = PipelineDesc {
+        alpha: AlphaMode::Premultiplied,
+        color_space: ColorSpace::Linear,
+        ..PipelineDesc::default()
+    };
+    assert_eq!(log[0], Call::CreatePipeline(desc));
+    let font = log.iter().find_map(|call| match call {
+        Call::CreateTexture { texture, format, .. } => Some((*texture, *format)),
+        _ => None,
+    });
+    assert_eq!(font, Some((MockTexture(1), TextureFormat::Rgba8Srgb)));
+
+    // the font texture isn't premultiplied on the CPU, sRGB texels have to
+    // be decoded first
+    let font_data = ctx.fonts().build_rgba32_texture().data.to_vec();
+    assert_eq!(renderer.device().texture_data(MockTexture(1)), &font_data[..]);
+    assert_eq!(renderer.texture_alpha(ctx.fonts().tex_id), AlphaMode::Straight);
+
+    let build = |ui: &imgui::Ui| {
+        Window::new("Linear").build(ui, || ui.text("Text"));
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    let target = MockRenderTarget { id: 1, size: [320, 200] };
+    renderer.render_to(ui.render(), &target, Some([0.5, 0.5, 0.5, 0.5])).unwrap();
+    let color = [srgb_to_linear(0.5), srgb_to_linear(0.5), srgb_to_linear(0.5), 0.5];
+    assert!(renderer.device().log().contains(&Call::ClearRenderTarget { target, color }));
+}
+
+#[test]
+fn pq_transfer_functions() {
+    assert!(pq_encode(0.0) < 1e-6);
+    assert!((pq_encode(100.0) - 0.508_078).abs() < 1e-5);
+    assert!((pq_encode(203.0) - 0.580_689).abs() < 1e-5);
+    assert!((pq_encode(10000.0) - 1.0).abs() < 1e-5);
+    assert_eq!(pq_encode(20000.0), pq_encode(10000.0));
+
+    for nits in [0.1, 1.0, 80.0, 203.0, 1000.0, 4000.0, 10000.0] {
+        let round_trip = pq_decode(pq_encode(nits));
+        assert!((round_trip - nits).abs() / nits < 1e-3, "{} {}", nits, round_trip);
+    }
+}
+
+#[test]
+fn hdr_output_colors() {
+    for (white, expected) in rec709_to_rec2020([1.0; 3]).iter().zip([1.0; 3].iter()) {
+        assert!((white - expected).abs() < 1e-5);
+    }
+    let white = [1.0, 1.0, 1.0, 0.5];
+    let paper_white = DEFAULT_PAPER_WHITE_NITS;
+    assert_eq!(output_color(white, ColorSpace::Srgb, AlphaMode::Straight, paper_white), white);
+
+    let [r, _, _, a] = output_color(white, ColorSpace::ScRgb, AlphaMode::Straight, paper_white);
+    assert!((r - 2.5375).abs() < 1e-5);
+    assert_eq!(a, 0.5);
+
+    let [r, g, b, a] = output_color(white, ColorSpace::Hdr10, AlphaMode::Straight, paper_white);
+    for c in [r, g, b] {
+        assert!((c - 0.580_689).abs() < 1e-4, "{}", c);
+    }
+    assert_eq!(a, 0.5);
+
+    // premultiplied colors are encoded unpremultiplied and scaled by coverage
+    let half = [0.5, 0.5, 0.5, 0.5];
+    let [r, _, _, a] = output_color(half, ColorSpace::Hdr10, AlphaMode::Premultiplied, paper_white);
+    assert!((r - 0.580_689 * 0.5).abs() < 1e-4, "{}", r);
+    assert_eq!(a, 0.5);
+    let transparent =
+        output_color([0.0; 4], ColorSpace::Hdr10, AlphaMode::Premultiplied, paper_white);
+    assert_eq!(transparent, [0.0; 4]);
+}
+
+#[test]
+fn hdr10_color_space() {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    assert_eq!(renderer.paper_white(), DEFAULT_PAPER_WHITE_NITS);
+    renderer.set_paper_white(0.0);
+    assert_eq!(renderer.paper_white(), 1.0);
+    renderer.set_paper_white(f32::NAN);
+    assert_eq!(renderer.paper_white(), DEFAULT_PAPER_WHITE_NITS);
+    renderer.set_paper_white(300.0);
+    renderer.device_mut().clear_log();
+
+    renderer.set_color_space(&mut ctx, ColorSpace::Hdr10).unwrap();
+    let desc = PipelineDesc { color_space: ColorSpace::Hdr10, ..PipelineDesc::default() };
+    assert_eq!(renderer.device_mut().take_log()[0], Call::CreatePipeline(desc));
+
+    let build = |ui: &imgui::Ui| {
+        Window::new("HDR").build(ui, || ui.text("Text"));
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    let target = MockRenderTarget { id: 1, size: [320, 200] };
+    renderer.render_to(ui.render(), &target, Some([1.0, 1.0, 1.0, 1.0])).unwrap();
+    let color = output_color([1.0; 4], ColorSpace::Hdr10, AlphaMode::Straight, 300.0);
+    assert!(renderer.device().log().contains(&Call::ClearRenderTarget { target, color }));
+
+    // the paper white follows the projection matrix in the constant buffer
+    let constants = renderer.device().buffer_data(MockBuffer(0));
+    assert_eq!(constants.len(), 80);
+    assert_eq!(constants[64..68], 300.0f32.to_ne_bytes());
+}
@@ -0,0 +1,251 @@
+//! Parsing of DDS headers and the layout of their subresources.
+
+use imgui_dx11_renderer::dds::{
+    full_mip_count, mip_dimension, DdsError, DdsFormat, DdsTexture, MAGIC,
+};
+use imgui_dx11_renderer::mock::{Call, MockError, RecordingDevice};
+use imgui_dx11_renderer::texture::{TextureError, TextureRect};
+use imgui_dx11_renderer::Renderer;
+
+const DDSD_MIPMAPCOUNT: u32 = 0x2_0000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+
+enum PixelFormat<'a> {
+    FourCc(&'a [u8; 4]),
+    Rgb { alpha: bool, masks: [u32; 4] },
+    Dx10 { dxgi_format: u32, dimension: u32, misc_flags: u32, array_size: u32 },
+}
+
+/// Builds a DDS file without pixel data.
+fn dds_header(width: u32, height: u32, mips: Option<u32>, format: PixelFormat) -> Vec<u8> {
+    let mut header = [0u32; 31];
+    header[0] = 124;
+    header[1] = 0x1007 | mips.map_or(0, |_| DDSD_MIPMAPCOUNT);
+    header[2] = height;
+    header[3] = width;
+    header[6] = mips.unwrap_or(0);
+    header[18] = 32;
+    let mut dx10 = None;
+    match format {
+        PixelFormat::FourCc(code) => {
+            header[19] = DDPF_FOURCC;
+            header[20] = u32::from_le_bytes(*code);
+        },
+        PixelFormat::Rgb { alpha, masks } => {
+            header[19] = DDPF_RGB | if alpha { DDPF_ALPHAPIXELS } else { 0 };
+            header[21] = 32;
+            header[22..26].copy_from_slice(&masks);
+        },
+        PixelFormat::Dx10 { dxgi_format, dimension, misc_flags, array_size } => {
+            header[19] = DDPF_FOURCC;
+            header[20] = u32::from_le_bytes(*b"DX10");
+            dx10 = Some([dxgi_format, dimension, misc_flags, array_size, 0]);
+        },
+    }
+    header[26] = 0x1000;
+
+    let mut bytes = MAGIC.to_vec();
+    header.iter().chain(dx10.iter().flatten()).for_each(|v| bytes.extend(v.to_le_bytes()));
+    bytes
+}
+
+fn dx10(dxgi_format: u32, array_size: u32) -> PixelFormat<'static> {
+    PixelFormat::Dx10 { dxgi_format, dimension: 3, misc_flags: 0, array_size }
+}
+
+/// Appends `len` bytes of pixel data, each the index of its subresource.
+fn push_surface(bytes: &mut Vec<u8>, index: u8, len: usize) {
+    bytes.resize(bytes.len() + len, index);
+}
+
+#[test]
+fn mip_sizes() {
+    assert_eq!(mip_dimension(256, 0), 256);
+    assert_eq!(mip_dimension(256, 3), 32);
+    assert_eq!(mip_dimension(5, 2), 1);
+    assert_eq!(mip_dimension(5, 40), 1);
+    assert_eq!(full_mip_count(256, 64), 9);
+    assert_eq!(full_mip_count(1, 1), 1);
+    assert_eq!(full_mip_count(5, 3), 3);
+
+    assert_eq!(DdsFormat::Bc1Unorm.surface_pitch(5, 5), (16, 32));
+    assert_eq!(DdsFormat::Bc4Snorm.surface_pitch(1, 1), (8, 8));
+    assert_eq!(DdsFormat::Bc7UnormSrgb.surface_pitch(1, 1), (16, 16));
+    assert_eq!(DdsFormat::Bc3Unorm.surface_pitch(16, 8), (64, 128));
+    assert_eq!(DdsFormat::Bgra8Unorm.surface_pitch(3, 2), (12, 24));
+    assert!(!DdsFormat::Rgba8UnormSrgb.is_block_compressed());
+    assert_eq!(DdsFormat::from_dxgi_format(98), Some(DdsFormat::Bc7Unorm));
+    assert_eq!(DdsFormat::from_dxgi_format(2), None);
+    assert_eq!(DdsFormat::from_dxgi_format(28), Some(DdsFormat::Rgba8Unorm));
+    assert_eq!(DdsFormat::from_dxgi_format(88), Some(DdsFormat::Bgrx8Unorm));
+    assert_eq!(DdsFormat::Rgbx8Unorm.surface_pitch(3, 2), (12, 24));
+}
+
+#[test]
+fn dx10_array_with_mips() {
+    let mut bytes = dds_header(8, 4, Some(4), dx10(DdsFormat::Bc7Unorm.dxgi_format(), 2));
+    let sizes = [32, 16, 16, 16];
+    for slice in 0..2 {
+        for (mip, &len) in sizes.iter().enumerate() {
+            push_surface(&mut bytes, slice * 4 + mip as u8, len);
+        }
+    }
+    let dds = DdsTexture::parse(&bytes).unwrap();
+    assert_eq!((dds.width, dds.height, dds.mip_levels, dds.array_size), (8, 4, 4, 2));
+    assert_eq!(dds.format, DdsFormat::Bc7Unorm);
+
+    let subresources = dds.subresources();
+    assert_eq!(subresources.len(), 8);
+    for (i, sub) in subresources.iter().enumerate() {
+        let mip = i as u32 % 4;
+        assert_eq!((sub.array_slice, sub.mip_level), (i as u32 / 4, mip));
+        assert_eq!([sub.width, sub.height], [mip_dimension(8, mip), mip_dimension(4, mip)]);
+        assert_eq!(sub.row_pitch, if mip == 0 { 32 } else { 16 });
+        assert_eq!(sub.data.len(), sizes[mip as usize]);
+        assert!(sub.data.iter().all(|&b| b == i as u8));
+    }
+
+    // trailing data is ignored, missing data is not
+    let mut trailing = bytes.clone();
+    trailing.push(0xFF);
+    assert_eq!(DdsTexture::parse(&trailing).unwrap().subresources(), subresources);
+    assert_eq!(DdsTexture::parse(&bytes[..bytes.len() - 1]), Err(DdsError::UnexpectedEof));
+}
+
+#[test]
+fn legacy_formats() {
+    for (code, format) in [
+        (b"DXT1", DdsFormat::Bc1Unorm),
+        (b"DXT3", DdsFormat::Bc2Unorm),
+        (b"DXT5", DdsFormat::Bc3Unorm),
+        (b"ATI1", DdsFormat::Bc4Unorm),
+        (b"BC5S", DdsFormat::Bc5Snorm),
+    ] {
+        let mut bytes = dds_header(16, 16, None, PixelFormat::FourCc(code));
+        push_surface(&mut bytes, 0, format.surface_pitch(16, 16).1);
+        let dds = DdsTexture::parse(&bytes).unwrap();
+        assert_eq!((dds.format, dds.mip_levels, dds.array_size), (format, 1, 1));
+    }
+
+    let bgra = [0xFF_0000, 0xFF00, 0xFF, 0xFF00_0000];
+    let mut bytes = dds_header(2, 2, Some(2), PixelFormat::Rgb { alpha: true, masks: bgra });
+    push_surface(&mut bytes, 0, 16);
+    push_surface(&mut bytes, 1, 4);
+    let dds = DdsTexture::parse(&bytes).unwrap();
+    assert_eq!((dds.format, dds.mip_levels), (DdsFormat::Bgra8Unorm, 2));
+    assert_eq!(dds.subresources()[1].data, [1; 4]);
+
+    // the unused byte of formats without alpha isn't sampled as alpha
+    let bgrx = [0xFF_0000, 0xFF00, 0xFF, 0];
+    let mut bytes = dds_header(1, 1, None, PixelFormat::Rgb { alpha: false, masks: bgrx });
+    bytes.extend([10, 20, 30, 0]);
+    let dds = DdsTexture::parse(&bytes).unwrap();
+    assert_eq!(dds.format, DdsFormat::Bgrx8Unorm);
+    assert_eq!(dds.upload_data(), [&[10, 20, 30, 0][..]]);
+
+    let rgbx = [0xFF, 0xFF00, 0xFF_0000, 0];
+    let mut bytes = dds_header(1, 1, None, PixelFormat::Rgb { alpha: false, masks: rgbx });
+    bytes.extend([10, 20, 30, 0]);
+    let dds = DdsTexture::parse(&bytes).unwrap();
+    assert_eq!(dds.format, DdsFormat::Rgbx8Unorm);
+    assert_eq!(dds.subresources()[0].data, [10, 20, 30, 0]);
+    assert_eq!(dds.upload_data(), [&[10, 20, 30, 0xFF][..]]);
+
+    // alpha masks other than the top byte aren't supported
+    let masks = [0xFF, 0xFF00, 0xFF_0000, 0xFF];
+    let bytes = dds_header(1, 1, None, PixelFormat::Rgb { alpha: true, masks });
+    assert_eq!(DdsTexture::parse(&bytes), Err(DdsError::UnsupportedFormat(0)));
+}
+
+#[test]
+fn invalid_files() {
+    let valid = dds_header(4, 4, None, PixelFormat::FourCc(b"DXT1"));
+    assert_eq!(DdsTexture::parse(b"PNG\0"), Err(DdsError::InvalidMagic));
+    assert_eq!(DdsTexture::parse(&valid[..100]), Err(DdsError::UnexpectedEof));
+    assert_eq!(DdsTexture::parse(&valid), Err(DdsError::UnexpectedEof));
+
+    let mut bytes = valid.clone();
+    bytes[4] = 0;
+    assert_eq!(DdsTexture::parse(&bytes), Err(DdsError::InvalidHeader));
+
+    let mut cube = valid.clone();
+    cube[4 + 108..4 + 112].copy_from_slice(&0xFE00u32.to_le_bytes());
+    assert_eq!(DdsTexture::parse(&cube), Err(DdsError::UnsupportedTexture));
+
+    let bytes = dds_header(4, 4, None, PixelFormat::FourCc(b"RXGB"));
+    let code = u32::from_le_bytes(*b"RXGB");
+    assert_eq!(DdsTexture::parse(&bytes), Err(DdsError::UnsupportedFormat(code)));
+    let bytes = dds_header(4, 4, None, dx10(2, 1));
+    assert_eq!(DdsTexture::parse(&bytes), Err(DdsError::UnsupportedFormat(2)));
+    let volume = PixelFormat::Dx10 { dxgi_format: 71, dimension: 4, misc_flags: 0, array_size: 1 };
+    let bytes = dds_header(4, 4, None, volume);
+    assert_eq!(DdsTexture::parse(&bytes), Err(DdsError::UnsupportedTexture));
+    let cube = PixelFormat::Dx10 { dxgi_format: 71, dimension: 3, misc_flags: 4, array_size: 1 };
+    let bytes = dds_header(4, 4, None, cube);
+    assert_eq!(DdsTexture::parse(&bytes), Err(DdsError::UnsupportedTexture));
+
+    let bytes = dds_header(4, 0, None, PixelFormat::FourCc(b"DXT1"));
+    assert_eq!(
+        DdsTexture::parse(&bytes),
+        Err(DdsError::InvalidDimensions { width: 4, height: 0, array_size: 1 })
+    );
+    let bytes = dds_header(4, 4, None, dx10(71, 0));
+    assert_eq!(
+        DdsTexture::parse(&bytes),
+        Err(DdsError::InvalidDimensions { width: 4, height: 4, array_size: 0 })
+    );
+    // block-compressed textures are made of whole blocks
+    let bytes = dds_header(6, 4, None, PixelFormat::FourCc(b"DXT1"));
+    assert_eq!(
+        DdsTexture::parse(&bytes),
+        Err(DdsError::InvalidDimensions { width: 6, height: 4, array_size: 1 })
+    );
+    let bytes = dds_header(8, 2, None, dx10(DdsFormat::Bc7Unorm.dxgi_format(), 1));
+    assert_eq!(
+        DdsTexture::parse(&bytes),
+        Err(DdsError::InvalidDimensions { width: 8, height: 2, array_size: 1 })
+    );
+    let bytes = dds_header(4, 4, Some(4), PixelFormat::FourCc(b"DXT1"));
+    assert_eq!(DdsTexture::parse(&bytes), Err(DdsError::InvalidMipCount(4)));
+}
+
+#[test]
+fn create_dds_texture() {
+    let mut ctx = imgui::Context::create();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    renderer.device_mut().clear_log();
+
+    let mut bytes = dds_header(4, 4, Some(3), PixelFormat::FourCc(b"DXT5"));
+    for mip in 0..3 {
+        push_surface(&mut bytes, mip, 16);
+    }
+    let dds = DdsTexture::parse(&bytes).unwrap();
+    let id = renderer.create_dds_texture(&dds).unwrap();
+    let texture = *renderer.textures().get(id).unwrap();
+    assert_eq!(
+        renderer.device().log(),
+        [Call::CreateDdsTexture {
+            texture,
+            width: 4,
+            height: 4,
+            mip_levels: 3,
+            array_size: 1,
+            format: DdsFormat::Bc3Unorm,
+        }]
+    );
+    assert_eq!(renderer.device().texture_data(texture), &bytes[128..]);
+    assert_eq!(
+        renderer.update_texture(id, TextureRect::new(0, 0, 4, 4), &[0; 64]),
+        Err(MockError::Texture(TextureError::Unmanaged(id)))
+    );
+
+    // textures without alpha are uploaded opaque
+    let rgbx = [0xFF, 0xFF00, 0xFF_0000, 0];
+    let mut bytes = dds_header(2, 1, None, PixelFormat::Rgb { alpha: false, masks: rgbx });
+    bytes.extend([1, 2, 3, 0, 4, 5, 6, 0]);
+    let id = renderer.create_dds_texture(&DdsTexture::parse(&bytes).unwrap()).unwrap();
+    let texture = *renderer.textures().get(id).unwrap();
+    assert_eq!(renderer.device().texture_data(texture), [1, 2, 3, 0xFF, 4, 5, 6, 0xFF]);
+}
@@ -0,0 +1,120 @@
+//! Decoding of image files into textures.
+
+use std::io::Cursor;
+
+use image::{ImageOutputFormat, Rgb, RgbImage, Rgba, RgbaImage};
+use imgui_dx11_renderer::color::ColorSpace;
+use imgui_dx11_renderer::decode::{
+    decode_image, premultiply_alpha, AlphaMode, DecodeError, LoadTextureError,
+};
+use imgui_dx11_renderer::mock::RecordingDevice;
+use imgui_dx11_renderer::texture::{TextureDesc, TextureFormat};
+use imgui_dx11_renderer::Renderer;
+
+/// A 3x2 image with a different color and alpha per pixel.
+fn rgba_image() -> RgbaImage {
+    RgbaImage::from_fn(3, 2, |x, y| Rgba([x as u8 * 100, y as u8 * 200, 255, 255 - x as u8 * 100]))
+}
+
+fn encode(image: impl Into<image::DynamicImage>, format: ImageOutputFormat) -> Vec<u8> {
+    let mut bytes = Cursor::new(Vec::new());
+    image.into().write_to(&mut bytes, format).unwrap();
+    bytes.into_inner()
+}
+
+#[test]
+fn premultiplication() {
+    let mut pixels = [255, 128, 0, 128, 10, 20, 30, 255, 200, 200, 200, 0];
+    premultiply_alpha(&mut pixels);
+    assert_eq!(pixels, [128, 64, 0, 128, 10, 20, 30, 255, 0, 0, 0, 0]);
+}
+
+#[test]
+fn decode_png() {
+    let expected = rgba_image();
+    let png = encode(expected.clone(), ImageOutputFormat::Png);
+
+    let image = decode_image(&png, AlphaMode::Straight).unwrap();
+    assert_eq!([image.width, image.height], [3, 2]);
+    assert_eq!(image.pixels, expected.as_raw()[..]);
+
+    let mut premultiplied = expected.into_raw();
+    premultiply_alpha(&mut premultiplied);
+    assert_eq!(decode_image(&png, AlphaMode::Premultiplied).unwrap().pixels, premultiplied);
+}
+
+#[test]
+fn decode_bmp_and_jpeg() {
+    let rgb = RgbImage::from_fn(4, 4, |x, y| Rgb([x as u8 * 60, y as u8 * 60, 90]));
+    let bmp = decode_image(&encode(rgb.clone(), ImageOutputFormat::Bmp), AlphaMode::Straight);
+    let expected: Vec<u8> = rgb.pixels().flat_map(|&Rgb([r, g, b])| [r, g, b, 255]).collect();
+    assert_eq!(bmp.unwrap().pixels, expected);
+
+    // jpeg is lossy, a flat image survives within a small tolerance
+    let flat = RgbImage::from_pixel(16, 8, Rgb([200, 100, 50]));
+    let jpeg =
+        decode_image(&encode(flat, ImageOutputFormat::Jpeg(95)), AlphaMode::Premultiplied).unwrap();
+    assert_eq!([jpeg.width, jpeg.height], [16, 8]);
+    for pixel in jpeg.pixels.chunks_exact(4) {
+        for (actual, expected) in pixel.iter().zip([200u8, 100, 50, 255]) {
+            assert!(actual.max(&expected) - actual.min(&expected) <= 2, "{:?}", pixel);
+        }
+    }
+}
+
+#[test]
+fn invalid_images() {
+    assert!(matches!(
+        decode_image(b"not an image", AlphaMode::Straight),
+        Err(DecodeError::Image(_))
+    ));
+    let png = encode(rgba_image(), ImageOutputFormat::Png);
+    assert!(matches!(
+        decode_image(&png[..png.len() / 2], AlphaMode::Straight),
+        Err(DecodeError::Image(_))
+    ));
+}
+
+#[test]
+fn load_image_texture() {
+    let mut ctx = imgui::Context::create();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    let png = encode(rgba_image(), ImageOutputFormat::Png);
+
+    let id = renderer.load_image_texture_from_memory(&png, AlphaMode::Premultiplied).unwrap();
+    assert_eq!(renderer.texture_desc(id), Some(&TextureDesc::new(3, 2, TextureFormat::Rgba8)));
+    assert_eq!(renderer.texture_alpha(id), AlphaMode::Premultiplied);
+    let texture = *renderer.textures().get(id).unwrap();
+    let expected = decode_image(&png, AlphaMode::Premultiplied).unwrap().pixels;
+    assert_eq!(renderer.device().texture_data(texture), &expected[..]);
+
+    let path = std::env::temp_dir().join("imgui_dx11_renderer_load_image_texture.png");
+    std::fs::write(&path, &png).unwrap();
+    let id = renderer.load_image_texture(&path, AlphaMode::Straight);
+    std::fs::remove_file(&path).unwrap();
+    let texture = *renderer.textures().get(id.unwrap()).unwrap();
+    assert_eq!(renderer.device().texture_data(texture), &rgba_image().as_raw()[..]);
+
+    assert!(matches!(
+        renderer.load_image_texture(&path, AlphaMode::Straight),
+        Err(LoadTextureError::Decode(DecodeError::Io(_)))
+    ));
+}
+
+#[test]
+fn load_image_texture_linear() {
+    let mut ctx = imgui::Context::create();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    renderer.set_color_space(&mut ctx, ColorSpace::Linear).unwrap();
+    // a half transparent texel
+    let png =
+        encode(RgbaImage::from_pixel(1, 1, Rgba([200, 100, 50, 128])), ImageOutputFormat::Png);
+
+    // the sRGB texels are uploaded straight, the shader premultiplies them
+    // once they're decoded
+    let id = renderer.load_image_texture_from_memory(&png, AlphaMode::Premultiplied).unwrap();
+    assert_eq!(renderer.texture_desc(id), Some(&TextureDesc::new(1, 1, TextureFormat::Rgba8Srgb)));
+    assert_eq!(renderer.texture_alpha(id), AlphaMode::Straight);
+    let texture = *renderer.textures().get(id).unwrap();
+    assert_eq!(renderer.device().texture_data(texture), [200, 100, 50, 128]);
+}
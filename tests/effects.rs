@@ -0,0 +1,283 @@
+//! Custom pixel shader effects applied per texture and through draw list
+//! markers.
+
+use std::mem;
+
+use imgui::{sys, Condition, Image, Window};
+use imgui_dx11_renderer::device::BufferKind;
+use imgui_dx11_renderer::effects::{push_effect, EffectError, EffectId};
+use imgui_dx11_renderer::mock::{
+    Call, MockBuffer, MockError, MockPixelShader, MockSampler, MockTexture, RecordingDevice,
+};
+use imgui_dx11_renderer::plan::{DrawOp, DrawPlan};
+use imgui_dx11_renderer::texture::{AddressMode, AlphaMode, Filter, SamplerDesc, TextureFormat};
+use imgui_dx11_renderer::Renderer;
+
+type RawCallbackFn = unsafe extern "C" fn(*const sys::ImDrawList, *const sys::ImDrawCmd);
+
+fn setup() -> (imgui::Context, Renderer<RecordingDevice>) {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    renderer.device_mut().clear_log();
+    (ctx, renderer)
+}
+
+/// Renders the second frame built by `build` and returns the calls binding
+/// textures, samplers and pixel shaders.
+fn render_binds(
+    ctx: &mut imgui::Context,
+    renderer: &mut Renderer<RecordingDevice>,
+    build: impl Fn(&imgui::Ui),
+) -> Vec<Call> {
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    renderer.device_mut().clear_log();
+    renderer.render(ui.render()).unwrap();
+    renderer
+        .device()
+        .log()
+        .iter()
+        .filter(|call| {
+            matches!(
+                call,
+                Call::SetTexture(_)
+                    | Call::SetSampler(_)
+                    | Call::SetPixelShader { .. }
+                    | Call::SetPixelConstantBuffer(_)
+            )
+        })
+        .cloned()
+        .collect()
+}
+
+fn push_window_effect(effect: Option<EffectId>) {
+    unsafe { push_effect(sys::igGetWindowDrawList(), effect) };
+}
+
+#[test]
+fn create_effects() {
+    let (_ctx, mut renderer) = setup();
+    let plain = renderer.create_effect(b"plain", None).unwrap();
+    let tinted = renderer.create_effect(b"tinted", Some(&[1, 2, 3, 4, 5])).unwrap();
+    assert_ne!(plain, tinted);
+    assert_eq!(
+        renderer.device_mut().take_log(),
+        [
+            Call::CreatePixelShader { shader: MockPixelShader(0), bytecode: b"plain".to_vec() },
+            Call::CreatePixelShader { shader: MockPixelShader(1), bytecode: b"tinted".to_vec() },
+            Call::CreateBuffer { buffer: MockBuffer(3), kind: BufferKind::Constant, size: 16 },
+            Call::Map(MockBuffer(3)),
+            Call::Unmap(MockBuffer(3)),
+        ]
+    );
+    let mut expected = [0; 16];
+    expected[..5].copy_from_slice(&[1, 2, 3, 4, 5]);
+    assert_eq!(renderer.device().buffer_data(MockBuffer(3)), expected);
+
+    renderer.update_effect_constants(tinted, &[9; 5]).unwrap();
+    expected[..5].fill(9);
+    assert_eq!(renderer.device().buffer_data(MockBuffer(3)), expected);
+
+    assert!(renderer.remove_effect(plain));
+    assert!(!renderer.remove_effect(plain));
+}
+
+#[test]
+fn update_constants_of_other_size() {
+    let (_ctx, mut renderer) = setup();
+    let effect = renderer.create_effect(b"tinted", Some(&[0; 4])).unwrap();
+    let plain = renderer.create_effect(b"plain", None).unwrap();
+    renderer.device_mut().clear_log();
+    assert_eq!(
+        renderer.update_effect_constants(effect, &[0; 8]),
+        Err(MockError::Effect(EffectError::DataSizeMismatch { expected: 4, actual: 8 }))
+    );
+    assert_eq!(
+        renderer.update_effect_constants(plain, &[0; 4]),
+        Err(MockError::Effect(EffectError::DataSizeMismatch { expected: 0, actual: 4 }))
+    );
+    assert!(renderer.device().log().is_empty());
+}
+
+#[test]
+fn unknown_effects() {
+    let (_ctx, mut renderer) = setup();
+    let image = renderer.create_texture(1, 1, TextureFormat::Rgba8, &[0; 4]).unwrap();
+    let effect = renderer.create_effect(b"tinted", Some(&[0; 4])).unwrap();
+    assert!(renderer.remove_effect(effect));
+    renderer.device_mut().clear_log();
+    assert_eq!(
+        renderer.update_effect_constants(effect, &[0; 4]),
+        Err(MockError::Effect(EffectError::UnknownEffect(effect)))
+    );
+    assert_eq!(
+        renderer.set_texture_effect(image, Some(effect)),
+        Err(EffectError::UnknownEffect(effect))
+    );
+    assert_eq!(renderer.texture_effect(image), None);
+    assert!(renderer.device().log().is_empty());
+}
+
+#[test]
+fn texture_effects() {
+    let (mut ctx, mut renderer) = setup();
+    let image = renderer.create_texture(1, 1, TextureFormat::Rgba8, &[0; 4]).unwrap();
+    let effect = renderer.create_effect(b"grayscale", Some(&[0; 16])).unwrap();
+    renderer.set_texture_effect(image, Some(effect)).unwrap();
+    assert_eq!(renderer.texture_effect(image), Some(effect));
+    let texture = *renderer.textures().get(image).unwrap();
+
+    let build = |ui: &imgui::Ui| {
+        Window::new("Effects").build(ui, || {
+            Image::new(image, [16.0, 16.0]).build(ui);
+            ui.text("Font");
+        });
+    };
+    assert_eq!(
+        render_binds(&mut ctx, &mut renderer, build),
+        [
+            Call::SetSampler(MockSampler(0)),
+            Call::SetTexture(MockTexture(0)),
+            Call::SetTexture(texture),
+            Call::SetPixelShader {
+                shader: Some(MockPixelShader(0)),
+                texture_alpha: AlphaMode::Straight
+            },
+            Call::SetPixelConstantBuffer(MockBuffer(3)),
+            Call::SetTexture(MockTexture(0)),
+            Call::SetPixelShader { shader: None, texture_alpha: AlphaMode::Straight },
+        ]
+    );
+
+    // removing the effect restores the default pixel shader
+    assert!(renderer.remove_effect(effect));
+    assert_eq!(renderer.texture_effect(image), None);
+    let binds = render_binds(&mut ctx, &mut renderer, build);
+    assert!(!binds.iter().any(|call| matches!(call, Call::SetPixelShader { .. })));
+
+    let other = renderer.create_effect(b"other", None).unwrap();
+    renderer.set_texture_effect(image, Some(other)).unwrap();
+    renderer.remove_texture(image);
+    assert_eq!(renderer.texture_effect(image), None);
+}
+
+#[test]
+fn effect_markers() {
+    let (mut ctx, mut renderer) = setup();
+    let image = renderer.create_texture(1, 1, TextureFormat::Rgba8, &[0; 4]).unwrap();
+    let blur = renderer.create_effect(b"blur", None).unwrap();
+    let tint = renderer.create_effect(b"tint", None).unwrap();
+    renderer.set_texture_effect(image, Some(tint)).unwrap();
+    let texture = *renderer.textures().get(image).unwrap();
+
+    let build = |ui: &imgui::Ui| {
+        Window::new("Marked").build(ui, || {
+            push_window_effect(Some(blur));
+            Image::new(image, [16.0, 16.0]).build(ui);
+            push_window_effect(None);
+            Image::new(image, [16.0, 16.0]).build(ui);
+            push_window_effect(Some(blur));
+            ui.text("Blurred until the end of the window");
+        });
+        Window::new("Unmarked").position([200.0, 0.0], Condition::Always).build(ui, || {
+            ui.text("Font");
+        });
+    };
+    assert_eq!(
+        render_binds(&mut ctx, &mut renderer, build),
+        [
+            Call::SetSampler(MockSampler(0)),
+            Call::SetTexture(MockTexture(0)),
+            Call::SetPixelShader {
+                shader: Some(MockPixelShader(0)),
+                texture_alpha: AlphaMode::Straight
+            },
+            Call::SetTexture(texture),
+            Call::SetPixelShader {
+                shader: Some(MockPixelShader(1)),
+                texture_alpha: AlphaMode::Straight
+            },
+            Call::SetPixelShader {
+                shader: Some(MockPixelShader(0)),
+                texture_alpha: AlphaMode::Straight
+            },
+            Call::SetTexture(MockTexture(0)),
+            Call::SetPixelShader { shader: None, texture_alpha: AlphaMode::Straight },
+        ]
+    );
+}
+
+#[test]
+fn reset_render_state_reapplies_texture_state() {
+    let (mut ctx, mut renderer) = setup();
+    let image = renderer.create_texture(1, 1, TextureFormat::Rgba8, &[0; 4]).unwrap();
+    let effect = renderer.create_effect(b"outline", None).unwrap();
+    let marker = renderer.create_effect(b"marker", None).unwrap();
+    let point = SamplerDesc::new(Filter::Point, AddressMode::Clamp);
+    renderer.set_texture_sampler(image, point).unwrap();
+    renderer.set_texture_effect(image, Some(effect)).unwrap();
+    let texture = *renderer.textures().get(image).unwrap();
+
+    let build = |ui: &imgui::Ui| {
+        Window::new("Reset").build(ui, || {
+            push_window_effect(Some(marker));
+            Image::new(image, [16.0, 16.0]).build(ui);
+            unsafe {
+                sys::ImDrawList_AddCallback(
+                    sys::igGetWindowDrawList(),
+                    Some(mem::transmute::<isize, RawCallbackFn>(-1)),
+                    std::ptr::null_mut(),
+                );
+            }
+            Image::new(image, [16.0, 16.0]).build(ui);
+        });
+    };
+    assert_eq!(
+        render_binds(&mut ctx, &mut renderer, build),
+        [
+            Call::SetSampler(MockSampler(0)),
+            Call::SetTexture(MockTexture(0)),
+            Call::SetPixelShader {
+                shader: Some(MockPixelShader(1)),
+                texture_alpha: AlphaMode::Straight
+            },
+            Call::SetTexture(texture),
+            Call::SetSampler(MockSampler(1)),
+            // the reset binds the defaults, the marker ends
+            Call::SetSampler(MockSampler(0)),
+            Call::SetSampler(MockSampler(1)),
+            Call::SetPixelShader {
+                shader: Some(MockPixelShader(0)),
+                texture_alpha: AlphaMode::Straight
+            },
+        ]
+    );
+}
+
+unsafe extern "C" fn noop_callback(_: *const sys::ImDrawList, _: *const sys::ImDrawCmd) {}
+
+#[test]
+fn foreign_callbacks_are_no_markers() {
+    let (mut ctx, renderer) = setup();
+    let build = |ui: &imgui::Ui| {
+        Window::new("Callbacks").build(ui, || unsafe {
+            // empty callbacks with user data looking like an effect
+            let draw_list = sys::igGetWindowDrawList();
+            sys::ImDrawList_AddCallback(draw_list, Some(noop_callback), 3 as *mut _);
+            sys::ImDrawList_AddCallback(draw_list, Some(noop_callback), 2 as *mut _);
+            push_effect(draw_list, None);
+        });
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    let plan = DrawPlan::build(ui.render(), renderer.textures()).unwrap();
+    let callbacks = plan.ops().iter().filter(|op| matches!(op, DrawOp::Callback(_))).count();
+    assert_eq!(callbacks, 2);
+    let effects: Vec<&DrawOp> =
+        plan.ops().iter().filter(|op| matches!(op, DrawOp::SetEffect(_))).collect();
+    assert_eq!(effects, [&DrawOp::SetEffect(None)]);
+}
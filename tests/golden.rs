@@ -0,0 +1,151 @@
+//! Golden image tests of the software reference renderer.
+//!
+//! Run with `cargo test --features golden`, set `UPDATE_GOLDENS=1` to
+//! regenerate the goldens after an intended rendering change.
+
+use imgui::{ChildWindow, Condition, Image as ImageWidget, Window};
+use imgui_dx11_renderer::golden::{FrameHarness, GoldenDir};
+use imgui_dx11_renderer::software::Image;
+
+fn goldens() -> GoldenDir {
+    GoldenDir::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden"))
+}
+
+fn checkerboard(size: u32, cell: u32) -> Image {
+    let mut image = Image::new(size, size);
+    for y in 0..size {
+        for x in 0..size {
+            let color = if (x / cell + y / cell) % 2 == 0 {
+                [0xE0, 0x40, 0x40, 0xFF]
+            } else {
+                [0x40, 0x40, 0xE0, 0x80]
+            };
+            image.set_pixel(x, y, color);
+        }
+    }
+    image
+}
+
+#[test]
+fn demo_window() {
+    let mut harness = FrameHarness::new([800.0, 600.0], [1.0, 1.0]);
+    let image = harness
+        .render(3, |ui| {
+            ui.show_demo_window(&mut true);
+        })
+        .unwrap();
+    goldens().check("demo_window", &image).unwrap();
+}
+
+#[test]
+fn text() {
+    let mut harness = FrameHarness::new([320.0, 200.0], [1.0, 1.0]);
+    let image = harness
+        .render(2, |ui| {
+            Window::new("Text")
+                .position([10.0, 10.0], Condition::Always)
+                .size([300.0, 180.0], Condition::Always)
+                .build(ui, || {
+                    ui.text("Hello world!");
+                    ui.text_colored([1.0, 0.5, 0.0, 1.0], "Colored text");
+                    ui.text_disabled("Disabled text");
+                    ui.separator();
+                    ui.text_wrapped(
+                        "Wrapped text that is long enough to span multiple lines of this window.",
+                    );
+                });
+        })
+        .unwrap();
+    goldens().check("text", &image).unwrap();
+}
+
+#[test]
+fn images() {
+    let mut harness = FrameHarness::new([320.0, 200.0], [1.0, 1.0]);
+    let texture_id = harness.renderer_mut().textures_mut().insert(checkerboard(32, 8));
+    let image = harness
+        .render(2, |ui| {
+            Window::new("Images")
+                .position([10.0, 10.0], Condition::Always)
+                .size([300.0, 180.0], Condition::Always)
+                .build(ui, || {
+                    ImageWidget::new(texture_id, [64.0, 64.0]).build(ui);
+                    ui.same_line();
+                    ImageWidget::new(texture_id, [64.0, 64.0])
+                        .uv0([0.25, 0.25])
+                        .uv1([2.25, 2.25])
+                        .tint_col([0.5, 1.0, 0.5, 1.0])
+                        .border_col([1.0, 1.0, 1.0, 1.0])
+                        .build(ui);
+                    ui.text("Font and image textures");
+                });
+        })
+        .unwrap();
+    goldens().check("images", &image).unwrap();
+}
+
+fn clip_rects_window(ui: &imgui::Ui) {
+    Window::new("Clipping")
+        .position([10.0, 10.0], Condition::Always)
+        .size([300.0, 220.0], Condition::Always)
+        .build(ui, || {
+            ChildWindow::new("outer").size([200.0, 120.0]).border(true).build(ui, || {
+                ChildWindow::new("inner").size([120.0, 60.0]).border(true).build(ui, || {
+                    for i in 0..8 {
+                        ui.text(format!("Inner line {} clipped", i));
+                    }
+                });
+                for i in 0..12 {
+                    ui.text(format!("Outer line {} with some text that gets clipped", i));
+                }
+            });
+            let draw_list = ui.get_window_draw_list();
+            draw_list.with_clip_rect([40.0, 150.0], [200.0, 200.0], || {
+                draw_list
+                    .add_rect([20.0, 140.0], [180.0, 210.0], [0.2, 0.8, 0.2, 1.0])
+                    .filled(true)
+                    .build();
+                draw_list.with_clip_rect_intersect([60.0, 160.0], [260.0, 190.0], || {
+                    draw_list
+                        .add_rect([0.0, 0.0], [320.0, 240.0], [0.9, 0.2, 0.2, 0.5])
+                        .filled(true)
+                        .build();
+                    draw_list.add_text([50.0, 165.0], [1.0, 1.0, 1.0, 1.0], "clipped text");
+                });
+            });
+        });
+}
+
+#[test]
+fn nested_clip_rects() {
+    let mut harness = FrameHarness::new([320.0, 240.0], [1.0, 1.0]);
+    let image = harness.render(2, clip_rects_window).unwrap();
+    goldens().check("nested_clip_rects", &image).unwrap();
+}
+
+#[test]
+fn nested_clip_rects_hidpi() {
+    // geometry is in display units, clip rectangles in framebuffer pixels
+    let mut harness = FrameHarness::new([320.0, 240.0], [2.0, 2.0]);
+    let image = harness.render(2, clip_rects_window).unwrap();
+    assert_eq!((image.width(), image.height()), (640, 480));
+    goldens().check("nested_clip_rects_hidpi", &image).unwrap();
+}
+
+#[test]
+fn offscreen_window() {
+    // geometry outside of the display has to be clipped by the viewport
+    let mut harness = FrameHarness::new([320.0, 200.0], [1.0, 1.0]);
+    let image = harness
+        .render(2, |ui| {
+            Window::new("Offscreen")
+                .position([200.0, 120.0], Condition::Always)
+                .size([200.0, 120.0], Condition::Always)
+                .build(ui, || {
+                    ui.text("Partially offscreen");
+                    ui.button("Button");
+                });
+        })
+        .unwrap();
+    goldens().check("offscreen_window", &image).unwrap();
+}
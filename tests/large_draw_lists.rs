@@ -0,0 +1,105 @@
+//! Rendering of draw lists exceeding the range of 16 bit indices.
+//!
+//! imgui splits such draw lists into commands with non-zero vertex offsets,
+//! which the renderers have to honor.
+
+use imgui::{DrawCmd, DrawData, ImColor32};
+use imgui_dx11_renderer::mock::{Call, RecordingDevice};
+use imgui_dx11_renderer::Renderer;
+
+const COLUMNS: u32 = 170;
+const ROWS: u32 = 100;
+const CELL: u32 = 2;
+
+/// The color of the rectangle covering the given cell, unique per rectangle.
+fn cell_color(x: u32, y: u32) -> [u8; 4] {
+    let i = y * COLUMNS + x;
+    [i as u8, (i >> 8) as u8, 0x80, 0xFF]
+}
+
+fn context() -> imgui::Context {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [(COLUMNS * CELL) as f32, (ROWS * CELL) as f32];
+    ctx.fonts().build_rgba32_texture();
+    ctx
+}
+
+/// Fills the display with 17000 rectangles, 68000 vertices in one draw list.
+fn build_frame(ui: &imgui::Ui) {
+    let draw_list = ui.get_background_draw_list();
+    for y in 0..ROWS {
+        for x in 0..COLUMNS {
+            let [r, g, b, a] = cell_color(x, y);
+            let min = [(x * CELL) as f32, (y * CELL) as f32];
+            let max = [min[0] + CELL as f32, min[1] + CELL as f32];
+            draw_list.add_rect(min, max, ImColor32::from_rgba(r, g, b, a)).filled(true).build();
+        }
+    }
+}
+
+fn assert_has_vtx_offsets(draw_data: &DrawData) {
+    let has_vtx_offset = draw_data.draw_lists().flat_map(|list| list.commands()).any(
+        |cmd| matches!(cmd, DrawCmd::Elements { cmd_params, .. } if cmd_params.vtx_offset > 0),
+    );
+    assert!(draw_data.total_vtx_count > 0xFFFF);
+    assert!(has_vtx_offset, "imgui didn't split the draw list");
+}
+
+#[test]
+fn command_offsets() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    let ui = ctx.frame();
+    build_frame(&ui);
+    ui.text("A window drawn after the background");
+    let draw_data = ui.render();
+    assert_has_vtx_offsets(draw_data);
+
+    renderer.device_mut().clear_log();
+    renderer.render(draw_data).unwrap();
+
+    let mut expected = Vec::new();
+    let (mut list_vtx_offset, mut list_idx_offset) = (0, 0);
+    for draw_list in draw_data.draw_lists() {
+        for cmd in draw_list.commands() {
+            if let DrawCmd::Elements { count, cmd_params } = cmd {
+                expected.push(Call::DrawIndexed {
+                    index_count: count as u32,
+                    start_index: (list_idx_offset + cmd_params.idx_offset) as u32,
+                    base_vertex: (list_vtx_offset + cmd_params.vtx_offset) as i32,
+                });
+            }
+        }
+        list_vtx_offset += draw_list.vtx_buffer().len();
+        list_idx_offset += draw_list.idx_buffer().len();
+    }
+    let draws: Vec<Call> = renderer
+        .device()
+        .log()
+        .iter()
+        .filter(|call| matches!(call, Call::DrawIndexed { .. }))
+        .cloned()
+        .collect();
+    assert_eq!(draws, expected);
+}
+
+#[cfg(feature = "software")]
+#[test]
+fn software_render() {
+    use imgui_dx11_renderer::software::SoftwareRenderer;
+
+    let mut ctx = context();
+    let mut renderer = SoftwareRenderer::new(&mut ctx);
+    let ui = ctx.frame();
+    build_frame(&ui);
+    let draw_data = ui.render();
+    assert_has_vtx_offsets(draw_data);
+
+    let image = renderer.render(draw_data, [0, 0, 0, 0]).unwrap();
+    for y in 0..ROWS * CELL {
+        for x in 0..COLUMNS * CELL {
+            assert_eq!(image.pixel(x, y), cell_color(x / CELL, y / CELL), "pixel {} {}", x, y);
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//! Command stream tests of the renderer against the recording mock device.
+
+use std::mem;
+
+use imgui::{
+    Condition, DrawCmd, DrawData, DrawVert, FontConfig, FontSource, Image, TextureId, Window,
+};
+use imgui_dx11_renderer::device::{BufferKind, IndexFormat, PipelineDesc, TouchedState, Viewport};
+use imgui_dx11_renderer::mock::{
+    Call, MockError, MockRenderTarget, MockSampler, MockTexture, RecordingDevice,
+};
+use imgui_dx11_renderer::plan::ScissorRect;
+use imgui_dx11_renderer::texture::TextureError;
+use imgui_dx11_renderer::Renderer;
+
+fn context() -> imgui::Context {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    ctx
+}
+
+fn created_buffers(log: &[Call]) -> Vec<(BufferKind, usize)> {
+    log.iter()
+        .filter_map(|call| match *call {
+            Call::CreateBuffer { kind, size, .. } => Some((kind, size)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The draw calls the renderer is expected to emit for the given draw data
+/// without callbacks or commands to merge.
+fn expected_draws(draw_data: &DrawData) -> Vec<Call> {
+    let mut calls = Vec::new();
+    let mut last_rect = None;
+    let (mut list_vtx_offset, mut list_idx_offset) = (0, 0);
+    for draw_list in draw_data.draw_lists() {
+        for cmd in draw_list.commands() {
+            if let DrawCmd::Elements { count, cmd_params } = cmd {
+                let rect = ScissorRect::from_clip_rect(
+                    cmd_params.clip_rect,
+                    draw_data.display_pos,
+                    draw_data.framebuffer_scale,
+                );
+                // unchanged scissor rectangles aren't set again
+                if last_rect != Some(rect) {
+                    calls.push(Call::SetScissorRect(rect));
+                    last_rect = Some(rect);
+                }
+                calls.push(Call::DrawIndexed {
+                    index_count: count as u32,
+                    start_index: (list_idx_offset + cmd_params.idx_offset) as u32,
+                    base_vertex: (list_vtx_offset + cmd_params.vtx_offset) as i32,
+                });
+            }
+        }
+        list_vtx_offset += draw_list.vtx_buffer().len();
+        list_idx_offset += draw_list.idx_buffer().len();
+    }
+    calls
+}
+
+#[test]
+fn creation() {
+    let mut ctx = context();
+    let renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    let log = renderer.device().log();
+
+    assert_eq!(log[0], Call::CreatePipeline(PipelineDesc::default()));
+    assert_eq!(
+        created_buffers(log),
+        [
+            (BufferKind::Constant, 80),
+            (BufferKind::Vertex, 5000 * mem::size_of::<DrawVert>()),
+            (BufferKind::Index, 10000 * mem::size_of::<imgui::DrawIdx>()),
+        ]
+    );
+    let mut fonts = ctx.fonts();
+    let font_atlas = fonts.build_rgba32_texture();
+    let texture_size = renderer.device().texture_size(MockTexture(0));
+    assert_eq!(texture_size, [font_atlas.width, font_atlas.height]);
+    assert_eq!(fonts.tex_id, TextureId::from(!0));
+}
+
+#[test]
+fn command_stream() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    let user_texture = MockTexture(42);
+    let texture_id = renderer.textures_mut().insert(user_texture);
+    renderer.device_mut().clear_log();
+
+    // windows are hidden during their first frame
+    let build = |ui: &imgui::Ui| {
+        Window::new("Mock").position([10.0, 10.0], Condition::Always).build(ui, || {
+            ui.text("Hello world!");
+            Image::new(texture_id, [32.0, 32.0]).build(ui);
+            ui.text("Back to the font texture");
+        });
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    let draw_data = ui.render();
+    renderer.render(draw_data).unwrap();
+    let log = renderer.device_mut().take_log();
+
+    let (vb, ib, cb) = match log[..8] {
+        [Call::CaptureState(TouchedState::ALL), Call::Map(vb), Call::Map(ib), Call::Unmap(vb2), Call::Unmap(ib2), Call::Map(cb), Call::Unmap(cb2), Call::SetViewport(vp)] =>
+        {
+            assert_eq!((vb, ib, cb), (vb2, ib2, cb2));
+            assert_eq!(vp, Viewport { x: 0.0, y: 0.0, width: 320.0, height: 200.0 });
+            (vb, ib, cb)
+        },
+        _ => panic!("unexpected buffer upload {:?}", &log[..8]),
+    };
+    assert_eq!(
+        log[8..13],
+        [
+            Call::SetVertexBuffer { buffer: vb, stride: mem::size_of::<DrawVert>() as u32 },
+            Call::SetIndexBuffer { buffer: ib, format: IndexFormat::U16 },
+            Call::SetConstantBuffer(cb),
+            Call::SetPipeline,
+            Call::SetSampler(MockSampler(0)),
+        ]
+    );
+    assert_eq!(log[13], Call::SetTexture(MockTexture(0)));
+    assert_eq!(log.last(), Some(&Call::ApplyState(TouchedState::ALL)));
+
+    let draws: Vec<Call> = log[14..log.len() - 1]
+        .iter()
+        .filter(|call| !matches!(call, Call::SetTexture(_)))
+        .cloned()
+        .collect();
+    assert_eq!(draws, expected_draws(draw_data));
+    let texture_binds: Vec<&Call> =
+        log[14..].iter().filter(|call| matches!(call, Call::SetTexture(_))).collect();
+    assert_eq!(texture_binds, [&Call::SetTexture(user_texture), &Call::SetTexture(MockTexture(0))]);
+
+    let vertices: Vec<u8> = draw_data
+        .draw_lists()
+        .flat_map(|draw_list| draw_list.vtx_buffer())
+        .flat_map(|vtx| {
+            let mut bytes = Vec::new();
+            vtx.pos.iter().chain(&vtx.uv).for_each(|f| bytes.extend_from_slice(&f.to_ne_bytes()));
+            bytes.extend_from_slice(&vtx.col);
+            bytes
+        })
+        .collect();
+    assert_eq!(renderer.device().buffer_data(vb)[..vertices.len()], vertices[..]);
+}
+
+#[test]
+fn unknown_texture() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    renderer.device_mut().clear_log();
+
+    let build = |ui: &imgui::Ui| {
+        Window::new("Mock").build(ui, || {
+            Image::new(TextureId::from(7), [32.0, 32.0]).build(ui);
+        });
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    let err = renderer.render(ui.render()).unwrap_err();
+    assert_eq!(err, MockError::Texture(TextureError::UnknownTexture(TextureId::from(7))));
+    assert!(renderer.device().log().iter().all(|call| !matches!(call, Call::DrawIndexed { .. })));
+}
+
+#[test]
+fn render_to_target() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    renderer.device_mut().clear_log();
+
+    let build = |ui: &imgui::Ui| {
+        Window::new("Mock").build(ui, || ui.text("Offscreen"));
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    let draw_data = ui.render();
+    let target = MockRenderTarget { id: 3, size: [640, 400] };
+    let color = [0.1, 0.2, 0.3, 1.0];
+    renderer.render_to(draw_data, &target, Some(color)).unwrap();
+    let log = renderer.device_mut().take_log();
+
+    assert_eq!(
+        log[..3],
+        [
+            Call::CaptureState(TouchedState::ALL),
+            Call::SetRenderTarget(target),
+            Call::ClearRenderTarget { target, color }
+        ]
+    );
+    let viewports: Vec<&Call> =
+        log.iter().filter(|call| matches!(call, Call::SetViewport(_))).collect();
+    assert_eq!(
+        viewports,
+        [&Call::SetViewport(Viewport { x: 0.0, y: 0.0, width: 640.0, height: 400.0 })]
+    );
+    assert!(log.iter().any(|call| matches!(call, Call::DrawIndexed { .. })));
+    assert_eq!(log.last(), Some(&Call::ApplyState(TouchedState::ALL)));
+
+    renderer.render_to(draw_data, &target, None).unwrap();
+    let log = renderer.device_mut().take_log();
+    assert_eq!(log[..2], [Call::CaptureState(TouchedState::ALL), Call::SetRenderTarget(target)]);
+    assert!(!log.iter().any(|call| matches!(call, Call::ClearRenderTarget { .. })));
+}
+
+#[test]
+fn framebuffer_scale() {
+    // renders a window at the given scale, returning the viewports and
+    // scissor rectangles set
+    let render = |scale: f32| {
+        let mut ctx = context();
+        ctx.io_mut().display_framebuffer_scale = [scale, scale];
+        let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+        renderer.device_mut().clear_log();
+        let build = |ui: &imgui::Ui| {
+            Window::new("Mock").build(ui, || ui.text("HiDPI"));
+        };
+        build(&ctx.frame());
+        let ui = ctx.frame();
+        build(&ui);
+        renderer.render(ui.render()).unwrap();
+        let log = renderer.device_mut().take_log();
+        let viewports: Vec<Viewport> = log
+            .iter()
+            .filter_map(|call| match *call {
+                Call::SetViewport(viewport) => Some(viewport),
+                _ => None,
+            })
+            .collect();
+        let scissors: Vec<ScissorRect> = log
+            .iter()
+            .filter_map(|call| match *call {
+                Call::SetScissorRect(rect) => Some(rect),
+                _ => None,
+            })
+            .collect();
+        (viewports, scissors)
+    };
+    let (_, scissors) = render(1.0);
+    let (viewports, scaled_scissors) = render(2.0);
+    // the viewport covers the framebuffer the scissor rectangles are given in
+    assert_eq!(viewports, [Viewport { x: 0.0, y: 0.0, width: 640.0, height: 400.0 }]);
+    let doubled: Vec<ScissorRect> = scissors
+        .iter()
+        .map(|r| ScissorRect {
+            left: r.left * 2,
+            top: r.top * 2,
+            right: r.right * 2,
+            bottom: r.bottom * 2,
+        })
+        .collect();
+    assert_eq!(scaled_scissors, doubled);
+}
+
+#[test]
+fn failing_render_to_restores_state() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    renderer.device_mut().set_fail_maps(true);
+    renderer.device_mut().clear_log();
+
+    let build = |ui: &imgui::Ui| {
+        Window::new("Mock").build(ui, || ui.text("Offscreen"));
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    let target = MockRenderTarget { id: 3, size: [640, 400] };
+    let err = renderer.render_to(ui.render(), &target, None).unwrap_err();
+    assert_eq!(err, MockError::MapFailed);
+    // the caller's render target is bound again
+    let log = renderer.device().log();
+    assert_eq!(log[..2], [Call::CaptureState(TouchedState::ALL), Call::SetRenderTarget(target)]);
+    assert_eq!(log.last(), Some(&Call::ApplyState(TouchedState::ALL)));
+}
+
+#[test]
+fn font_reload() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    let initial_size = renderer.device().texture_size(MockTexture(0));
+    renderer.device_mut().clear_log();
+
+    assert!(!renderer.reload_font_texture_if_changed(&mut ctx).unwrap());
+    assert!(renderer.device().log().is_empty());
+
+    ctx.fonts().clear();
+    ctx.fonts().add_font(&[FontSource::DefaultFontData {
+        config: Some(FontConfig { size_pixels: 39.0, ..FontConfig::default() }),
+    }]);
+    assert!(renderer.reload_font_texture_if_changed(&mut ctx).unwrap());
+    assert!(!renderer.reload_font_texture_if_changed(&mut ctx).unwrap());
+    let log = renderer.device_mut().take_log();
+    assert!(matches!(log[..], [Call::CreateTexture { texture: MockTexture(1), .. }]));
+    assert_ne!(renderer.device().texture_size(MockTexture(1)), initial_size);
+    assert_eq!(ctx.fonts().tex_id, TextureId::from(!0));
+
+    // an explicit reload always rebuilds and uploads the atlas
+    renderer.reload_font_texture(&mut ctx).unwrap();
+    assert!(matches!(
+        renderer.device_mut().take_log()[..],
+        [Call::CreateTexture { texture: MockTexture(2), .. }]
+    ));
+
+    let build = |ui: &imgui::Ui| {
+        Window::new("Mock").build(ui, || ui.text("Reloaded font"));
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    renderer.render(ui.render()).unwrap();
+    assert!(renderer.device().log().contains(&Call::SetTexture(MockTexture(2))));
+}
@@ -0,0 +1,171 @@
+//! Construction time configuration through renderer options and the
+//! builder.
+
+use std::mem;
+
+use imgui::{DrawIdx, DrawVert, Window};
+use imgui_dx11_renderer::color::ColorSpace;
+use imgui_dx11_renderer::device::{
+    BufferKind, CompareFunc, CullMode, DepthStencilDesc, FillMode, PipelineDesc, RasterizerDesc,
+};
+use imgui_dx11_renderer::mock::{Call, MockSampler, RecordingDevice};
+use imgui_dx11_renderer::options::{
+    BuildError, GrowthPolicy, OptionsError, RendererBuilder, RendererOptions, ShrinkPolicy,
+    DEFAULT_INDEX_CAPACITY, DEFAULT_VERTEX_CAPACITY,
+};
+use imgui_dx11_renderer::texture::{AddressMode, AlphaMode, Filter, SamplerDesc};
+use imgui_dx11_renderer::Renderer;
+
+fn context() -> imgui::Context {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    ctx
+}
+
+fn created_buffers(log: &[Call]) -> Vec<(BufferKind, usize)> {
+    log.iter()
+        .filter_map(|call| match *call {
+            Call::CreateBuffer { kind, size, .. } => Some((kind, size)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn validation() {
+    assert_eq!(RendererOptions::default().validate(), Ok(()));
+    let options = RendererOptions { vertex_capacity: 0, ..Default::default() };
+    assert_eq!(options.validate(), Err(OptionsError::ZeroCapacity));
+    let options = RendererOptions { index_capacity: usize::MAX, ..Default::default() };
+    assert_eq!(options.validate(), Err(OptionsError::CapacityTooLarge));
+    let options = RendererOptions { renderer_name: "tools\0".into(), ..Default::default() };
+    assert_eq!(options.validate(), Err(OptionsError::InvalidRendererName));
+    let depth_stencil = DepthStencilDesc { depth_test: None, depth_write: true };
+    let options = RendererOptions { depth_stencil, ..Default::default() };
+    assert_eq!(options.validate(), Err(OptionsError::InvalidDepthStencil));
+    let depth_stencil = DepthStencilDesc { depth_test: Some(CompareFunc::Less), depth_write: true };
+    let options = RendererOptions { depth_stencil, ..Default::default() };
+    assert_eq!(options.validate(), Ok(()));
+
+    // invalid options never reach the device
+    let mut ctx = context();
+    let res = RendererBuilder::new().vertex_capacity(0).build(&mut ctx, RecordingDevice::new());
+    match res {
+        Err(BuildError::Options(OptionsError::ZeroCapacity)) => {},
+        other => panic!("unexpected result {:?}", other.map(|_| ())),
+    }
+    let options = RendererOptions { renderer_name: "tools\0".into(), ..Default::default() };
+    let res = Renderer::with_options(&mut ctx, RecordingDevice::new(), &options);
+    assert!(matches!(res, Err(BuildError::Options(OptionsError::InvalidRendererName))));
+}
+
+#[test]
+fn growth_policies() {
+    let initial = DEFAULT_VERTEX_CAPACITY;
+    assert_eq!(GrowthPolicy::Linear.capacity(7000, initial, initial), 12000);
+    assert_eq!(GrowthPolicy::Exact.capacity(7000, initial, initial), 7000);
+    assert_eq!(GrowthPolicy::Exact.capacity(10, initial, initial), 5000);
+    assert_eq!(GrowthPolicy::Geometric(2.0).capacity(7000, initial, initial), 10000);
+    assert_eq!(GrowthPolicy::Geometric(1.5).capacity(9000, initial, initial), 9000);
+    assert_eq!(GrowthPolicy::Linear.capacity(usize::MAX, 1, 1), usize::MAX);
+    assert_eq!(GrowthPolicy::Geometric(2.0).capacity(1, usize::MAX, 1), usize::MAX);
+
+    for factor in [1.0, 0.5, f32::NAN, f32::INFINITY] {
+        let options = RendererOptions {
+            growth_policy: GrowthPolicy::Geometric(factor),
+            ..Default::default()
+        };
+        assert_eq!(options.validate(), Err(OptionsError::InvalidGrowthFactor), "{}", factor);
+    }
+    for shrink in
+        [ShrinkPolicy::new(0, 0.25), ShrinkPolicy::new(10, 1.0), ShrinkPolicy::new(10, -0.5)]
+    {
+        let options = RendererOptions { shrink_policy: Some(shrink), ..Default::default() };
+        assert_eq!(options.validate(), Err(OptionsError::InvalidShrinkPolicy), "{:?}", shrink);
+    }
+}
+
+#[test]
+fn default_options() {
+    let mut ctx = context();
+    let renderer = RendererBuilder::new().build(&mut ctx, RecordingDevice::new()).unwrap();
+    assert_eq!(
+        created_buffers(renderer.device().log())[1..],
+        [
+            (BufferKind::Vertex, DEFAULT_VERTEX_CAPACITY * mem::size_of::<DrawVert>()),
+            (BufferKind::Index, DEFAULT_INDEX_CAPACITY * mem::size_of::<DrawIdx>()),
+        ]
+    );
+    let name = ctx.renderer_name().unwrap();
+    assert!(name.starts_with("imgui_dx11_renderer@"), "{}", name);
+}
+
+#[test]
+fn builder_options() {
+    let mut ctx = context();
+    let point = SamplerDesc::new(Filter::Point, AddressMode::Clamp);
+    let wireframe = RasterizerDesc {
+        fill_mode: FillMode::Wireframe,
+        cull_mode: CullMode::Back,
+        depth_clip: false,
+    };
+    let depth_stencil =
+        DepthStencilDesc { depth_test: Some(CompareFunc::LessEqual), depth_write: false };
+    let builder = RendererBuilder::new()
+        .vertex_capacity(16)
+        .index_capacity(32)
+        .growth_policy(GrowthPolicy::Exact)
+        .default_sampler(point)
+        .alpha(AlphaMode::Premultiplied)
+        .color_space(ColorSpace::Linear)
+        .rasterizer(wireframe)
+        .depth_stencil(depth_stencil)
+        .renderer_name("tools");
+    assert_eq!(builder.options().vertex_capacity, 16);
+    let mut renderer = builder.build(&mut ctx, RecordingDevice::new()).unwrap();
+    assert_eq!(ctx.renderer_name(), Some("tools"));
+    assert_eq!(
+        (renderer.alpha(), renderer.color_space()),
+        (AlphaMode::Premultiplied, ColorSpace::Linear)
+    );
+
+    let log = renderer.device_mut().take_log();
+    let desc = PipelineDesc {
+        alpha: AlphaMode::Premultiplied,
+        color_space: ColorSpace::Linear,
+        rasterizer: wireframe,
+        depth_stencil,
+    };
+    assert_eq!(log[0], Call::CreatePipeline(desc));
+    assert!(log.contains(&Call::CreateSampler { sampler: MockSampler(0), desc: point }));
+    assert_eq!(
+        created_buffers(&log)[1..],
+        [
+            (BufferKind::Vertex, 16 * mem::size_of::<DrawVert>()),
+            (BufferKind::Index, 32 * mem::size_of::<DrawIdx>()),
+        ]
+    );
+    assert_eq!(renderer.texture_sampler(ctx.fonts().tex_id), point);
+
+    let build = |ui: &imgui::Ui| {
+        Window::new("Options").build(ui, || ui.text("Grown to exactly the frame's size"));
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    let draw_data = ui.render();
+    renderer.render(draw_data).unwrap();
+    let log = renderer.device_mut().take_log();
+    assert_eq!(
+        created_buffers(&log),
+        [
+            (BufferKind::Vertex, draw_data.total_vtx_count as usize * mem::size_of::<DrawVert>()),
+            (BufferKind::Index, draw_data.total_idx_count as usize * mem::size_of::<DrawIdx>()),
+        ]
+    );
+    // the default sampler is bound without any sampler changes
+    let samplers: Vec<&Call> =
+        log.iter().filter(|call| matches!(call, Call::SetSampler(_))).collect();
+    assert_eq!(samplers, [&Call::SetSampler(MockSampler(0))]);
+}
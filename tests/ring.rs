@@ -0,0 +1,143 @@
+//! Suballocation of the streaming buffers and the resulting command stream.
+
+use imgui::Window;
+use imgui_dx11_renderer::mock::{Call, MockBuffer, RecordingDevice};
+use imgui_dx11_renderer::options::RendererBuilder;
+use imgui_dx11_renderer::ring::{RingAllocation, RingBuffer};
+use imgui_dx11_renderer::Renderer;
+
+fn context() -> imgui::Context {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    ctx
+}
+
+/// Renders a frame, returning the log and the number of vertices and
+/// indices.
+fn render(
+    ctx: &mut imgui::Context,
+    renderer: &mut Renderer<RecordingDevice>,
+) -> (Vec<Call>, usize, usize) {
+    renderer.device_mut().clear_log();
+    let build = |ui: &imgui::Ui| {
+        Window::new("Ring").build(ui, || ui.text("Streamed"));
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    let draw_data = ui.render();
+    renderer.render(draw_data).unwrap();
+    let counts = (draw_data.total_vtx_count as usize, draw_data.total_idx_count as usize);
+    (renderer.device_mut().take_log(), counts.0, counts.1)
+}
+
+fn draws(log: &[Call]) -> Vec<(u32, u32, i32)> {
+    log.iter()
+        .filter_map(|call| match *call {
+            Call::DrawIndexed { index_count, start_index, base_vertex } => {
+                Some((index_count, start_index, base_vertex))
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn allocations() {
+    let mut ring = RingBuffer::new(100);
+    assert_eq!(ring.capacity(), 100);
+    assert_eq!(ring.allocate(30), Some(RingAllocation { offset: 0, discard: true }));
+    assert_eq!(ring.allocate(50), Some(RingAllocation { offset: 30, discard: false }));
+    assert_eq!(ring.head(), 80);
+    assert_eq!(ring.allocate(20), Some(RingAllocation { offset: 80, discard: false }));
+    // the buffer is full, the next allocation wraps
+    assert_eq!(ring.allocate(1), Some(RingAllocation { offset: 0, discard: true }));
+    assert_eq!(ring.allocate(99), Some(RingAllocation { offset: 1, discard: false }));
+    assert_eq!(ring.allocate(0), Some(RingAllocation { offset: 100, discard: false }));
+}
+
+#[test]
+fn oversized_allocations() {
+    let mut ring = RingBuffer::new(10);
+    assert_eq!(ring.allocate(11), None);
+    // failed allocations leave the ring untouched
+    assert_eq!(ring.head(), 10);
+    assert_eq!(ring.allocate(10), Some(RingAllocation { offset: 0, discard: true }));
+    assert_eq!(ring.allocate(6), Some(RingAllocation { offset: 0, discard: true }));
+    assert_eq!(ring.allocate(6), Some(RingAllocation { offset: 0, discard: true }));
+}
+
+#[test]
+fn consecutive_renders_append() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    let (first, vtx_count, idx_count) = render(&mut ctx, &mut renderer);
+    let (cb, vb, ib) = (MockBuffer(0), MockBuffer(1), MockBuffer(2));
+    for buffer in [cb, vb, ib].iter() {
+        assert!(first.contains(&Call::Map(*buffer)));
+    }
+
+    let (second, ..) = render(&mut ctx, &mut renderer);
+    assert!(second.contains(&Call::MapNoOverwrite(vb)));
+    assert!(second.contains(&Call::MapNoOverwrite(ib)));
+    // the unchanged constants aren't rewritten
+    assert!(!second.iter().any(|call| matches!(call, Call::Map(_))));
+    let expected: Vec<(u32, u32, i32)> = draws(&first)
+        .iter()
+        .map(|&(count, start, base)| (count, start + idx_count as u32, base + vtx_count as i32))
+        .collect();
+    assert_eq!(draws(&second), expected);
+}
+
+#[test]
+fn wrap_around_discards() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    let (first, vtx_count, idx_count) = render(&mut ctx, &mut renderer);
+    // room for the data of two frames
+    let mut renderer = RendererBuilder::new()
+        .vertex_capacity(vtx_count * 5 / 2)
+        .index_capacity(idx_count * 5 / 2)
+        .build(&mut ctx, RecordingDevice::new())
+        .unwrap();
+    let (vb, ib) = (MockBuffer(1), MockBuffer(2));
+    for frame in 0..6 {
+        let (log, ..) = render(&mut ctx, &mut renderer);
+        if frame % 2 == 0 {
+            assert!(log.contains(&Call::Map(vb)) && log.contains(&Call::Map(ib)));
+            assert_eq!(draws(&log), draws(&first));
+        } else {
+            assert!(log.contains(&Call::MapNoOverwrite(vb)));
+            assert!(log.contains(&Call::MapNoOverwrite(ib)));
+        }
+    }
+}
+
+#[test]
+fn failed_maps_leave_the_rings_untouched() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    let (first, ..) = render(&mut ctx, &mut renderer);
+    let (vb, ib) = (MockBuffer(1), MockBuffer(2));
+
+    renderer.device_mut().set_fail_maps_of(Some(ib));
+    renderer.device_mut().clear_log();
+    let ui = ctx.frame();
+    Window::new("Ring").build(&ui, || ui.text("Streamed"));
+    assert!(renderer.render(ui.render()).is_err());
+    // the mapped vertex buffer is unmapped again
+    let log = renderer.device_mut().take_log();
+    let pos = log.iter().position(|call| *call == Call::MapNoOverwrite(vb)).unwrap();
+    assert_eq!(log[pos + 1..].iter().filter(|call| **call == Call::Unmap(vb)).count(), 1);
+    assert!(!log.iter().any(|call| matches!(call, Call::DrawIndexed { .. })));
+
+    // the next frame is written where the failed one would have been
+    renderer.device_mut().set_fail_maps_of(None);
+    let (second, vtx_count, idx_count) = render(&mut ctx, &mut renderer);
+    let expected: Vec<(u32, u32, i32)> = draws(&first)
+        .iter()
+        .map(|&(count, start, base)| (count, start + idx_count as u32, base + vtx_count as i32))
+        .collect();
+    assert_eq!(draws(&second), expected);
+}
@@ -0,0 +1,137 @@
+//! Preserving the device state around render calls according to the state
+//! policy.
+
+use imgui::{DrawData, Window};
+use imgui_dx11_renderer::device::{StatePolicy, TouchedState};
+use imgui_dx11_renderer::mock::{Call, MockError, MockRenderTarget, RecordingDevice};
+use imgui_dx11_renderer::plan::DrawPlan;
+use imgui_dx11_renderer::Renderer;
+
+fn setup() -> (imgui::Context, Renderer<RecordingDevice>) {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    renderer.device_mut().clear_log();
+    (ctx, renderer)
+}
+
+/// Renders the second frame of a window, or of nothing, with `render` and
+/// returns the calls made.
+fn render_log(
+    ctx: &mut imgui::Context,
+    renderer: &mut Renderer<RecordingDevice>,
+    window: bool,
+    render: impl Fn(&mut Renderer<RecordingDevice>, &DrawData),
+) -> Vec<Call> {
+    let build = |ui: &imgui::Ui| {
+        if window {
+            Window::new("State").build(ui, || ui.text("Text"));
+        }
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    let draw_data = ui.render();
+    // the plan the renderer derives the touched state from
+    let plan = DrawPlan::build(draw_data, renderer.textures()).unwrap();
+    assert_eq!(plan.draws(), window);
+    render(renderer, draw_data);
+    renderer.device_mut().take_log()
+}
+
+fn state_calls(log: &[Call]) -> Vec<Call> {
+    log.iter()
+        .filter(|call| matches!(call, Call::CaptureState(_) | Call::ApplyState(_)))
+        .cloned()
+        .collect()
+}
+
+#[test]
+fn full_policy() {
+    let (mut ctx, mut renderer) = setup();
+    assert_eq!(renderer.state_policy(), StatePolicy::Full);
+    let log = render_log(&mut ctx, &mut renderer, true, |r, d| r.render(d).unwrap());
+    assert_eq!(log.first(), Some(&Call::CaptureState(TouchedState::ALL)));
+    assert_eq!(log.last(), Some(&Call::ApplyState(TouchedState::ALL)));
+}
+
+#[test]
+fn touched_policy() {
+    let (mut ctx, mut renderer) = setup();
+    renderer.set_state_policy(StatePolicy::Touched);
+    let drawn = TouchedState {
+        render_targets: false,
+        viewports: true,
+        scissor_rects: true,
+        pipeline: true,
+        index_buffer: true,
+        vertex_buffers: 1,
+        vs_constant_buffers: 1,
+        ps_constant_buffers: 2,
+        ps_textures: 1,
+        ps_samplers: 1,
+    };
+    let log = render_log(&mut ctx, &mut renderer, true, |r, d| r.render(d).unwrap());
+    assert_eq!(state_calls(&log), [Call::CaptureState(drawn), Call::ApplyState(drawn)]);
+    assert_eq!(log.first(), Some(&Call::CaptureState(drawn)));
+    assert_eq!(log.last(), Some(&Call::ApplyState(drawn)));
+
+    let target = MockRenderTarget { id: 1, size: [320, 200] };
+    let log =
+        render_log(&mut ctx, &mut renderer, true, |r, d| r.render_to(d, &target, None).unwrap());
+    let with_target = TouchedState { render_targets: true, ..drawn };
+    assert_eq!(state_calls(&log), [Call::CaptureState(with_target), Call::ApplyState(with_target)]);
+
+    // frames without draws touch nothing but the render target
+    let log = render_log(&mut ctx, &mut renderer, false, |r, d| r.render(d).unwrap());
+    assert!(log.is_empty(), "{:?}", log);
+    let color = [0.0; 4];
+    let log = render_log(&mut ctx, &mut renderer, false, |r, d| {
+        r.render_to(d, &target, Some(color)).unwrap()
+    });
+    let targets_only = TouchedState { render_targets: true, ..TouchedState::default() };
+    assert_eq!(
+        log,
+        [
+            Call::CaptureState(targets_only),
+            Call::SetRenderTarget(target),
+            Call::ClearRenderTarget { target, color },
+            Call::ApplyState(targets_only),
+        ]
+    );
+}
+
+#[test]
+fn no_policy() {
+    let (mut ctx, mut renderer) = setup();
+    renderer.set_state_policy(StatePolicy::None);
+    let log = render_log(&mut ctx, &mut renderer, true, |r, d| r.render(d).unwrap());
+    assert!(state_calls(&log).is_empty());
+    assert!(log.iter().any(|call| matches!(call, Call::DrawIndexed { .. })));
+}
+
+#[test]
+fn failing_render_calls_restore_state() {
+    for &policy in [StatePolicy::Full, StatePolicy::Touched, StatePolicy::None].iter() {
+        let (mut ctx, mut renderer) = setup();
+        renderer.set_state_policy(policy);
+        renderer.device_mut().set_fail_maps(true);
+        let log = render_log(&mut ctx, &mut renderer, true, |r, d| {
+            assert_eq!(r.render(d), Err(MockError::MapFailed))
+        });
+        assert!(!log.iter().any(|call| matches!(call, Call::DrawIndexed { .. })));
+        let calls = state_calls(&log);
+        match policy {
+            StatePolicy::None => assert!(calls.is_empty()),
+            // every captured state is applied again
+            _ => match calls[..] {
+                [Call::CaptureState(captured), Call::ApplyState(applied)] => {
+                    assert_eq!(captured, applied);
+                    assert_eq!(log.last(), Some(&Call::ApplyState(applied)));
+                },
+                _ => panic!("{:?}: unexpected state calls {:?}", policy, calls),
+            },
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Per render call statistics.
+
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use imgui::{sys, DrawIdx, DrawVert, ImColor32, Image, Window};
+use imgui_dx11_renderer::mock::{Call, MockTexture, RecordingDevice};
+use imgui_dx11_renderer::options::RendererBuilder;
+use imgui_dx11_renderer::stats::FrameStats;
+use imgui_dx11_renderer::Renderer;
+
+static CALLBACK_INVOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn count_callback(_: *const sys::ImDrawList, _: *const sys::ImDrawCmd) {
+    CALLBACK_INVOCATIONS.fetch_add(1, Ordering::SeqCst);
+}
+
+fn context() -> imgui::Context {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    ctx
+}
+
+/// Renders a frame with a user texture, a callback and a command clipped
+/// entirely, returning the log and the number of vertices and indices.
+fn render(
+    ctx: &mut imgui::Context,
+    renderer: &mut Renderer<RecordingDevice>,
+) -> (Vec<Call>, usize, usize) {
+    let texture_id = renderer.textures_mut().insert(MockTexture(42));
+    renderer.device_mut().clear_log();
+    let build = |ui: &imgui::Ui| {
+        Window::new("Stats").build(ui, || {
+            ui.text("Counted");
+            Image::new(texture_id, [32.0, 32.0]).build(ui);
+            unsafe {
+                let draw_list = sys::igGetWindowDrawList();
+                sys::ImDrawList_AddCallback(draw_list, Some(count_callback), std::ptr::null_mut());
+            }
+        });
+        let draw_list = ui.get_background_draw_list();
+        draw_list.with_clip_rect([50.0, 50.0], [50.0, 80.0], || {
+            draw_list.add_rect([40.0, 40.0], [90.0, 90.0], ImColor32::WHITE).filled(true).build();
+        });
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    let draw_data = ui.render();
+    renderer.render(draw_data).unwrap();
+    let counts = (draw_data.total_vtx_count as usize, draw_data.total_idx_count as usize);
+    (renderer.device_mut().take_log(), counts.0, counts.1)
+}
+
+fn count(log: &[Call], f: impl Fn(&Call) -> bool) -> u32 {
+    log.iter().filter(|call| f(call)).count() as u32
+}
+
+#[test]
+fn frame_stats() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    assert_eq!(renderer.last_frame_stats(), FrameStats::default());
+    let invocations = CALLBACK_INVOCATIONS.load(Ordering::SeqCst);
+    let (log, vtx_count, idx_count) = render(&mut ctx, &mut renderer);
+    assert_eq!(CALLBACK_INVOCATIONS.load(Ordering::SeqCst) - invocations, 1);
+
+    let triangles: u32 = log
+        .iter()
+        .map(|call| match *call {
+            Call::DrawIndexed { index_count, .. } => index_count / 3,
+            _ => 0,
+        })
+        .sum();
+    let buffer_bytes =
+        vtx_count * mem::size_of::<DrawVert>() + idx_count * mem::size_of::<DrawIdx>();
+    let stats = renderer.last_frame_stats();
+    assert_eq!(
+        stats,
+        FrameStats {
+            draw_calls: count(&log, |call| matches!(call, Call::DrawIndexed { .. })),
+            triangles,
+            vertices: vtx_count as u32,
+            indices: idx_count as u32,
+            texture_binds: count(&log, |call| matches!(call, Call::SetTexture(_))),
+            scissor_changes: count(&log, |call| matches!(call, Call::SetScissorRect(_))),
+            skipped_commands: 1,
+            callbacks: 1,
+            buffer_reallocs: 0,
+            // the buffers and the vertex shader constants
+            bytes_mapped: (buffer_bytes + 80) as u64,
+        }
+    );
+    assert!(stats.draw_calls >= 2 && stats.texture_binds >= 2);
+
+    // unchanged constants aren't mapped again
+    render(&mut ctx, &mut renderer);
+    let second = renderer.last_frame_stats();
+    assert_eq!(second.bytes_mapped, stats.bytes_mapped - 80);
+
+    // the statistics of several render calls add up
+    let total = stats + second;
+    assert_eq!(total.draw_calls, stats.draw_calls * 2);
+    assert_eq!(total.bytes_mapped, stats.bytes_mapped + second.bytes_mapped);
+}
+
+#[test]
+fn skipped_commands_draw_nothing() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    let (log, ..) = render(&mut ctx, &mut renderer);
+    // the clip rectangle is empty, the rectangle behind it is never drawn
+    assert!(log.iter().all(|call| match call {
+        Call::SetScissorRect(rect) => rect.right > rect.left && rect.bottom > rect.top,
+        _ => true,
+    }));
+}
+
+#[test]
+fn reallocations() {
+    let mut ctx = context();
+    let mut renderer = RendererBuilder::new()
+        .vertex_capacity(1)
+        .index_capacity(1)
+        .build(&mut ctx, RecordingDevice::new())
+        .unwrap();
+    render(&mut ctx, &mut renderer);
+    assert_eq!(renderer.last_frame_stats().buffer_reallocs, 2);
+    render(&mut ctx, &mut renderer);
+    assert_eq!(renderer.last_frame_stats().buffer_reallocs, 0);
+}
+
+#[test]
+fn empty_frames() {
+    let mut ctx = context();
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    render(&mut ctx, &mut renderer);
+    assert_ne!(renderer.last_frame_stats(), FrameStats::default());
+
+    // nothing is drawn, uploaded or mapped
+    let ui = ctx.frame();
+    renderer.render(ui.render()).unwrap();
+    assert_eq!(renderer.last_frame_stats(), FrameStats::default());
+}
@@ -0,0 +1,244 @@
+//! Validation and upload of textures created from pixel data.
+
+use imgui::{Image, TextureId, Window};
+use imgui_dx11_renderer::device::PipelineDesc;
+use imgui_dx11_renderer::mock::{Call, MockError, MockSampler, MockTexture, RecordingDevice};
+use imgui_dx11_renderer::texture::{
+    premultiply_alpha, AddressMode, AlphaMode, Filter, SamplerDesc, TextureDesc, TextureError,
+    TextureFormat, TextureRect, MAX_TEXTURE_DIMENSION,
+};
+use imgui_dx11_renderer::Renderer;
+
+fn renderer(ctx: &mut imgui::Context) -> Renderer<RecordingDevice> {
+    let mut renderer = Renderer::with_device(ctx, RecordingDevice::new()).unwrap();
+    renderer.device_mut().clear_log();
+    renderer
+}
+
+#[test]
+fn validation() {
+    let desc = TextureDesc::new(4, 2, TextureFormat::Rgba16Float);
+    assert_eq!(desc.row_pitch(), 32);
+    assert_eq!(desc.validate_data(&[0; 64]), Ok(()));
+    assert_eq!(
+        desc.validate_data(&[0; 63]),
+        Err(TextureError::DataSizeMismatch { expected: 64, actual: 63 })
+    );
+    for (width, height) in [(0, 2), (4, 0), (MAX_TEXTURE_DIMENSION + 1, 1)] {
+        let desc = TextureDesc::new(width, height, TextureFormat::Rgba8);
+        assert_eq!(desc.validate_data(&[]), Err(TextureError::InvalidDimensions { width, height }));
+    }
+
+    assert_eq!(desc.validate_update(TextureRect::new(1, 1, 3, 1), &[0; 24]), Ok(()));
+    assert_eq!(
+        desc.validate_update(TextureRect::new(1, 1, 3, 1), &[0; 32]),
+        Err(TextureError::DataSizeMismatch { expected: 24, actual: 32 })
+    );
+    for rect in [
+        TextureRect::new(2, 0, 3, 1),
+        TextureRect::new(0, 1, 1, 2),
+        TextureRect::new(0, 0, 0, 1),
+        TextureRect::new(u32::MAX, 0, 1, 1),
+    ] {
+        assert_eq!(
+            desc.validate_update(rect, &[]),
+            Err(TextureError::RectOutOfBounds { rect, width: 4, height: 2 })
+        );
+    }
+}
+
+#[test]
+fn create_and_update() {
+    let mut ctx = imgui::Context::create();
+    let mut renderer = renderer(&mut ctx);
+
+    let pixels: Vec<u8> = (0..16).collect();
+    let id = renderer.create_texture(2, 2, TextureFormat::Bgra8, &pixels).unwrap();
+    assert_eq!(renderer.texture_desc(id), Some(&TextureDesc::new(2, 2, TextureFormat::Bgra8)));
+    let texture = *renderer.textures().get(id).unwrap();
+    assert_eq!(renderer.device().texture_data(texture), &pixels[..]);
+
+    let rect = TextureRect::new(1, 0, 1, 2);
+    renderer.update_texture(id, rect, &[0xAA; 8]).unwrap();
+    let mut expected = pixels.clone();
+    expected[4..8].fill(0xAA);
+    expected[12..16].fill(0xAA);
+    assert_eq!(renderer.device().texture_data(texture), &expected[..]);
+    assert_eq!(
+        renderer.device_mut().take_log(),
+        [
+            Call::CreateTexture { texture, width: 2, height: 2, format: TextureFormat::Bgra8 },
+            Call::UpdateTexture { texture, rect },
+        ]
+    );
+
+    assert_eq!(renderer.remove_texture(id), Some(texture));
+    assert_eq!(renderer.texture_desc(id), None);
+    assert_eq!(
+        renderer.update_texture(id, rect, &[0xAA; 8]),
+        Err(MockError::Texture(TextureError::UnknownTexture(id)))
+    );
+}
+
+#[test]
+fn invalid_uploads_skip_the_device() {
+    let mut ctx = imgui::Context::create();
+    let mut renderer = renderer(&mut ctx);
+
+    assert_eq!(
+        renderer.create_texture(2, 2, TextureFormat::Rgba8, &[0; 15]),
+        Err(MockError::Texture(TextureError::DataSizeMismatch { expected: 16, actual: 15 }))
+    );
+    let id = renderer.create_texture(2, 2, TextureFormat::Rgba8, &[0; 16]).unwrap();
+    let rect = TextureRect::new(1, 1, 2, 1);
+    assert_eq!(
+        renderer.update_texture(id, rect, &[0; 8]),
+        Err(MockError::Texture(TextureError::RectOutOfBounds { rect, width: 2, height: 2 }))
+    );
+    assert_eq!(renderer.device().log().len(), 1);
+
+    let unmanaged = renderer.textures_mut().insert(MockTexture(0));
+    assert_eq!(
+        renderer.update_texture(unmanaged, TextureRect::new(0, 0, 1, 1), &[0; 4]),
+        Err(MockError::Texture(TextureError::Unmanaged(unmanaged)))
+    );
+}
+
+#[test]
+fn render_created_texture() {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    let mut renderer = renderer(&mut ctx);
+    let id = renderer.create_texture(1, 1, TextureFormat::Rgba32Float, &[0; 16]).unwrap();
+    assert_ne!(id, TextureId::from(!0));
+
+    let build = |ui: &imgui::Ui| {
+        Window::new("Textures").build(ui, || Image::new(id, [32.0, 32.0]).build(ui));
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    renderer.render(ui.render()).unwrap();
+    let texture = *renderer.textures().get(id).unwrap();
+    assert!(renderer.device().log().contains(&Call::SetTexture(texture)));
+}
+
+#[test]
+fn texture_samplers() {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    let mut renderer = renderer(&mut ctx);
+    let sprite = renderer.create_texture(1, 1, TextureFormat::Rgba8, &[0; 4]).unwrap();
+    let tiles = renderer.create_texture(1, 1, TextureFormat::Rgba8, &[0; 4]).unwrap();
+    let blurry = renderer.create_texture(1, 1, TextureFormat::Rgba8, &[0; 4]).unwrap();
+    let pixel_art = SamplerDesc::new(Filter::Point, AddressMode::Clamp);
+    renderer.set_texture_sampler(sprite, pixel_art).unwrap();
+    renderer.set_texture_sampler(tiles, pixel_art).unwrap();
+    assert_eq!(renderer.texture_sampler(tiles), pixel_art);
+    assert_eq!(renderer.texture_sampler(blurry), SamplerDesc::default());
+    let samplers_created = renderer
+        .device()
+        .log()
+        .iter()
+        .filter(|call| matches!(call, Call::CreateSampler { .. }))
+        .count();
+    assert_eq!(samplers_created, 1);
+    assert_eq!(renderer.device().sampler_desc(MockSampler(0)), SamplerDesc::default());
+    assert_eq!(renderer.device().sampler_desc(MockSampler(1)), pixel_art);
+    renderer.device_mut().clear_log();
+
+    let build = |ui: &imgui::Ui| {
+        Window::new("Samplers").build(ui, || {
+            ui.text("Font");
+            for id in [sprite, tiles, blurry] {
+                Image::new(id, [16.0, 16.0]).build(ui);
+            }
+        });
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    renderer.render(ui.render()).unwrap();
+
+    let texture = |id| Call::SetTexture(*renderer.textures().get(id).unwrap());
+    let binds: Vec<Call> = renderer
+        .device()
+        .log()
+        .iter()
+        .filter(|call| matches!(call, Call::SetTexture(_) | Call::SetSampler(_)))
+        .cloned()
+        .collect();
+    assert_eq!(
+        binds,
+        [
+            Call::SetSampler(MockSampler(0)),
+            Call::SetTexture(MockTexture(0)),
+            texture(sprite),
+            Call::SetSampler(MockSampler(1)),
+            texture(tiles),
+            texture(blurry),
+            Call::SetSampler(MockSampler(0)),
+        ]
+    );
+
+    renderer.remove_texture(sprite);
+    assert_eq!(renderer.texture_sampler(sprite), SamplerDesc::default());
+}
+
+#[test]
+fn premultiplied_alpha() {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    let device = RecordingDevice::new();
+    let mut renderer =
+        Renderer::with_device_and_alpha(&mut ctx, device, AlphaMode::Premultiplied).unwrap();
+    assert_eq!(renderer.alpha(), AlphaMode::Premultiplied);
+    let desc = PipelineDesc { alpha: AlphaMode::Premultiplied, ..PipelineDesc::default() };
+    assert_eq!(renderer.device().log()[0], Call::CreatePipeline(desc));
+
+    // the font texture is uploaded premultiplied
+    let mut font_data = ctx.fonts().build_rgba32_texture().data.to_vec();
+    premultiply_alpha(&mut font_data);
+    assert_eq!(renderer.device().texture_data(MockTexture(0)), &font_data[..]);
+    assert_eq!(renderer.texture_alpha(ctx.fonts().tex_id), AlphaMode::Premultiplied);
+
+    let straight = renderer.create_texture(1, 1, TextureFormat::Rgba8, &[0; 4]).unwrap();
+    let premultiplied = renderer.create_texture(1, 1, TextureFormat::Rgba8, &[0; 4]).unwrap();
+    renderer.set_texture_alpha(premultiplied, AlphaMode::Premultiplied);
+    assert_eq!(renderer.texture_alpha(straight), AlphaMode::Straight);
+    assert_eq!(renderer.texture_alpha(premultiplied), AlphaMode::Premultiplied);
+
+    let build = |ui: &imgui::Ui| {
+        Window::new("Alpha").build(ui, || {
+            Image::new(premultiplied, [16.0, 16.0]).build(ui);
+            Image::new(straight, [16.0, 16.0]).build(ui);
+            ui.text("Font");
+        });
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    renderer.device_mut().clear_log();
+    renderer.render(ui.render()).unwrap();
+    let shaders: Vec<Call> = renderer
+        .device()
+        .log()
+        .iter()
+        .filter(|call| matches!(call, Call::SetPixelShader { .. }))
+        .cloned()
+        .collect();
+    // only straight textures need the converting pixel shader
+    assert_eq!(
+        shaders,
+        [
+            Call::SetPixelShader { shader: None, texture_alpha: AlphaMode::Straight },
+            Call::SetPixelShader { shader: None, texture_alpha: AlphaMode::Premultiplied },
+        ]
+    );
+
+    renderer.remove_texture(premultiplied);
+    assert_eq!(renderer.texture_alpha(premultiplied), AlphaMode::Straight);
+}
@@ -0,0 +1,172 @@
+//! GPU timer query bookkeeping with fake timestamps.
+
+use imgui::Window;
+use imgui_dx11_renderer::mock::{Call, MockTimerQuery, RecordingDevice};
+use imgui_dx11_renderer::timing::{GpuTimer, GpuTiming, QueryRing, TimestampData, TimingHistory};
+use imgui_dx11_renderer::Renderer;
+
+fn timestamps(begin: u64, end: u64) -> TimestampData {
+    TimestampData { begin, end, frequency: 1_000_000, disjoint: false }
+}
+
+fn timing(frame: u64, milliseconds: f64) -> GpuTiming {
+    GpuTiming { frame, milliseconds, latency: 0 }
+}
+
+#[test]
+fn milliseconds() {
+    assert_eq!(timestamps(1000, 3500).milliseconds(), Some(2.5));
+    assert_eq!(timestamps(1000, 1000).milliseconds(), Some(0.0));
+    assert_eq!(TimestampData { disjoint: true, ..timestamps(0, 10) }.milliseconds(), None);
+    assert_eq!(TimestampData { frequency: 0, ..timestamps(0, 10) }.milliseconds(), None);
+    assert_eq!(timestamps(10, 0).milliseconds(), None);
+}
+
+#[test]
+fn query_ring() {
+    let mut ring = QueryRing::new(vec!['a', 'b', 'c']);
+    assert_eq!(ring.capacity(), 3);
+    assert_eq!(ring.poll(|_| unreachable!()), None);
+    assert_eq!(ring.issue(0), Some(&'a'));
+    assert_eq!(ring.issue(1), Some(&'b'));
+    assert_eq!(ring.issue(2), Some(&'c'));
+    assert_eq!(ring.newest(), Some(&'c'));
+    // all queries are in flight
+    assert_eq!(ring.issue(3), None);
+    assert_eq!((ring.in_flight(), ring.dropped()), (3, 1));
+
+    // queries are read back in issue order
+    assert_eq!(ring.poll(|_| None), None);
+    assert_eq!(ring.poll(|&q| Some(timestamps(0, q as u64))), Some((0, timestamps(0, 'a' as u64))));
+    assert_eq!(ring.issue(4), Some(&'a'));
+    assert_eq!(ring.poll(|&q| Some(timestamps(0, q as u64))), Some((1, timestamps(0, 'b' as u64))));
+    assert_eq!(ring.in_flight(), 2);
+
+    let mut empty = QueryRing::<char>::new(Vec::new());
+    assert_eq!(empty.issue(0), None);
+    assert_eq!(empty.newest(), None);
+}
+
+#[test]
+fn rolling_history() {
+    let mut history = TimingHistory::new(3);
+    assert!(history.is_empty());
+    assert_eq!((history.average_ms(), history.max_ms()), (None, None));
+    for (frame, ms) in [1.0, 4.0, 2.0, 3.0].iter().enumerate() {
+        history.push(timing(frame as u64, *ms));
+    }
+    assert_eq!(history.len(), 3);
+    let frames: Vec<u64> = history.iter().map(|timing| timing.frame).collect();
+    assert_eq!(frames, [1, 2, 3]);
+    assert_eq!(history.latest(), Some(&timing(3, 3.0)));
+    assert_eq!(history.average_ms(), Some(3.0));
+    assert_eq!(history.max_ms(), Some(4.0));
+    history.clear();
+    assert!(history.is_empty());
+
+    let mut disabled = TimingHistory::new(0);
+    disabled.push(timing(0, 1.0));
+    assert!(disabled.is_empty());
+}
+
+#[test]
+fn fake_gpu_latency() {
+    // the fake GPU finishes a frame three frames after it was issued
+    const LATENCY: u64 = 3;
+    let mut timer = GpuTimer::new(vec![0, 1, 2, 3], 8);
+    let mut issued = [0; 4];
+    for _ in 0..20 {
+        let frame = timer.frame();
+        timer.next_frame(|&q| {
+            let issued = issued[q];
+            (frame + 1 - issued >= LATENCY).then(|| timestamps(issued * 1000, issued * 1000 + 1500))
+        });
+        if let Some(&q) = timer.issue() {
+            issued[q] = timer.frame();
+        }
+    }
+    assert_eq!(timer.frame(), 20);
+    assert_eq!(timer.ring().dropped(), 0);
+    assert_eq!(timer.ring().in_flight(), LATENCY as usize);
+    let history = timer.history();
+    assert_eq!(history.len(), 8);
+    assert!(history.iter().all(|timing| timing.latency == LATENCY && timing.milliseconds == 1.5));
+    assert_eq!(history.latest().map(|timing| timing.frame), Some(20 - LATENCY));
+}
+
+#[test]
+fn dropped_and_disjoint_frames() {
+    let mut timer = GpuTimer::new(vec![0, 1], 8);
+    // the GPU never finishes
+    for _ in 0..4 {
+        timer.next_frame(|_| None);
+        timer.issue();
+    }
+    assert_eq!((timer.ring().in_flight(), timer.ring().dropped()), (2, 2));
+
+    timer.next_frame(|_| Some(TimestampData { disjoint: true, ..timestamps(0, 10) }));
+    assert_eq!(timer.disjoint(), 2);
+    assert!(timer.history().is_empty());
+    assert_eq!(timer.ring().in_flight(), 0);
+}
+
+fn render(ctx: &mut imgui::Context, renderer: &mut Renderer<RecordingDevice>) -> Vec<Call> {
+    renderer.device_mut().clear_log();
+    let build = |ui: &imgui::Ui| {
+        Window::new("Timed").build(ui, || ui.text("Measured"));
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    renderer.render(ui.render()).unwrap();
+    renderer.device_mut().take_log()
+}
+
+fn timer_calls(log: &[Call]) -> Vec<Call> {
+    log.iter()
+        .filter(|call| matches!(call, Call::BeginTimerQuery(_) | Call::EndTimerQuery(_)))
+        .cloned()
+        .collect()
+}
+
+#[test]
+fn renderer_timing() {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    assert!(renderer.gpu_timer().is_none());
+    assert!(timer_calls(&render(&mut ctx, &mut renderer)).is_empty());
+
+    renderer.device_mut().clear_log();
+    renderer.enable_gpu_timing_with(2, 4).unwrap();
+    let (q0, q1) = (MockTimerQuery(0), MockTimerQuery(1));
+    assert_eq!(renderer.device().log(), [Call::CreateTimerQuery(q0), Call::CreateTimerQuery(q1)]);
+
+    // the queries enclose the UI pass
+    let log = render(&mut ctx, &mut renderer);
+    let begin = log.iter().position(|call| *call == Call::BeginTimerQuery(q0)).unwrap();
+    let end = log.iter().position(|call| *call == Call::EndTimerQuery(q0)).unwrap();
+    assert!(matches!(log[begin + 1], Call::SetViewport(_)));
+    assert!(log[begin..end].iter().any(|call| matches!(call, Call::DrawIndexed { .. })));
+    assert!(matches!(log[end + 1..], [Call::ApplyState(_)]));
+
+    let log = render(&mut ctx, &mut renderer);
+    assert_eq!(timer_calls(&log), [Call::BeginTimerQuery(q1), Call::EndTimerQuery(q1)]);
+    // both queries are in flight
+    assert!(timer_calls(&render(&mut ctx, &mut renderer)).is_empty());
+    assert_eq!(renderer.gpu_timer().unwrap().ring().dropped(), 1);
+
+    renderer.device_mut().complete_timer_query(q0, timestamps(2000, 2250));
+    let log = render(&mut ctx, &mut renderer);
+    assert_eq!(timer_calls(&log), [Call::BeginTimerQuery(q0), Call::EndTimerQuery(q0)]);
+    let timer = renderer.gpu_timer().unwrap();
+    assert_eq!(
+        timer.history().iter().collect::<Vec<_>>(),
+        [&GpuTiming { frame: 1, milliseconds: 0.25, latency: 3 }]
+    );
+
+    renderer.disable_gpu_timing();
+    assert!(renderer.gpu_timer().is_none());
+    assert!(timer_calls(&render(&mut ctx, &mut renderer)).is_empty());
+}
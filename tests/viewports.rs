@@ -0,0 +1,108 @@
+//! Secondary viewport rendering against the recording mock device.
+
+use imgui::{Condition, Window};
+use imgui_dx11_renderer::capture::FrameCapture;
+use imgui_dx11_renderer::device::Viewport;
+use imgui_dx11_renderer::mock::{Call, MockError, MockWindow, RecordingDevice};
+use imgui_dx11_renderer::plan::orthographic_projection;
+use imgui_dx11_renderer::viewports::Viewports;
+use imgui_dx11_renderer::Renderer;
+
+/// Captures a frame and moves its display to `display_pos`, the way imgui
+/// positions the draw data of a secondary viewport on the desktop.
+fn viewport_frame(ctx: &mut imgui::Context, display_pos: [f32; 2]) -> FrameCapture {
+    let build = |ui: &imgui::Ui| {
+        Window::new("Tool").position([10.0, 10.0], Condition::Always).build(ui, || {
+            ui.text("Dragged outside of the main window");
+        });
+    };
+    build(&ctx.frame());
+    let ui = ctx.frame();
+    build(&ui);
+    let mut bytes = FrameCapture::capture(ui.render()).to_bytes();
+    bytes[12..16].copy_from_slice(&display_pos[0].to_le_bytes());
+    bytes[16..20].copy_from_slice(&display_pos[1].to_le_bytes());
+    FrameCapture::from_bytes(&bytes).unwrap()
+}
+
+fn full_viewport(width: f32, height: f32) -> Call {
+    Call::SetViewport(Viewport { x: 0.0, y: 0.0, width, height })
+}
+
+fn projection(device: &RecordingDevice, log: &[Call]) -> [f32; 16] {
+    let cb = log
+        .iter()
+        .find_map(|call| match *call {
+            Call::SetConstantBuffer(cb) => Some(cb),
+            _ => None,
+        })
+        .unwrap();
+    let mut mvp = [0.0; 16];
+    for (v, bytes) in mvp.iter_mut().zip(device.buffer_data(cb).chunks_exact(4)) {
+        *v = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    mvp
+}
+
+#[test]
+fn viewport_lifecycle() {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = [320.0, 200.0];
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    let mut viewports = Viewports::new();
+    renderer.device_mut().clear_log();
+
+    viewports.create(&mut renderer, 7, &MockWindow(1), [320, 200]).unwrap();
+    viewports.create(&mut renderer, 9, &MockWindow(2), [100, 100]).unwrap();
+    assert_eq!(
+        renderer.device_mut().take_log(),
+        [
+            Call::CreateSurface { surface: 0, window: MockWindow(1), size: [320, 200] },
+            Call::CreateSurface { surface: 1, window: MockWindow(2), size: [100, 100] },
+        ]
+    );
+    assert_eq!(viewports.len(), 2);
+
+    let display_pos = [1500.0, 300.0];
+    let frame = viewport_frame(&mut ctx, display_pos);
+    let target = viewports.surface(7).unwrap().target();
+    viewports.render(&mut renderer, 7, frame.draw_data(), Some([0.0, 0.0, 0.0, 1.0])).unwrap();
+    viewports.present(&mut renderer, 7).unwrap();
+    let log = renderer.device_mut().take_log();
+    assert_eq!(log[1], Call::SetRenderTarget(target));
+    assert!(log.contains(&full_viewport(320.0, 200.0)));
+    assert_eq!(log.last(), Some(&Call::Present(0)));
+    let expected: Vec<f32> =
+        orthographic_projection(display_pos, [320.0, 200.0]).iter().flatten().copied().collect();
+    assert_eq!(projection(renderer.device(), &log), expected[..]);
+
+    viewports.resize(&mut renderer, 7, [640, 400]).unwrap();
+    viewports.render(&mut renderer, 7, frame.draw_data(), None).unwrap();
+    let log = renderer.device_mut().take_log();
+    assert_eq!(log[0], Call::ResizeSurface { surface: 0, size: [640, 400] });
+    assert!(log.contains(&full_viewport(640.0, 400.0)));
+
+    assert!(viewports.destroy(7));
+    assert!(!viewports.destroy(7));
+    assert!(!viewports.contains(7));
+    assert!(viewports.contains(9));
+}
+
+#[test]
+fn unknown_viewport() {
+    let mut ctx = imgui::Context::create();
+    ctx.io_mut().display_size = [320.0, 200.0];
+    let mut renderer = Renderer::with_device(&mut ctx, RecordingDevice::new()).unwrap();
+    let mut viewports = Viewports::new();
+    viewports.create(&mut renderer, 1, &MockWindow(1), [320, 200]).unwrap();
+    assert!(viewports.destroy(1));
+    renderer.device_mut().clear_log();
+
+    let err = Err(MockError::UnknownViewport(1));
+    assert_eq!(viewports.resize(&mut renderer, 1, [640, 400]), err);
+    let ui = ctx.frame();
+    assert_eq!(viewports.render(&mut renderer, 1, ui.render(), None), err);
+    assert_eq!(viewports.present(&mut renderer, 1), err);
+    assert!(renderer.device().log().is_empty());
+}